@@ -0,0 +1,492 @@
+use anyhow::{bail, Context};
+use cairo_lang_compiler::{
+    compile_prepared_db, db::RootDatabase, project::setup_project, CompilerConfig,
+};
+use cairo_lang_runner::{short_string::as_cairo_short_string, RunResultValue};
+use cairo_lang_sierra::{
+    extensions::core::{CoreConcreteLibfunc, CoreLibfunc, CoreType},
+    ids::ConcreteTypeId,
+    program::{Program, Statement, StatementIdx},
+    program_registry::ProgramRegistry,
+};
+use cairo_native::{
+    context::NativeContext,
+    debug::{with_statement_hook, DebugOptions, LiveVariable, SourceLocations},
+    executor::JitNativeExecutor,
+    metadata::gas::{GasMetadata, MetadataComputationConfig},
+    starknet_stub::StubSyscallHandler,
+};
+use clap::Parser;
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use utils::{args::parse_args, find_function, result_to_runresult};
+
+mod utils;
+
+const HELP: &str = "\
+Commands:
+  s, step [N]                     Execute N statements (1 by default).
+  n, next                         Execute until the next statement of the current function.
+  finish                          Execute until the current function returns.
+  c, continue                     Execute until the next breakpoint.
+  b, break <FUNCTION|STATEMENT>   Break at a function's entry or at a statement.
+  d, delete [N]                   Delete the breakpoint N, or every breakpoint.
+  i, info breakpoints             List the breakpoints.
+  p, print [VAR]                  Print the live variables, or only the variable VAR.
+  l, list                         List the statements around the current one.
+  h, help                         Print this message.
+  q, quit                         Abort the execution and exit.
+An empty line repeats the last command.
+
+`next` and `finish` count the Sierra function calls and returns, not the native stack frames:
+tail-recursive calls are compiled into loops, which share a single frame in a native debugger
+or backtrace, but still count as calls here.";
+
+/// Interactive step debugger for Sierra programs.
+///
+/// Compiles the program with a hook before every Sierra statement, runs it with the JIT and stops
+/// at the first statement. The execution can then be stepped through statement by statement,
+/// stopped at breakpoints and inspected using the commands read from the standard input (see
+/// `help`).
+///
+/// Exits with 1 if the compilation or run fails, otherwise 0.
+#[derive(Parser, Debug)]
+#[clap(version, verbatim_doc_comment)]
+struct Args {
+    /// The Cairo project path to compile and debug.
+    path: PathBuf,
+    /// In cases where gas is available, the amount of provided gas.
+    #[arg(long)]
+    available_gas: Option<u64>,
+    /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
+    #[arg(short = 'O', long, default_value_t = 0)]
+    opt_level: u8,
+    /// The function to run, given by the end of its name.
+    #[arg(long, default_value = "::main")]
+    function: String,
+    /// The arguments of the function, either as a JSON array or as comma-separated Cairo-like
+    /// literals (for example: `5, [1, 2], Option::Some(3)`).
+    #[arg(long)]
+    args: Option<String>,
+    /// Break at a function's entry, given by the end of its name, or at a Sierra statement. Can be
+    /// repeated.
+    #[arg(long = "break", value_name = "FUNCTION|STATEMENT_IDX")]
+    breakpoints: Vec<String>,
+    /// Run until the first breakpoint instead of stopping at the first statement.
+    #[arg(long)]
+    run: bool,
+}
+
+/// When the execution stops next, besides the breakpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepMode {
+    /// Stop after the given number of statements.
+    Step(usize),
+    /// Stop at the next statement whose call depth is at most the given one.
+    Next(usize),
+    /// Stop at the next statement whose call depth is below the given one.
+    Finish(usize),
+    /// Only stop at the breakpoints.
+    Continue,
+    /// Never stop again, because the input was closed.
+    Detached,
+}
+
+#[derive(Clone, Debug)]
+struct Breakpoint {
+    statement_idx: StatementIdx,
+    description: String,
+}
+
+struct Debugger {
+    program: Program,
+    registry: ProgramRegistry<CoreType, CoreLibfunc>,
+    source_locations: SourceLocations,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    next_breakpoint: usize,
+    mode: StepMode,
+    /// The number of calls being executed, and its change once the current statement completes.
+    depth: usize,
+    depth_change: isize,
+    last_command: String,
+}
+
+impl Debugger {
+    fn new(
+        program: Program,
+        source_locations: SourceLocations,
+        mode: StepMode,
+    ) -> anyhow::Result<Self> {
+        let registry = ProgramRegistry::new(&program)?;
+        Ok(Self {
+            program,
+            registry,
+            source_locations,
+            breakpoints: BTreeMap::new(),
+            next_breakpoint: 1,
+            mode,
+            depth: 0,
+            depth_change: 0,
+            last_command: String::new(),
+        })
+    }
+
+    /// Add a breakpoint at a function's entry, given by its full name or a path suffix of it, or at
+    /// a statement.
+    fn add_breakpoint(&mut self, target: &str) -> anyhow::Result<&Breakpoint> {
+        let breakpoint = match target.parse::<usize>() {
+            Ok(statement_idx) => {
+                if statement_idx >= self.program.statements.len() {
+                    bail!(
+                        "the program has only {} statements",
+                        self.program.statements.len()
+                    );
+                }
+                Breakpoint {
+                    statement_idx: StatementIdx(statement_idx),
+                    description: format!("statement {statement_idx}"),
+                }
+            }
+            Err(_) => {
+                let function = self
+                    .program
+                    .funcs
+                    .iter()
+                    .find(|function| {
+                        function.id.debug_name.as_ref().is_some_and(|name| {
+                            name == target || name.ends_with(&format!("::{target}"))
+                        })
+                    })
+                    .with_context(|| format!("no function named {target}"))?;
+                Breakpoint {
+                    statement_idx: function.entry_point,
+                    description: format!("{} (statement {})", function.id, function.entry_point),
+                }
+            }
+        };
+
+        let id = self.next_breakpoint;
+        self.next_breakpoint += 1;
+        Ok(self.breakpoints.entry(id).or_insert(breakpoint))
+    }
+
+    /// Called before every statement: stop if necessary and process the commands until the
+    /// execution is resumed.
+    fn on_statement(&mut self, statement_idx: StatementIdx, variables: &[LiveVariable]) {
+        self.depth = self.depth.saturating_add_signed(self.depth_change);
+        self.depth_change = match &self.program.statements[statement_idx.0] {
+            Statement::Invocation(invocation) => {
+                let libfunc = self.registry.get_libfunc(&invocation.libfunc_id);
+                match libfunc {
+                    Ok(
+                        CoreConcreteLibfunc::FunctionCall(_) | CoreConcreteLibfunc::CouponCall(_),
+                    ) => 1,
+                    _ => 0,
+                }
+            }
+            Statement::Return(_) => -1,
+        };
+
+        let at_breakpoint = self
+            .breakpoints
+            .values()
+            .any(|breakpoint| breakpoint.statement_idx == statement_idx);
+        let stop = match self.mode {
+            StepMode::Step(remaining) if remaining > 1 => {
+                self.mode = StepMode::Step(remaining - 1);
+                at_breakpoint
+            }
+            StepMode::Step(_) => true,
+            StepMode::Next(depth) => self.depth <= depth || at_breakpoint,
+            StepMode::Finish(depth) => self.depth < depth || at_breakpoint,
+            StepMode::Continue => at_breakpoint,
+            StepMode::Detached => false,
+        };
+        if !stop {
+            return;
+        }
+
+        self.print_statement(statement_idx);
+        let stdin = io::stdin();
+        loop {
+            print!("(sierra) ");
+            io::stdout().flush().ok();
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                // Run until the end once there are no more commands.
+                println!();
+                self.mode = StepMode::Detached;
+                return;
+            }
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            self.last_command.clone_from(&line);
+
+            match self.run_command(statement_idx, variables, &line) {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(e) => println!("Error: {e:#}"),
+            }
+        }
+    }
+
+    /// Run a command, and return whether the execution should be resumed.
+    fn run_command(
+        &mut self,
+        statement_idx: StatementIdx,
+        variables: &[LiveVariable],
+        line: &str,
+    ) -> anyhow::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(false);
+        };
+        let argument = words.next();
+
+        match command {
+            "s" | "step" => {
+                let count = argument.map(str::parse).transpose()?.unwrap_or(1);
+                self.mode = StepMode::Step(count);
+                return Ok(true);
+            }
+            "n" | "next" => {
+                self.mode = StepMode::Next(self.depth);
+                return Ok(true);
+            }
+            "finish" => {
+                self.mode = StepMode::Finish(self.depth);
+                return Ok(true);
+            }
+            "c" | "continue" => {
+                self.mode = StepMode::Continue;
+                return Ok(true);
+            }
+            "b" | "break" => {
+                let target = argument.context("missing function or statement")?;
+                let breakpoint = self.add_breakpoint(target)?;
+                println!("Breakpoint at {}.", breakpoint.description);
+            }
+            "d" | "delete" => match argument {
+                Some(id) => {
+                    self.breakpoints
+                        .remove(&id.parse()?)
+                        .with_context(|| format!("no breakpoint {id}"))?;
+                }
+                None => self.breakpoints.clear(),
+            },
+            "i" | "info" => {
+                if !matches!(argument, Some("b" | "break" | "breakpoints")) {
+                    bail!("unknown info command, try `info breakpoints`");
+                }
+                if self.breakpoints.is_empty() {
+                    println!("No breakpoints.");
+                }
+                for (id, breakpoint) in &self.breakpoints {
+                    println!("{id}: {}", breakpoint.description);
+                }
+            }
+            "p" | "print" => {
+                let var_id = argument
+                    .map(|var| var.trim_matches(['[', ']']).parse::<u64>())
+                    .transpose()?;
+                let mut found = false;
+                for variable in variables {
+                    if var_id.is_some_and(|id| id != variable.var_id.id) {
+                        continue;
+                    }
+                    found = true;
+
+                    let value = match variable.value(&self.registry)? {
+                        Some(value) => format!("{value:?}"),
+                        None => "<unavailable>".to_string(),
+                    };
+                    println!(
+                        "{}: {} = {value}",
+                        variable.var_id,
+                        self.type_name(&variable.type_id)
+                    );
+                }
+                if !found {
+                    match argument {
+                        Some(var) => bail!("no live variable {var}"),
+                        None => println!("No live variables."),
+                    }
+                }
+            }
+            "l" | "list" => {
+                let start = statement_idx.0.saturating_sub(3);
+                let end = (statement_idx.0 + 4).min(self.program.statements.len());
+                for idx in start..end {
+                    let marker = if idx == statement_idx.0 { "=>" } else { "  " };
+                    let breakpoint = if self
+                        .breakpoints
+                        .values()
+                        .any(|breakpoint| breakpoint.statement_idx.0 == idx)
+                    {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!(
+                        "{marker}{breakpoint}{idx:>6}: {}",
+                        self.program.statements[idx]
+                    );
+                }
+            }
+            "h" | "help" => println!("{HELP}"),
+            "q" | "quit" => std::process::exit(0),
+            _ => bail!("unknown command `{command}`, try `help`"),
+        }
+
+        Ok(false)
+    }
+
+    /// Print the statement, the function it belongs to and its Cairo source location.
+    fn print_statement(&self, statement_idx: StatementIdx) {
+        // The statements of each function follow its entry point.
+        let function = self
+            .program
+            .funcs
+            .iter()
+            .filter(|function| function.entry_point.0 <= statement_idx.0)
+            .max_by_key(|function| function.entry_point.0);
+
+        match function {
+            Some(function) => println!("Stopped at statement {statement_idx} in {}:", function.id),
+            None => println!("Stopped at statement {statement_idx}:"),
+        }
+        println!("  {}", self.program.statements[statement_idx.0]);
+        if let Some(location) = self.source_locations.get(statement_idx) {
+            println!("  at {}:{}:{}", location.file, location.line, location.col);
+        }
+    }
+
+    /// Return the name of a type as declared in the program.
+    fn type_name(&self, type_id: &ConcreteTypeId) -> String {
+        self.program
+            .type_declarations
+            .iter()
+            .find(|declaration| declaration.id.id == type_id.id)
+            .map_or_else(
+                || type_id.to_string(),
+                |declaration| declaration.id.to_string(),
+            )
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    // Configure logging and error handling.
+    tracing::subscriber::set_global_default(
+        FmtSubscriber::builder()
+            .with_env_filter(EnvFilter::from_default_env())
+            .finish(),
+    )?;
+
+    let args = Args::parse();
+
+    let mut db = RootDatabase::builder().detect_corelib().build()?;
+    let main_crate_ids = setup_project(&mut db, &args.path)?;
+
+    let sierra_program_with_debug = compile_prepared_db(
+        &db,
+        main_crate_ids,
+        CompilerConfig {
+            replace_ids: true,
+            ..Default::default()
+        },
+    )?;
+    let sierra_program = sierra_program_with_debug.program;
+
+    // Keep the Cairo source locations so that the stops can show the Cairo code.
+    let source_locations = SourceLocations::from(
+        sierra_program_with_debug
+            .debug_info
+            .statements_locations
+            .extract_statements_source_code_locations(&db),
+    );
+
+    let mut debugger = Debugger::new(
+        sierra_program.clone(),
+        source_locations.clone(),
+        if args.run {
+            StepMode::Continue
+        } else {
+            StepMode::Step(1)
+        },
+    )?;
+    for target in &args.breakpoints {
+        debugger
+            .add_breakpoint(target)
+            .with_context(|| format!("Failed to set a breakpoint at {target}."))?;
+    }
+
+    let native_context = NativeContext::new();
+    let native_module = native_context.compile_with_debug_options(
+        &sierra_program,
+        false,
+        Some(Default::default()),
+        DebugOptions::new()
+            .with_statement_hooks()
+            .with_source_locations(source_locations),
+    )?;
+    let executor = JitNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
+
+    let gas_metadata =
+        GasMetadata::new(&sierra_program, Some(MetadataComputationConfig::default()))?;
+
+    let func = find_function(&sierra_program, &args.function)?;
+
+    let func_args = match &args.args {
+        Some(input) => {
+            let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&sierra_program)?;
+            parse_args(&registry, func, input).with_context(|| "Failed to parse the arguments.")?
+        }
+        None => Vec::new(),
+    };
+
+    let initial_gas = gas_metadata
+        .get_initial_available_gas(&func.id, args.available_gas)
+        .with_context(|| "not enough gas to run")?;
+
+    let mut syscall_handler = StubSyscallHandler::default();
+    let result = with_statement_hook(
+        move |statement_idx, variables| debugger.on_statement(statement_idx, variables),
+        || {
+            executor.invoke_dynamic_with_syscall_handler(
+                &func.id,
+                &func_args,
+                Some(initial_gas),
+                &mut syscall_handler,
+            )
+        },
+    )
+    .with_context(|| "Failed to run the function.")?;
+
+    match result_to_runresult(&result)? {
+        RunResultValue::Success(values) => {
+            println!("Run completed successfully, returning {values:?}")
+        }
+        RunResultValue::Panic(values) => {
+            print!("Run panicked with [");
+            for value in &values {
+                match as_cairo_short_string(value) {
+                    Some(as_string) => print!("{value} ('{as_string}'), "),
+                    None => print!("{value}, "),
+                }
+            }
+            println!("].");
+        }
+    }
+    if let Some(gas) = result.remaining_gas {
+        println!("Remaining gas: {gas}");
+    }
+
+    Ok(())
+}
@@ -8,12 +8,18 @@ use cairo_lang_starknet::{
     compile::compile_contract_in_prepared_db, inline_macros::selector::SelectorMacro,
     plugin::StarkNetPlugin,
 };
-use cairo_native::context::NativeContext;
-use clap::Parser;
-use melior::ir::operation::OperationPrintingFlags;
+use cairo_native::{
+    context::NativeContext, module_to_assembly, module_to_llvm_ir, module_to_object,
+    utils::generate_function_name,
+};
+use clap::{Parser, ValueEnum};
+use melior::ir::{
+    attribute::StringAttribute, operation::OperationPrintingFlags, BlockLike, Module,
+};
 use std::{
     ffi::OsStr,
     fs,
+    io::{self, Write},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -34,22 +40,158 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let context = NativeContext::new();
     let program = load_program(Path::new(&args.input), args.starknet)?;
 
+    // Find the symbol of the selected function, if any.
+    let symbol = match &args.function {
+        Some(name) => {
+            let function = program
+                .funcs
+                .iter()
+                .find(|function| match function.id.debug_name.as_deref() {
+                    Some(debug_name) => {
+                        debug_name == name || debug_name.ends_with(&format!("::{name}"))
+                    }
+                    None => format!("f{}", function.id.id) == *name,
+                })
+                .ok_or_else(|| format!("function `{name}` not found"))?;
+            Some(generate_function_name(&function.id, false).into_owned())
+        }
+        None => None,
+    };
+
     // Compile the program.
-    let module = context.compile(&program, false, Some(Default::default()))?;
+    let module = match args.emit {
+        Emit::MlirPrepass => {
+            context.compile_without_passes(&program, false, Some(Default::default()))?
+        }
+        _ => context.compile(&program, false, Some(Default::default()))?,
+    };
+    let module = module.module();
+    let opt_level = args.opt_level.into();
+
+    // Generate the output.
+    let output = match args.emit {
+        Emit::MlirPrepass | Emit::Mlir => print_mlir(module, symbol.as_deref())?.into_bytes(),
+        Emit::LlvmIr => {
+            let llvm_ir = module_to_llvm_ir(module, opt_level)?;
+            match &symbol {
+                Some(symbol) => filter_lines(
+                    &llvm_ir,
+                    symbol,
+                    |line| {
+                        let (_, name) = line.strip_prefix("define ")?.split_once('@')?;
+                        match name.strip_prefix('"') {
+                            Some(name) => name.split_once('"').map(|(name, _)| name),
+                            None => name.split_once('(').map(|(name, _)| name),
+                        }
+                    },
+                    |line| line == "}",
+                ),
+                None => llvm_ir,
+            }
+            .into_bytes()
+        }
+        Emit::Asm => {
+            let assembly = module_to_assembly(module, opt_level)?;
+            match &symbol {
+                Some(symbol) => filter_lines(
+                    &assembly,
+                    symbol,
+                    |line| {
+                        line.strip_suffix(':')
+                            .filter(|_| !line.starts_with(char::is_whitespace))
+                    },
+                    |line| line.contains("-- End function"),
+                ),
+                None => assembly,
+            }
+            .into_bytes()
+        }
+        Emit::Obj => {
+            if symbol.is_some() {
+                return Err("`--function` can't be used when emitting an object file".into());
+            }
+            module_to_object(module, opt_level)?
+        }
+    };
 
     // Write the output.
-    let output_str = module
-        .module()
-        .as_operation()
-        .to_string_with_flags(OperationPrintingFlags::new().enable_debug_info(true, false))?;
     match args.output {
-        CompilerOutput::Stdout => println!("{output_str}"),
-        CompilerOutput::Path(path) => fs::write(path, &output_str)?,
+        CompilerOutput::Stdout => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&output)?;
+            if output.last() != Some(&b'\n') && args.emit != Emit::Obj {
+                writeln!(stdout)?;
+            }
+        }
+        CompilerOutput::Path(path) => fs::write(path, &output)?,
     }
 
     Ok(())
 }
 
+/// Prints the MLIR module with debug info. If a symbol is given, only the operations defining it
+/// (and its C interface wrapper) are printed.
+fn print_mlir(module: &Module, symbol: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    let flags = OperationPrintingFlags::new().enable_debug_info(true, false);
+    let Some(symbol) = symbol else {
+        return Ok(module.as_operation().to_string_with_flags(flags)?);
+    };
+
+    let mut output = String::new();
+    let mut operation = module.body().first_operation();
+    while let Some(current) = operation {
+        let name = current
+            .attribute("sym_name")
+            .ok()
+            .and_then(|x| StringAttribute::try_from(x).ok())
+            .map(|x| x.value());
+        if name.is_some_and(|name| is_symbol(name, symbol)) {
+            output.push_str(&current.to_string_with_flags(flags)?);
+            output.push('\n');
+        }
+
+        // SAFETY: The operation belongs to the module, which outlives the loop.
+        operation = unsafe { current.to_ref() }.next_in_block();
+    }
+
+    Ok(output)
+}
+
+/// Keeps only the blocks of lines defining the given symbol. A block starts with a line for which
+/// `start` returns the (possibly quoted) name of the symbol being defined, and ends with the first
+/// line for which `end` returns true.
+fn filter_lines<'a>(
+    input: &'a str,
+    symbol: &str,
+    start: impl Fn(&'a str) -> Option<&'a str>,
+    end: impl Fn(&'a str) -> bool,
+) -> String {
+    let mut output = String::new();
+    let mut in_block = false;
+    for line in input.lines() {
+        if !in_block {
+            in_block = start(line).is_some_and(|name| is_symbol(name.trim_matches('"'), symbol));
+        }
+        if in_block {
+            output.push_str(line);
+            output.push('\n');
+            in_block = !end(line);
+        }
+    }
+
+    output
+}
+
+/// Whether a symbol name is the given function's symbol or its C interface wrapper. Platforms
+/// which prefix symbols with an underscore are handled too.
+fn is_symbol(name: &str, symbol: &str) -> bool {
+    let name = name.strip_prefix("_mlir_ciface_").unwrap_or(name);
+    name == symbol
+        || name.strip_prefix('_').is_some_and(|name| {
+            name == symbol || name.strip_prefix("_mlir_ciface_") == Some(symbol)
+        })
+}
+
 fn load_program(path: &Path, is_contract: bool) -> Result<Program, Box<dyn std::error::Error>> {
     Ok(match path.extension().and_then(OsStr::to_str) {
         Some("cairo") if !is_contract => {
@@ -115,6 +257,33 @@ struct CmdLine {
     /// Compile a starknet contract
     #[clap(long)]
     starknet: bool,
+
+    /// The compilation stage to output.
+    #[clap(long, value_enum, default_value_t = Emit::Mlir)]
+    emit: Emit,
+
+    /// Optimization level (for LLVM IR, assembly and objects), Valid: 0, 1, 2, 3. Values higher
+    /// than 3 are considered as 3.
+    #[clap(short = 'O', long, default_value_t = 0)]
+    opt_level: u8,
+
+    /// Only output the code of the Sierra function with this name (or path suffix).
+    #[clap(long)]
+    function: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// The MLIR module generated from the Sierra program, before any pass.
+    MlirPrepass,
+    /// The MLIR module lowered to the LLVM dialect.
+    Mlir,
+    /// The LLVM IR, after the LLVM passes.
+    LlvmIr,
+    /// The assembly for the host target.
+    Asm,
+    /// An object file for the host target.
+    Obj,
 }
 
 #[derive(Clone, Debug)]
@@ -139,14 +308,6 @@ fn parse_output(input: &str) -> Result<CompilerOutput, String> {
     Ok(if input == "-" {
         CompilerOutput::Stdout
     } else {
-        CompilerOutput::Path(match Path::new(input).extension().and_then(OsStr::to_str) {
-            Some("mlir") => input.into(),
-            _ => {
-                return Err(
-                    "Output path expected to be `-` for stdout or have `mlir` as its extension."
-                        .to_string(),
-                )
-            }
-        })
+        CompilerOutput::Path(input.into())
     })
 }
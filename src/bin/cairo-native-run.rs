@@ -3,16 +3,31 @@ use cairo_lang_compiler::{
     compile_prepared_db, db::RootDatabase, project::setup_project, CompilerConfig,
 };
 use cairo_lang_runner::short_string::as_cairo_short_string;
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType},
+    program::StatementIdx,
+    program_registry::ProgramRegistry,
+};
+#[cfg(feature = "with-debug-utils")]
+use cairo_native::metadata::auto_breakpoint::AutoBreakpoint;
 use cairo_native::{
     context::NativeContext,
+    debug::{
+        take_debug_results, DebugOptions, DebugResults, ProfileMetric, SourceLocations, TraceLevel,
+    },
+    execution_result::ExecutionResult,
     executor::{AotNativeExecutor, JitNativeExecutor},
     metadata::gas::{GasMetadata, MetadataComputationConfig},
     starknet_stub::StubSyscallHandler,
 };
 use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use serde::Serialize;
+use starknet_types_core::felt::Felt;
+use std::{fs, path::PathBuf, time::Instant};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
-use utils::{find_function, result_to_runresult};
+use utils::{
+    args::parse_args, decode_panic_data, find_function, result_to_runresult, OutputFormat,
+};
 
 mod utils;
 
@@ -22,6 +37,21 @@ enum RunMode {
     Jit,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Metric {
+    Gas,
+    Time,
+}
+
+impl From<Metric> for ProfileMetric {
+    fn from(value: Metric) -> Self {
+        match value {
+            Metric::Gas => ProfileMetric::Gas,
+            Metric::Time => ProfileMetric::Time,
+        }
+    }
+}
+
 /// Command line args parser.
 /// Exits with 1 if the compilation or run fails, otherwise 0.
 #[derive(Parser, Debug)]
@@ -44,6 +74,65 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The arguments of the `main` function, either as a JSON array or as comma-separated Cairo-like
+    /// literals (for example: `5, [1, 2], Option::Some(3)`).
+    #[arg(long)]
+    args: Option<String>,
+    /// The format of the results printed to stdout.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Write the debug artifacts (Sierra, MLIR and, with AOT, the object file) into this directory.
+    #[arg(long)]
+    debug_dir: Option<PathBuf>,
+    /// Insert a debugger trap before this Sierra statement. Can be repeated.
+    #[arg(long, value_name = "STATEMENT_IDX")]
+    trap_at_stmt: Vec<usize>,
+    /// Write the JIT-compiled functions' symbols to `/tmp/perf-<pid>.map` for `perf` (JIT only).
+    #[arg(long)]
+    perf_map: bool,
+    /// Register the JIT-compiled functions' symbols with the GDB JIT interface (JIT only).
+    #[arg(long)]
+    gdb_jit: bool,
+    /// Profile every function and write the call stacks to this path, in the folded format used by
+    /// flamegraph tools.
+    #[arg(long)]
+    profile: Option<PathBuf>,
+    /// The metric used to weight the profile's call stacks: gas units or nanoseconds.
+    #[arg(long, value_enum, default_value_t = Metric::Gas, requires = "profile")]
+    profile_metric: Metric,
+    /// Record every executed Sierra statement and write the trace to this path, one statement per
+    /// line followed by the branch it took.
+    #[arg(long)]
+    trace: Option<PathBuf>,
+    /// Also record the values produced by every statement in the trace.
+    #[arg(long, requires = "trace")]
+    trace_values: bool,
+    /// Print the Cairo backtrace if the program panics.
+    #[arg(long)]
+    backtrace: bool,
+    /// Insert the breakpoints listed in this JSON file (JIT only).
+    #[cfg(feature = "with-debug-utils")]
+    #[arg(long, value_name = "CONFIG")]
+    breakpoints: Option<PathBuf>,
+}
+
+/// The results of a run, as printed with `--format json`.
+#[derive(Serialize)]
+struct RunReport<'a> {
+    success: bool,
+    /// The return value serialized as felts, or the panic data if the run panicked.
+    values: &'a [Felt],
+    /// The decoded panic data, if the run panicked.
+    panic_data: Option<Vec<String>>,
+    #[serde(flatten)]
+    result: &'a ExecutionResult,
+    /// The data collected by the debugging instrumentation, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug: Option<&'a DebugResults>,
+    /// The time spent compiling the Sierra program to native code, in seconds.
+    compilation_time: f64,
+    /// The time spent running `main`, in seconds.
+    execution_time: f64,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -59,21 +148,67 @@ fn main() -> anyhow::Result<()> {
     let mut db = RootDatabase::builder().detect_corelib().build()?;
     let main_crate_ids = setup_project(&mut db, &args.path)?;
 
-    let sierra_program = compile_prepared_db(
+    let sierra_program_with_debug = compile_prepared_db(
         &db,
         main_crate_ids,
         CompilerConfig {
             replace_ids: true,
             ..Default::default()
         },
-    )?
-    .program;
+    )?;
+    let sierra_program = sierra_program_with_debug.program;
 
+    // Keep the Cairo source locations so that debuggers can show the Cairo code.
+    let source_locations = SourceLocations::from(
+        sierra_program_with_debug
+            .debug_info
+            .statements_locations
+            .extract_statements_source_code_locations(&db),
+    );
+
+    let compilation_start = Instant::now();
     let native_context = NativeContext::new();
 
+    let mut debug_options = DebugOptions {
+        trap_at_statements: args
+            .trap_at_stmt
+            .iter()
+            .copied()
+            .map(StatementIdx)
+            .collect(),
+        perf_map: args.perf_map,
+        gdb_jit_interface: args.gdb_jit,
+        profiler: args.profile.is_some(),
+        trace: args.trace.is_some().then_some(if args.trace_values {
+            TraceLevel::Values
+        } else {
+            TraceLevel::Statements
+        }),
+        panic_backtraces: args.backtrace,
+        ..Default::default()
+    }
+    .with_source_locations(source_locations.clone());
+    #[cfg(feature = "with-debug-utils")]
+    if let Some(breakpoints) = &args.breakpoints {
+        debug_options = debug_options.with_auto_breakpoint(
+            AutoBreakpoint::from_config(breakpoints).context("failed to load the breakpoints")?,
+        );
+    }
+    if let Some(debug_dir) = &args.debug_dir {
+        debug_options = debug_options.with_artifacts_dir(debug_dir);
+        if let Some(name) = args.path.file_stem() {
+            debug_options = debug_options.with_program_name(name.to_string_lossy());
+        }
+    }
+
     // Compile the sierra program into a MLIR module.
     let native_module = native_context
-        .compile(&sierra_program, false, Some(Default::default()))
+        .compile_with_debug_options(
+            &sierra_program,
+            false,
+            Some(Default::default()),
+            debug_options,
+        )
         .unwrap();
 
     let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _> = match args.run_mode {
@@ -103,22 +238,77 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
+    let compilation_time = compilation_start.elapsed();
+
     let gas_metadata =
         GasMetadata::new(&sierra_program, Some(MetadataComputationConfig::default())).unwrap();
 
     let func = find_function(&sierra_program, "::main")?;
 
+    let func_args = match &args.args {
+        Some(input) => {
+            let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&sierra_program)?;
+            parse_args(&registry, func, input).with_context(|| "Failed to parse the arguments.")?
+        }
+        None => Vec::new(),
+    };
+
     let initial_gas = gas_metadata
         .get_initial_available_gas(&func.id, args.available_gas)
         .with_context(|| "not enough gas to run")?;
 
     let mut syscall_handler = StubSyscallHandler::default();
 
-    let result = native_executor(&func.id, &[], Some(initial_gas), &mut syscall_handler)
-        .with_context(|| "Failed to run the function.")?;
+    let execution_start = Instant::now();
+    let result = native_executor(
+        &func.id,
+        &func_args,
+        Some(initial_gas),
+        &mut syscall_handler,
+    )
+    .with_context(|| "Failed to run the function.")?;
+
+    let execution_time = execution_start.elapsed();
+    let debug = take_debug_results();
+
+    if let Some(path) = &args.profile {
+        let profile = debug
+            .as_ref()
+            .and_then(|debug| debug.profile.as_ref())
+            .with_context(|| "The run didn't record a profile.")?;
+        fs::write(path, profile.to_folded(args.profile_metric.into()))
+            .with_context(|| format!("Failed to write the profile to {}.", path.display()))?;
+    }
+
+    if let Some(path) = &args.trace {
+        let trace = debug
+            .as_ref()
+            .and_then(|debug| debug.trace.as_ref())
+            .with_context(|| "The run didn't record a trace.")?;
+        fs::write(path, trace.to_string())
+            .with_context(|| format!("Failed to write the trace to {}.", path.display()))?;
+    }
 
     let run_result = result_to_runresult(&result)?;
 
+    if args.format == OutputFormat::Json {
+        let (success, values) = match &run_result {
+            cairo_lang_runner::RunResultValue::Success(values) => (true, values),
+            cairo_lang_runner::RunResultValue::Panic(values) => (false, values),
+        };
+        let report = RunReport {
+            success,
+            values,
+            panic_data: (!success).then(|| decode_panic_data(values.clone().into_iter())),
+            result: &result,
+            debug: debug.as_ref(),
+            compilation_time: compilation_time.as_secs_f64(),
+            execution_time: execution_time.as_secs_f64(),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
     match run_result {
         cairo_lang_runner::RunResultValue::Success(values) => {
             println!("Run completed successfully, returning {values:?}")
@@ -131,7 +321,11 @@ fn main() -> anyhow::Result<()> {
                     None => print!("{value}, "),
                 }
             }
-            println!("].")
+            println!("].");
+            if let Some(backtrace) = debug.as_ref().and_then(|debug| debug.backtrace.as_ref()) {
+                println!("Cairo backtrace:");
+                print!("{}", backtrace.format(Some(&source_locations)));
+            }
         }
     }
     if let Some(gas) = result.remaining_gas {
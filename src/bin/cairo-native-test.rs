@@ -1,4 +1,4 @@
-use anyhow::bail;
+use anyhow::{bail, Context};
 use cairo_lang_compiler::{
     db::RootDatabase,
     diagnostics::DiagnosticsReporter,
@@ -7,13 +7,17 @@ use cairo_lang_compiler::{
 use cairo_lang_filesystem::cfg::{Cfg, CfgSet};
 use cairo_lang_starknet::starknet_plugin_suite;
 use cairo_lang_test_plugin::{compile_test_prepared_db, test_plugin_suite, TestsCompilationConfig};
+use cairo_native::context::NativeContext;
 use clap::Parser;
 use colored::Colorize;
-use std::path::PathBuf;
+use std::{fs, num::NonZeroUsize, path::PathBuf, thread, time::Duration};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 use utils::{
-    test::{display_tests_summary, filter_test_cases, run_tests},
-    RunArgs, RunMode,
+    test::{
+        display_test_list, display_tests_summary, display_tests_summary_json, filter_test_cases,
+        lcov_report, load_event_abis, run_tests, write_junit_report, TestFilter,
+    },
+    OutputFormat, RunArgs, RunMode,
 };
 
 mod utils;
@@ -34,6 +38,15 @@ struct Args {
     /// The filter for the tests, running only tests containing the filter string.
     #[arg(short, long, default_value_t = String::default())]
     filter: String,
+    /// Only run the test whose full name is exactly the filter.
+    #[arg(long, conflicts_with = "regex")]
+    exact: bool,
+    /// Interpret the filter as a regular expression.
+    #[arg(long)]
+    regex: bool,
+    /// List the tests instead of running them.
+    #[arg(long)]
+    list: bool,
     /// Should we run ignored tests as well.
     #[arg(long, default_value_t = false)]
     include_ignored: bool,
@@ -49,6 +62,34 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The format of the results printed to stdout.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// The maximum number of tests running at the same time. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+    /// Stop running tests after the first failure.
+    #[arg(long)]
+    fail_fast: bool,
+    /// Consider the tests running for longer than this many seconds as failed.
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Also write the results as a JUnit XML report to the given path.
+    #[arg(long)]
+    junit: Option<PathBuf>,
+    /// Collect the tests' line coverage and write it as an LCOV tracefile to the given path.
+    #[arg(long)]
+    coverage: Option<PathBuf>,
+    /// Print the Cairo backtraces of the tests which fail with a panic.
+    #[arg(long)]
+    backtrace: bool,
+    /// Print the events emitted by every test.
+    #[arg(long)]
+    events: bool,
+    /// A contract class (as compiled or as declared) whose ABI is used to decode the events. May
+    /// be given more than once.
+    #[arg(long = "abi", requires = "events")]
+    abis: Vec<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -83,7 +124,7 @@ fn main() -> anyhow::Result<()> {
     let test_config = TestsCompilationConfig {
         starknet: args.starknet,
         add_statements_functions: false,
-        add_statements_code_locations: false,
+        add_statements_code_locations: args.coverage.is_some(),
         contract_declarations: None,
         contract_crate_ids: None,
         executable_crate_ids: None,
@@ -101,20 +142,53 @@ fn main() -> anyhow::Result<()> {
         build_test_compilation,
         args.include_ignored,
         args.ignored,
-        args.filter.clone(),
+        &TestFilter::new(args.filter.clone(), args.exact, args.regex)?,
     );
 
+    if args.list {
+        display_test_list(&compiled.metadata.named_tests, args.format);
+        return Ok(());
+    }
+
+    // The context is leaked so that the threads of timed out tests can outlive the run.
+    let native_context: &'static NativeContext = Box::leak(Box::new(NativeContext::new()));
     let summary = run_tests(
+        native_context,
         compiled.metadata.named_tests,
         compiled.sierra_program.program,
         compiled.metadata.function_set_costs,
         RunArgs {
             run_mode: args.run_mode.clone(),
             opt_level: args.opt_level,
+            format: args.format,
+            jobs: args.jobs.map_or_else(
+                || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+                NonZeroUsize::get,
+            ),
+            fail_fast: args.fail_fast,
+            timeout: args.timeout.map(Duration::from_secs),
+            coverage: args.coverage.is_some(),
+            backtraces: args.backtrace,
+            event_abis: args
+                .events
+                .then(|| load_event_abis(&args.abis))
+                .transpose()?,
         },
     )?;
 
-    display_tests_summary(&summary, filtered_out);
+    match args.format {
+        OutputFormat::Text => display_tests_summary(&summary, filtered_out),
+        OutputFormat::Json => display_tests_summary_json(&summary, filtered_out)?,
+    }
+    if let Some(junit) = &args.junit {
+        let suite_name = args.path.file_stem().unwrap_or_default().to_string_lossy();
+        write_junit_report(&[(&*suite_name, &summary)], junit)?;
+    }
+    if let Some(coverage) = &args.coverage {
+        let lcov = lcov_report(&summary, compiled.sierra_program.debug_info.as_ref())?;
+        fs::write(coverage, lcov)
+            .with_context(|| format!("failed to write the coverage to {}", coverage.display()))?;
+    }
     if !summary.failed.is_empty() {
         bail!(
             "test result: {}. {} passed; {} failed; {} ignored",
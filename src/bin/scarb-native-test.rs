@@ -1,13 +1,25 @@
 use anyhow::Context;
 use cairo_lang_sierra::program::VersionedProgram;
 use cairo_lang_test_plugin::{TestCompilation, TestCompilationMetadata};
+use cairo_native::context::NativeContext;
 use clap::{Parser, ValueEnum};
 use scarb_metadata::{Metadata, MetadataCommand, ScarbCommand};
 use scarb_ui::args::PackagesFilter;
-use std::{collections::HashSet, env, fs, path::Path};
+use std::{
+    collections::HashSet,
+    env, fs,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
 use utils::{
-    test::{display_tests_summary, filter_test_cases, find_testable_targets, run_tests},
-    RunArgs, RunMode,
+    test::{
+        display_test_list, display_tests_summary, display_tests_summary_json, filter_test_cases,
+        find_testable_targets, lcov_report, load_event_abis, run_tests, write_junit_report,
+        TestFilter,
+    },
+    OutputFormat, RunArgs, RunMode,
 };
 
 mod utils;
@@ -23,6 +35,15 @@ struct Args {
     /// Run only tests whose name contain FILTER.
     #[arg(short, long, default_value = "")]
     filter: String,
+    /// Run only the test whose full name is exactly FILTER.
+    #[arg(long, conflicts_with = "regex")]
+    exact: bool,
+    /// Interpret FILTER as a regular expression.
+    #[arg(long)]
+    regex: bool,
+    /// List the tests instead of running them.
+    #[arg(long)]
+    list: bool,
     /// Run ignored and not ignored tests.
     #[arg(long, default_value_t = false)]
     include_ignored: bool,
@@ -38,6 +59,39 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The format of the results printed to stdout. With JSON, the summary of every target is
+    /// printed as its own document.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// The maximum number of tests running at the same time. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+    /// Stop running tests after the first failure.
+    #[arg(long)]
+    fail_fast: bool,
+    /// Consider the tests running for longer than this many seconds as failed.
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Also write the results as a JUnit XML report to the given path, with a test suite for every
+    /// target.
+    #[arg(long)]
+    junit: Option<PathBuf>,
+    /// Collect the tests' line coverage and write it as an LCOV tracefile to the given path.
+    ///
+    /// Requires `unstable-add-statements-code-locations-debug-info = true` in the profile's
+    /// `cairo` section of the `Scarb.toml`.
+    #[arg(long)]
+    coverage: Option<PathBuf>,
+    /// Print the Cairo backtraces of the tests which fail with a panic.
+    #[arg(long)]
+    backtrace: bool,
+    /// Print the events emitted by every test.
+    #[arg(long)]
+    events: bool,
+    /// A contract class (as compiled or as declared) whose ABI is used to decode the events. May
+    /// be given more than once.
+    #[arg(long = "abi", requires = "events")]
+    abis: Vec<PathBuf>,
 }
 
 #[derive(ValueEnum, Clone, Debug, Default)]
@@ -86,8 +140,13 @@ fn main() -> anyhow::Result<()> {
         })
         .collect::<Vec<_>>();
 
-    // Build only the filtered packages.
-    ScarbCommand::new()
+    // Build only the filtered packages. Scarb's messages are kept machine-readable along with the
+    // results.
+    let mut build_command = ScarbCommand::new();
+    if args.format == OutputFormat::Json {
+        build_command.arg("--json");
+    }
+    build_command
         .arg("build")
         .arg("--test")
         .env("SCARB_TARGET_NAMES", target_names.clone().join(","))
@@ -103,9 +162,27 @@ fn main() -> anyhow::Result<()> {
         .unwrap_or(default_target_dir)
         .join(profile);
 
+    let test_filter = TestFilter::new(args.filter.clone(), args.exact, args.regex)?;
+    let jobs = args.jobs.map_or_else(
+        || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        NonZeroUsize::get,
+    );
+    let event_abis = args
+        .events
+        .then(|| load_event_abis(&args.abis))
+        .transpose()?;
+
+    // The context is shared by every target. It's leaked so that the threads of timed out tests can
+    // outlive the run.
+    let native_context: &'static NativeContext = Box::leak(Box::new(NativeContext::new()));
+
     let mut deduplicator = TargetGroupDeduplicator::default();
+    let mut lcov = String::new();
+    let mut suites = Vec::new();
     for package in matched {
-        println!("testing {} ...", package.name);
+        if args.format == OutputFormat::Text {
+            println!("testing {} ...", package.name);
+        }
 
         // Iterate over the filtered targets.
         for target in find_testable_targets(&package) {
@@ -124,27 +201,59 @@ fn main() -> anyhow::Result<()> {
             }
             let compiled = deserialize_test_compilation(target_dir.as_std_path(), name.clone())?;
 
-            let (compiled, filtered_out) = filter_test_cases(
-                compiled,
-                args.include_ignored,
-                args.ignored,
-                args.filter.clone(),
-            );
+            let (compiled, filtered_out) =
+                filter_test_cases(compiled, args.include_ignored, args.ignored, &test_filter);
+
+            if args.list {
+                display_test_list(&compiled.metadata.named_tests, args.format);
+                continue;
+            }
 
             let summary = run_tests(
+                native_context,
                 compiled.metadata.named_tests,
                 compiled.sierra_program.program,
                 compiled.metadata.function_set_costs,
                 RunArgs {
                     run_mode: args.run_mode.clone(),
                     opt_level: args.opt_level,
+                    format: args.format,
+                    jobs,
+                    fail_fast: args.fail_fast,
+                    timeout: args.timeout.map(Duration::from_secs),
+                    coverage: args.coverage.is_some(),
+                    backtraces: args.backtrace,
+                    event_abis: event_abis.clone(),
                 },
             )?;
 
-            display_tests_summary(&summary, filtered_out);
+            match args.format {
+                OutputFormat::Text => display_tests_summary(&summary, filtered_out),
+                OutputFormat::Json => display_tests_summary_json(&summary, filtered_out)?,
+            }
+            if args.coverage.is_some() {
+                lcov.push_str(&lcov_report(
+                    &summary,
+                    compiled.sierra_program.debug_info.as_ref(),
+                )?);
+            }
+            suites.push((name, summary));
         }
     }
 
+    if let Some(junit) = &args.junit {
+        let suites = suites
+            .iter()
+            .map(|(name, summary)| (name.as_str(), summary))
+            .collect::<Vec<_>>();
+        write_junit_report(&suites, junit)?;
+    }
+
+    if let Some(coverage) = &args.coverage {
+        fs::write(coverage, lcov)
+            .with_context(|| format!("failed to write the coverage to {}", coverage.display()))?;
+    }
+
     Ok(())
 }
 
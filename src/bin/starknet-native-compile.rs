@@ -1,30 +1,73 @@
 use anyhow::{anyhow, bail, Context};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
 
 use cairo_lang_sierra::program::Program;
 use cairo_lang_starknet_classes::compiler_version::VersionId;
 use cairo_lang_starknet_classes::contract_class::ContractClass;
-use cairo_native::executor::AotContractExecutor;
+use cairo_native::{
+    executor::AotContractExecutor,
+    starknet::class_hash::{compute_class_hash, native_artifact_hash, parse_contract_class},
+    OptLevel,
+};
 use clap::Parser;
 
 /// Given a Sierra file (as saved in Starknet's contract tree), extracts the sierra_program from
 /// felts into readable Sierra code, compiles it to native, and saves the result to the given output
 /// path.
+///
+/// If the path is a directory (or a manifest, with `--manifest`), every contract class in it is
+/// compiled in parallel into the output directory. Artifacts are named after their class, the
+/// optimization level and the cairo-native version, so that classes which are already up to date
+/// are skipped. An `index.json` mapping every class hash to its artifact path is written alongside.
 #[derive(Parser, Debug)]
 #[clap(version, verbatim_doc_comment)]
 struct Args {
-    /// The path of the Sierra file to compile.
+    /// The path of the Sierra file to compile, or of a directory of Sierra files.
     path: PathBuf,
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
-    /// The output file path.
+    /// The output file path, or the output directory when compiling in batch.
     output: PathBuf,
+    /// Treat the path as a manifest listing a Sierra file per line, relative to the manifest.
+    #[arg(long)]
+    manifest: bool,
+    /// The maximum number of classes compiled at the same time. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+    /// Recompile the classes which are already up to date.
+    #[arg(long)]
+    force: bool,
+}
+
+/// The index written to the output directory when compiling in batch.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BatchIndex {
+    /// The artifact of every compiled class, by class hash.
+    artifacts: BTreeMap<String, PathBuf>,
+    /// The error of every class which failed to compile, by path.
+    failures: BTreeMap<PathBuf, String>,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let (contract_class, sierra_program, sierra_version) =
+    if args.manifest || args.path.is_dir() {
+        return compile_batch(&args);
+    }
+
+    let (contract_class, _, sierra_program, sierra_version) =
         load_sierra_program_from_file(&args.path)?;
 
     AotContractExecutor::new_into(
@@ -39,6 +82,172 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Compiles every class from the directory or manifest into the output directory.
+fn compile_batch(args: &Args) -> anyhow::Result<()> {
+    let paths = if args.manifest {
+        read_manifest(&args.path)?
+    } else {
+        list_classes(&args.path, &args.output)?
+    };
+    fs::create_dir_all(&args.output).context("Error creating the output directory.")?;
+
+    let jobs = match args.jobs {
+        Some(jobs) => jobs.get(),
+        None => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    };
+    let opt_level = OptLevel::from(args.opt_level);
+
+    let next_index = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(paths.len()));
+    thread::scope(|s| {
+        for _ in 0..jobs.min(paths.len()) {
+            s.spawn(|| {
+                while let Some(path) = paths.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+                    // A panic only fails its class, so that the index is written nonetheless.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        compile_class(path, &args.output, opt_level, args.force)
+                    }))
+                    .unwrap_or_else(|payload| {
+                        let message = payload
+                            .downcast_ref::<&str>()
+                            .copied()
+                            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                            .unwrap_or("unknown cause");
+                        Err(anyhow!("The compilation panicked: {message}"))
+                    });
+                    match &result {
+                        Ok((_, artifact_path, true)) => {
+                            println!("compiled {} -> {}", path.display(), artifact_path.display())
+                        }
+                        Ok((_, _, false)) => println!("up to date {}", path.display()),
+                        Err(e) => eprintln!("failed {}: {e:#}", path.display()),
+                    }
+                    results.lock().unwrap().push((path, result));
+                }
+            });
+        }
+    });
+
+    // Update the existing index, if any, so that it keeps track of previous batches.
+    let index_path = args.output.join("index.json");
+    let mut index = fs::read_to_string(&index_path)
+        .ok()
+        .and_then(|x| serde_json::from_str::<BatchIndex>(&x).ok())
+        .unwrap_or_default();
+
+    let (mut compiled, mut up_to_date) = (0, 0);
+    for (path, result) in results.into_inner().unwrap() {
+        match result {
+            Ok((class_hash, artifact_path, is_compiled)) => {
+                match is_compiled {
+                    true => compiled += 1,
+                    false => up_to_date += 1,
+                }
+                index.failures.remove(path);
+                index
+                    .artifacts
+                    .insert(format!("{class_hash:#x}"), artifact_path);
+            }
+            Err(e) => {
+                index.failures.insert(path.clone(), format!("{e:#}"));
+            }
+        }
+    }
+    fs::write(&index_path, serde_json::to_string_pretty(&index)?)
+        .context("Error writing the batch index.")?;
+
+    let failed = paths.len() - compiled - up_to_date;
+    println!("{compiled} compiled; {up_to_date} up to date; {failed} failed");
+    if failed != 0 {
+        bail!(
+            "{failed} classes failed to compile, see {}",
+            index_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Compiles a class into the output directory unless it's already up to date.
+///
+/// Returns the class hash, the artifact path and whether it has been compiled.
+fn compile_class(
+    path: &Path,
+    output_dir: &Path,
+    opt_level: OptLevel,
+    force: bool,
+) -> anyhow::Result<(starknet_types_core::felt::Felt, PathBuf, bool)> {
+    let (contract_class, abi, sierra_program, sierra_version) =
+        load_sierra_program_from_file(path)?;
+    let class_hash = compute_class_hash(&contract_class, &abi);
+
+    let artifact_path = output_dir.join(format!(
+        "{:#x}.so",
+        native_artifact_hash(class_hash, opt_level)
+    ));
+    if !force
+        && artifact_path.exists()
+        && artifact_path.with_extension("json").exists()
+        && !artifact_path.with_extension("lock").exists()
+    {
+        return Ok((class_hash, artifact_path, false));
+    }
+
+    AotContractExecutor::new_into(
+        &sierra_program,
+        &contract_class.entry_points_by_type,
+        sierra_version,
+        &artifact_path,
+        opt_level,
+    )
+    .context("Error compiling Sierra program.")?
+    .ok_or_else(|| anyhow!("The artifact is locked by another process."))?;
+
+    Ok((class_hash, artifact_path, true))
+}
+
+/// Lists the Sierra files (`*.json`) in a directory.
+///
+/// When it's also the output directory, the files written by previous batches (the index and the
+/// contract info next to every artifact) are skipped.
+fn list_classes(dir: &Path, output_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let is_output_dir = output_dir.exists()
+        && fs::canonicalize(dir).context("Error reading the input directory.")?
+            == fs::canonicalize(output_dir).context("Error reading the output directory.")?;
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).context("Error reading the input directory.")? {
+        let path = entry?.path();
+        if !path.is_file() || !path.extension().is_some_and(|x| x == "json") {
+            continue;
+        }
+        if is_output_dir
+            && (path.file_name().is_some_and(|x| x == "index.json")
+                || path.with_extension("so").exists())
+        {
+            continue;
+        }
+
+        paths.push(path);
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Reads the Sierra file paths from a manifest. Empty lines and lines starting with `#` are
+/// ignored.
+fn read_manifest(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    Ok(fs::read_to_string(path)
+        .context("Error reading the manifest.")?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base_dir.join(line))
+        .collect())
+}
+
 /// Extracts the first 3 felts from the Sierra program and parses them into a VersionId.
 fn get_sierra_version_from_program<F>(sierra_program: &[F]) -> anyhow::Result<VersionId>
 where
@@ -73,15 +282,15 @@ where
     })
 }
 
-/// Given a Sierra file path, loads the contract class from it, extracts the sierra version from the
-/// first 3 felts of the compressed sierra_program, and extracts the compressed sierra_program into
-/// readable sierra code.
+/// Given a Sierra file path, loads the contract class and its declared ABI from it, extracts the
+/// sierra version from the first 3 felts of the compressed sierra_program, and extracts the
+/// compressed sierra_program into readable sierra code.
 fn load_sierra_program_from_file(
-    path: &PathBuf,
-) -> anyhow::Result<(ContractClass, Program, VersionId)> {
+    path: &Path,
+) -> anyhow::Result<(ContractClass, String, Program, VersionId)> {
     let raw_contract_class = std::fs::read_to_string(path).context("Error reading Sierra file.")?;
 
-    let contract_class: ContractClass = serde_json::from_str(&raw_contract_class)
+    let (contract_class, abi) = parse_contract_class(&raw_contract_class)
         .context("Error deserializing Sierra file into contract class.")?;
     let raw_sierra_program: Vec<_> = contract_class
         .sierra_program
@@ -92,6 +301,7 @@ fn load_sierra_program_from_file(
     let sierra_version = get_sierra_version_from_program(&raw_sierra_program)?;
     Ok((
         contract_class.clone(),
+        abi,
         contract_class
             .extract_sierra_program()
             .context("Error extracting Sierra program from contract class.")?,
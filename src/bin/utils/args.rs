@@ -0,0 +1,596 @@
+//! Parsing of entry point arguments given from the command line.
+//!
+//! Arguments may be given either as a JSON array, with one element per argument, or as a
+//! comma-separated list of Cairo-like literals:
+//!
+//!   - Numbers: `5`, `-3`, `0x1f` or short strings of up to 31 bytes (`'hello'`). Valid for felts,
+//!     integers (including `u256`), bounded integers, addresses and class hashes.
+//!   - Booleans: `true` or `false`.
+//!   - Arrays and spans: `[1, 2, 3]`.
+//!   - Tuples and structs: `(1, 2)`, `Point { x: 1, y: 2 }` or `Point(1, 2)`. Fields are matched by
+//!     position since Sierra doesn't keep their names.
+//!   - Enums: `Option::Some(5)`, `None`. Variant names are only known for `Option`, `Result` and
+//!     `bool`, any other variant must be given by its index (`Enum::2(5)`).
+//!
+//! In JSON, numbers may also be strings (for values which don't fit in a JSON number), tuples and
+//! structs are arrays or objects, and enums are objects with a single field named after the
+//! variant (`{"Some": 5}`) or strings for variants without data (`"None"`).
+//!
+//! The arguments are converted into [`Value`]s according to the entry point's signature. Builtins
+//! are skipped since they're provided by the executor.
+
+use anyhow::{anyhow, bail, Context};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarkNetTypeConcrete,
+    },
+    ids::ConcreteTypeId,
+    program::Function,
+    program_registry::ProgramRegistry,
+};
+use cairo_native::Value;
+use num_bigint::{BigInt, Sign};
+use num_traits::{Num, ToPrimitive};
+use starknet_types_core::felt::Felt;
+use std::{iter::Peekable, str::Chars};
+
+/// An argument as written by the user, before knowing its type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Arg {
+    Number(BigInt),
+    Bool(bool),
+    /// Arrays, tuples and structs with positional fields.
+    List(Vec<Arg>),
+    /// Structs with named fields.
+    Fields(Vec<(String, Arg)>),
+    Variant {
+        name: String,
+        value: Option<Box<Arg>>,
+    },
+}
+
+/// Parse the arguments of a function according to its signature.
+pub fn parse_args(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    function: &Function,
+    input: &str,
+) -> anyhow::Result<Vec<Value>> {
+    // Only inputs starting with an array may be JSON, and those which aren't valid JSON are parsed
+    // as literals (for example, `[1, 2], 3`).
+    let json = match input.trim_start().starts_with('[') {
+        true => serde_json::from_str::<Vec<serde_json::Value>>(input).ok(),
+        false => None,
+    };
+    let args = match json {
+        Some(values) => values
+            .into_iter()
+            .map(json_to_arg)
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        None => LiteralParser::new(input).parse_list()?,
+    };
+
+    let mut param_types = Vec::new();
+    for ty in &function.signature.param_types {
+        if !is_builtin(registry.get_type(ty)?) {
+            param_types.push(ty);
+        }
+    }
+    if param_types.len() != args.len() {
+        bail!(
+            "expected {} arguments, but {} were given",
+            param_types.len(),
+            args.len()
+        );
+    }
+
+    param_types
+        .into_iter()
+        .zip(args)
+        .enumerate()
+        .map(|(i, (ty, arg))| {
+            arg_to_value(registry, ty, &arg).with_context(|| format!("invalid argument #{i}"))
+        })
+        .collect()
+}
+
+fn is_builtin(ty: &CoreTypeConcrete) -> bool {
+    matches!(
+        ty,
+        CoreTypeConcrete::Bitwise(_)
+            | CoreTypeConcrete::EcOp(_)
+            | CoreTypeConcrete::GasBuiltin(_)
+            | CoreTypeConcrete::BuiltinCosts(_)
+            | CoreTypeConcrete::RangeCheck(_)
+            | CoreTypeConcrete::RangeCheck96(_)
+            | CoreTypeConcrete::Pedersen(_)
+            | CoreTypeConcrete::Poseidon(_)
+            | CoreTypeConcrete::Coupon(_)
+            | CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_))
+            | CoreTypeConcrete::SegmentArena(_)
+    )
+}
+
+fn json_to_arg(value: serde_json::Value) -> anyhow::Result<Arg> {
+    Ok(match value {
+        serde_json::Value::Number(x) => Arg::Number(parse_number(&x.to_string())?),
+        serde_json::Value::Bool(x) => Arg::Bool(x),
+        serde_json::Value::String(x) => match parse_number(&x) {
+            Ok(x) => Arg::Number(x),
+            Err(_) => Arg::Variant {
+                name: x,
+                value: None,
+            },
+        },
+        serde_json::Value::Array(values) => Arg::List(
+            values
+                .into_iter()
+                .map(json_to_arg)
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        serde_json::Value::Object(fields) => Arg::Fields(
+            fields
+                .into_iter()
+                .map(|(name, value)| Ok((name, json_to_arg(value)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        serde_json::Value::Null => bail!("null is not a valid argument"),
+    })
+}
+
+fn parse_number(input: &str) -> anyhow::Result<BigInt> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, input),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => BigInt::from_str_radix(hex, 16),
+        None => BigInt::from_str_radix(digits, 10),
+    }
+    .map_err(|_| anyhow!("invalid number `{input}`"))?;
+
+    Ok(if negative { -value } else { value })
+}
+
+/// A recursive descent parser for the Cairo-like literal syntax.
+struct LiteralParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> LiteralParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+        }
+    }
+
+    /// Parse a comma-separated list of values until the end of the input.
+    fn parse_list(&mut self) -> anyhow::Result<Vec<Arg>> {
+        self.skip_whitespace();
+        if self.chars.peek().is_none() {
+            return Ok(Vec::new());
+        }
+
+        let values = self.parse_items(None)?;
+        match self.chars.next() {
+            None => Ok(values),
+            Some(c) => bail!("unexpected `{c}`"),
+        }
+    }
+
+    /// Parse comma-separated values until the closing delimiter (which is consumed) or, if there
+    /// is none, the end of the input. A trailing comma is allowed.
+    fn parse_items(&mut self, closing: Option<char>) -> anyhow::Result<Vec<Arg>> {
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if closing.is_some() && self.chars.peek().copied() == closing {
+                self.chars.next();
+                return Ok(values);
+            }
+
+            values.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                Some(&c) if Some(c) == closing => {
+                    self.chars.next();
+                    return Ok(values);
+                }
+                None if closing.is_none() => return Ok(values),
+                Some(&c) => bail!("unexpected `{c}`"),
+                None => bail!("unexpected end of input"),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> anyhow::Result<Arg> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('[') => {
+                self.chars.next();
+                Ok(Arg::List(self.parse_items(Some(']'))?))
+            }
+            Some('(') => {
+                self.chars.next();
+                Ok(Arg::List(self.parse_items(Some(')'))?))
+            }
+            Some('\'') => {
+                self.chars.next();
+                let value = self.take_while(|c| c != '\'');
+                if self.chars.next() != Some('\'') {
+                    bail!("unterminated short string");
+                }
+                if value.len() > 31 {
+                    bail!("short string '{value}' is longer than 31 bytes");
+                }
+                Ok(Arg::Number(BigInt::from_bytes_be(
+                    Sign::Plus,
+                    value.as_bytes(),
+                )))
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let value = self.take_while(|c| c == '-' || c.is_ascii_alphanumeric());
+                Ok(Arg::Number(parse_number(&value)?))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => self.parse_path_value(),
+            Some(c) => bail!("unexpected `{c}`"),
+            None => bail!("unexpected end of input"),
+        }
+    }
+
+    /// Parse a value starting with a path: booleans, enum variants and structs.
+    fn parse_path_value(&mut self) -> anyhow::Result<Arg> {
+        let path = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == ':');
+        match path.as_str() {
+            "true" => return Ok(Arg::Bool(true)),
+            "false" => return Ok(Arg::Bool(false)),
+            _ => {}
+        }
+
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.peek() == Some(&'}') {
+                        self.chars.next();
+                        return Ok(Arg::Fields(fields));
+                    }
+
+                    let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                    self.skip_whitespace();
+                    if name.is_empty() || self.chars.next() != Some(':') {
+                        bail!("expected a field name followed by `:` in `{path}`");
+                    }
+                    fields.push((name, self.parse_value()?));
+
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Arg::Fields(fields)),
+                        _ => bail!("expected `,` or `}}` in `{path}`"),
+                    }
+                }
+            }
+            Some('(') => {
+                self.chars.next();
+                let mut values = self.parse_items(Some(')'))?;
+                Ok(Arg::Variant {
+                    name: path,
+                    value: Some(Box::new(match values.len() {
+                        1 => values.remove(0),
+                        _ => Arg::List(values),
+                    })),
+                })
+            }
+            _ => Ok(Arg::Variant {
+                name: path,
+                value: None,
+            }),
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut value = String::new();
+        while let Some(c) = self.chars.next_if(|c| predicate(*c)) {
+            value.push(c);
+        }
+        value
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+/// Convert an argument into a value of the given type.
+fn arg_to_value(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ty: &ConcreteTypeId,
+    arg: &Arg,
+) -> anyhow::Result<Value> {
+    let type_name = ty.debug_name.as_deref().unwrap_or_default();
+    let mismatch = || anyhow!("expected a value of type `{ty}`, found {arg:?}");
+
+    Ok(match registry.get_type(ty)? {
+        CoreTypeConcrete::Felt252(_)
+        | CoreTypeConcrete::StarkNet(
+            StarkNetTypeConcrete::ClassHash(_)
+            | StarkNetTypeConcrete::ContractAddress(_)
+            | StarkNetTypeConcrete::StorageBaseAddress(_)
+            | StarkNetTypeConcrete::StorageAddress(_),
+        ) => Value::Felt252(Felt::from(number(arg).ok_or_else(mismatch)?)),
+        CoreTypeConcrete::Bytes31(_) => {
+            let value = number(arg).ok_or_else(mismatch)?;
+            let bytes = Felt::from(&value).to_bytes_be();
+            if bytes[0] != 0 {
+                bail!("{value} doesn't fit in a bytes31");
+            }
+            Value::Bytes31(bytes[1..].try_into()?)
+        }
+        CoreTypeConcrete::Uint8(_) => Value::Uint8(integer(arg, BigInt::to_u8)?),
+        CoreTypeConcrete::Uint16(_) => Value::Uint16(integer(arg, BigInt::to_u16)?),
+        CoreTypeConcrete::Uint32(_) => Value::Uint32(integer(arg, BigInt::to_u32)?),
+        CoreTypeConcrete::Uint64(_) => Value::Uint64(integer(arg, BigInt::to_u64)?),
+        CoreTypeConcrete::Uint128(_) => Value::Uint128(integer(arg, BigInt::to_u128)?),
+        CoreTypeConcrete::Sint8(_) => Value::Sint8(integer(arg, BigInt::to_i8)?),
+        CoreTypeConcrete::Sint16(_) => Value::Sint16(integer(arg, BigInt::to_i16)?),
+        CoreTypeConcrete::Sint32(_) => Value::Sint32(integer(arg, BigInt::to_i32)?),
+        CoreTypeConcrete::Sint64(_) => Value::Sint64(integer(arg, BigInt::to_i64)?),
+        CoreTypeConcrete::Sint128(_) => Value::Sint128(integer(arg, BigInt::to_i128)?),
+        CoreTypeConcrete::BoundedInt(info) => {
+            let value = number(arg).ok_or_else(mismatch)?;
+            if value < info.range.lower || value >= info.range.upper {
+                bail!("{value} is out of the range of `{ty}`");
+            }
+            Value::BoundedInt {
+                value: Felt::from(value),
+                range: info.range.clone(),
+            }
+        }
+        CoreTypeConcrete::Array(info) => match arg {
+            Arg::List(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| arg_to_value(registry, &info.ty, value))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            _ => return Err(mismatch()),
+        },
+        CoreTypeConcrete::Snapshot(info)
+        | CoreTypeConcrete::Box(info)
+        | CoreTypeConcrete::NonZero(info)
+        | CoreTypeConcrete::Nullable(info) => arg_to_value(registry, &info.ty, arg)?,
+        CoreTypeConcrete::Struct(info) => {
+            // `u256` may be written as a single number.
+            if let (true, Some(value)) = (type_name.contains("core::integer::u256"), number(arg)) {
+                let value = value
+                    .to_biguint()
+                    .filter(|value| value.bits() <= 256)
+                    .ok_or_else(|| anyhow!("{value} doesn't fit in a u256"))?;
+                let digits = value.to_u64_digits();
+                let limb = |i: usize| digits.get(i).copied().unwrap_or_default() as u128;
+                return Ok(Value::Struct {
+                    fields: vec![
+                        Value::Uint128(limb(0) | (limb(1) << 64)),
+                        Value::Uint128(limb(2) | (limb(3) << 64)),
+                    ],
+                    debug_name: ty.debug_name.as_ref().map(ToString::to_string),
+                });
+            }
+
+            // Single member structs (like spans) may be written as their member.
+            if let [member] = info.members.as_slice() {
+                if let Ok(value) = arg_to_value(registry, member, arg) {
+                    return Ok(Value::Struct {
+                        fields: vec![value],
+                        debug_name: ty.debug_name.as_ref().map(ToString::to_string),
+                    });
+                }
+            }
+
+            let fields = match arg {
+                Arg::List(values) if values.len() == info.members.len() => values.iter().collect(),
+                Arg::Fields(fields) if fields.len() == info.members.len() => {
+                    fields.iter().map(|(_, value)| value).collect()
+                }
+                // `Point(1, 2)`: the payload is a list unless there's a single value.
+                Arg::Variant {
+                    value: Some(value), ..
+                } => match &**value {
+                    Arg::List(values)
+                        if info.members.len() != 1 && values.len() == info.members.len() =>
+                    {
+                        values.iter().collect()
+                    }
+                    value if info.members.len() == 1 => vec![value],
+                    _ => return Err(mismatch()),
+                },
+                Arg::Variant { value: None, .. } if info.members.is_empty() => Vec::new(),
+                _ => return Err(mismatch()),
+            };
+
+            Value::Struct {
+                fields: info
+                    .members
+                    .iter()
+                    .zip(fields)
+                    .map(|(member, value)| arg_to_value(registry, member, value))
+                    .collect::<anyhow::Result<_>>()?,
+                debug_name: ty.debug_name.as_ref().map(ToString::to_string),
+            }
+        }
+        CoreTypeConcrete::Enum(info) => {
+            let (tag, value) = match arg {
+                Arg::Bool(value) if type_name.contains("core::bool") => (*value as usize, None),
+                Arg::Variant { name, value } => (variant_index(type_name, name)?, value.as_deref()),
+                Arg::Fields(fields) if fields.len() == 1 => {
+                    (variant_index(type_name, &fields[0].0)?, Some(&fields[0].1))
+                }
+                _ => return Err(mismatch()),
+            };
+            let variant_ty = info
+                .variants
+                .get(tag)
+                .ok_or_else(|| anyhow!("variant #{tag} not found in `{ty}`"))?;
+
+            Value::Enum {
+                tag,
+                value: Box::new(match value {
+                    Some(value) => arg_to_value(registry, variant_ty, value)?,
+                    None => arg_to_value(registry, variant_ty, &Arg::List(Vec::new()))?,
+                }),
+                debug_name: ty.debug_name.as_ref().map(ToString::to_string),
+            }
+        }
+        _ => bail!("arguments of type `{ty}` are not supported"),
+    })
+}
+
+fn number(arg: &Arg) -> Option<BigInt> {
+    match arg {
+        Arg::Number(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn integer<T>(arg: &Arg, convert: impl Fn(&BigInt) -> Option<T>) -> anyhow::Result<T> {
+    let value = number(arg).ok_or_else(|| anyhow!("expected an integer, found {arg:?}"))?;
+    convert(&value).ok_or_else(|| anyhow!("{value} is out of range"))
+}
+
+/// Find the index of an enum variant given its name.
+fn variant_index(type_name: &str, name: &str) -> anyhow::Result<usize> {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    if let Ok(index) = name.parse() {
+        return Ok(index);
+    }
+
+    let variants: &[&str] = if type_name.contains("core::option::Option") {
+        &["Some", "None"]
+    } else if type_name.contains("core::result::Result") {
+        &["Ok", "Err"]
+    } else if type_name.contains("core::bool") {
+        &["False", "True"]
+    } else {
+        &[]
+    };
+
+    variants
+        .iter()
+        .position(|variant| *variant == name)
+        .ok_or_else(|| anyhow!("unknown variant `{name}`, use its index instead"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_lang_sierra::ProgramParser;
+
+    fn parse(input: &str) -> anyhow::Result<Vec<Value>> {
+        let program = ProgramParser::new()
+            .parse(
+                r#"
+                type felt252 = felt252;
+                type u8 = u8;
+                type i32 = i32;
+                type u128 = u128;
+                type Unit = Struct<ut@Tuple>;
+                type Array<felt252> = Array<felt252>;
+                type Snapshot<Array<felt252>> = Snapshot<Array<felt252>>;
+                type core::array::Span::<core::felt252> = Struct<ut@core::array::Span::<core::felt252>, Snapshot<Array<felt252>>>;
+                type core::integer::u256 = Struct<ut@core::integer::u256, u128, u128>;
+                type Point = Struct<ut@Point, i32, i32>;
+                type core::option::Option::<core::integer::u8> = Enum<ut@core::option::Option::<core::integer::u8>, u8, Unit>;
+                type RangeCheck = RangeCheck;
+
+                return();
+
+                main@0(rc: RangeCheck, a: felt252, b: core::array::Span::<core::felt252>, c: core::integer::u256, d: Point, e: core::option::Option::<core::integer::u8>) -> ();
+                "#,
+            )
+            .unwrap();
+        let registry = ProgramRegistry::new(&program).unwrap();
+        parse_args(&registry, &program.funcs[0], input)
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        let values = parse("-1, [1, 0x2, 'a'], 0x100000000000000000000000000000002, Point { x: -5, y: 6 }, Option::Some(7)").unwrap();
+        assert_eq!(
+            values,
+            [
+                Value::Felt252(Felt::from(-1)),
+                Value::Struct {
+                    fields: vec![Value::Array(vec![
+                        Value::Felt252(1.into()),
+                        Value::Felt252(2.into()),
+                        Value::Felt252(97.into()),
+                    ])],
+                    debug_name: None,
+                },
+                Value::Struct {
+                    fields: vec![Value::Uint128(2), Value::Uint128(1)],
+                    debug_name: None,
+                },
+                Value::Struct {
+                    fields: vec![Value::Sint32(-5), Value::Sint32(6)],
+                    debug_name: None,
+                },
+                Value::Enum {
+                    tag: 0,
+                    value: Box::new(Value::Uint8(7)),
+                    debug_name: None,
+                },
+            ]
+        );
+
+        assert_eq!(parse("1, [], 0, Point(-5, 6), None").unwrap()[3], values[3]);
+        assert_eq!(
+            parse("1, [], 0, (1, 2), None").unwrap()[4],
+            Value::Enum {
+                tag: 1,
+                value: Box::new(Value::Struct {
+                    fields: vec![],
+                    debug_name: None
+                }),
+                debug_name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        assert_eq!(
+            parse(r#"["0x10", [1], "5", {"x": 1, "y": 2}, {"Some": 3}]"#).unwrap(),
+            parse("16, [1], 5, Point { x: 1, y: 2 }, Option::Some(3)").unwrap(),
+        );
+        assert_eq!(
+            parse(r#"[1, [], 0, [1, 2], "None"]"#).unwrap(),
+            parse("1, [], 0, (1, 2), None").unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("1, [], 0, (1, 2)").is_err());
+        assert!(parse("1, [], 0, (1, 2), Option::Some(256)").is_err());
+        assert!(parse("1, [], 0, (1, 2), Option::Other").is_err());
+        assert!(parse("1, [], 0, (1, 2, 3), None").is_err());
+        assert!(parse("1, [, 0, (1, 2), None").is_err());
+        assert!(parse("1, [], 0, Point(1, 2, 3), None").is_err());
+        assert!(parse("'abcdefghijklmnopqrstuvwxyz012345', [], 0, (1, 2), None").is_err());
+
+        // Scalars aren't mistaken for JSON.
+        assert_eq!(
+            parse("5").unwrap_err().to_string(),
+            "expected 5 arguments, but 1 were given"
+        );
+    }
+}
@@ -6,19 +6,33 @@ use cairo_lang_runner::{casm_run::format_next_item, RunResultValue};
 use cairo_lang_sierra::program::{Function, Program};
 use cairo_native::{
     execution_result::ExecutionResult,
-    starknet::{Secp256k1Point, Secp256r1Point},
+    starknet::{abi::ContractAbi, Secp256k1Point, Secp256r1Point},
     Value,
 };
 use clap::ValueEnum;
 use itertools::Itertools;
 use starknet_types_core::felt::Felt;
-use std::vec::IntoIter;
+use std::{time::Duration, vec::IntoIter};
 
+pub mod args;
 pub mod test;
 
 pub(super) struct RunArgs {
     pub run_mode: RunMode,
     pub opt_level: u8,
+    pub format: OutputFormat,
+    /// The maximum number of tests running at the same time.
+    pub jobs: usize,
+    /// Stop running tests after the first failure.
+    pub fail_fast: bool,
+    /// The time after which a running test is considered failed.
+    pub timeout: Option<Duration>,
+    /// Instrument the tests to collect their statement coverage.
+    pub coverage: bool,
+    /// Instrument the tests to report the Cairo backtraces of their panics.
+    pub backtraces: bool,
+    /// Report the events emitted by the tests, decoded with these ABIs when one matches.
+    pub event_abis: Option<Vec<ContractAbi>>,
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -27,6 +41,16 @@ pub enum RunMode {
     Jit,
 }
 
+/// How the results are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// A single JSON document, printed once everything has finished.
+    Json,
+}
+
 /// Find the function ending with `name_suffix` in the program.
 pub fn find_function<'a>(
     sierra_program: &'a Program,
@@ -59,6 +83,15 @@ pub fn format_for_panic(mut felts: IntoIter<Felt>) -> String {
     format!("Panicked with {panic_values_string}.")
 }
 
+/// Decodes the given panic data into its items, either strings or (short string) felts.
+pub fn decode_panic_data(mut felts: IntoIter<Felt>) -> Vec<String> {
+    let mut items = Vec::new();
+    while let Some(item) = format_next_item(&mut felts) {
+        items.push(item.get());
+    }
+    items
+}
+
 /// Convert the execution result to a run result.
 pub fn result_to_runresult(result: &ExecutionResult) -> anyhow::Result<RunResultValue> {
     let is_success;
@@ -221,6 +254,17 @@ mod tests {
         assert!(find_function(&ProgramParser::new().parse("").unwrap(), "Func2").is_err());
     }
 
+    #[test]
+    fn test_decode_panic_data() {
+        assert_eq!(
+            decode_panic_data(
+                vec![Felt::from_bytes_be_slice(b"Out of gas"), Felt::from(1)].into_iter()
+            ),
+            ["0x4f7574206f6620676173 ('Out of gas')", "0x1"]
+        );
+        assert!(decode_panic_data(Vec::new().into_iter()).is_empty());
+    }
+
     #[test]
     fn test_result_to_runresult_enum_nonpanic() {
         // Tests the conversion of a non-panic enum result to a `RunResultValue::Success`.
@@ -1,7 +1,12 @@
-use super::{find_function, format_for_panic, result_to_runresult, RunArgs, RunMode};
-use anyhow::Context;
+use super::{
+    decode_panic_data, find_function, format_for_panic, result_to_runresult, OutputFormat, RunArgs,
+    RunMode,
+};
+use anyhow::{anyhow, bail, Context};
 use cairo_lang_runner::RunResultValue;
-use cairo_lang_sierra::{extensions::gas::CostTokenType, ids::FunctionId, program::Program};
+use cairo_lang_sierra::{
+    debug_info::DebugInfo, extensions::gas::CostTokenType, ids::FunctionId, program::Program,
+};
 use cairo_lang_test_plugin::{
     test_config::{PanicExpectation, TestExpectation},
     TestConfig,
@@ -10,23 +15,133 @@ use cairo_lang_test_plugin::{TestCompilation, TestCompilationMetadata};
 use cairo_lang_utils::{casts::IntoOrPanic, ordered_hash_map::OrderedHashMap};
 use cairo_native::{
     context::NativeContext,
+    debug::{take_debug_results, Coverage, DebugOptions, PanicBacktrace, SourceLocations},
+    execution_result::ExecutionResult,
     executor::{AotNativeExecutor, JitNativeExecutor},
     metadata::gas::{GasMetadata, MetadataComputationConfig},
-    starknet_stub::StubSyscallHandler,
+    starknet::{
+        abi::{ContractAbi, DecodedEvent},
+        class_hash::parse_contract_class,
+    },
+    starknet_stub::{StubEvent, StubSyscallHandler},
+    Value,
 };
 use colored::Colorize;
 use itertools::Itertools;
 use num_traits::ToPrimitive;
+use regex::Regex;
 #[cfg(feature = "scarb")]
 use scarb_metadata::{PackageMetadata, TargetMetadata};
-use std::sync::Mutex;
+use serde::Serialize;
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A native executor shared between the threads running the tests.
+type SharedExecutor = dyn Fn(
+        &FunctionId,
+        &[Value],
+        Option<u64>,
+        &mut StubSyscallHandler,
+    ) -> cairo_native::error::Result<ExecutionResult>
+    + Send
+    + Sync;
 
 /// Summary data of the ran tests.
 pub struct TestsSummary {
     pub passed: Vec<String>,
     pub failed: Vec<String>,
     pub ignored: Vec<String>,
-    pub failed_run_results: Vec<RunResultValue>,
+    /// The report of every test, in the order they were given.
+    pub reports: Vec<TestReport>,
+    /// The time spent compiling the tests.
+    pub compilation_time: Duration,
+    /// The statement coverage of all the tests, if they were instrumented and none timed out.
+    pub coverage: Option<Coverage>,
+}
+
+/// The report of a single test, for the machine-readable outputs.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestReport {
+    pub name: String,
+    pub status: TestReportStatus,
+    /// The estimated gas usage, if relevant.
+    pub gas_usage: Option<i64>,
+    /// The time spent running the test, in seconds.
+    pub duration: f64,
+    /// Why the test failed.
+    pub failure: Option<String>,
+    /// The decoded panic data, if the test panicked.
+    pub panic_data: Option<Vec<String>>,
+    /// The events emitted by the test, if they're being reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<ReportedEvent>>,
+}
+
+/// An event emitted by a test, decoded if one of the ABIs given to the runner matches it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ReportedEvent {
+    Decoded(DecodedEvent),
+    Raw { keys: Vec<Felt>, data: Vec<Felt> },
+}
+
+impl ReportedEvent {
+    fn new(event: StubEvent, abis: &[ContractAbi]) -> Self {
+        abis.iter().find_map(|abi| event.decode(abi).ok()).map_or(
+            Self::Raw {
+                keys: event.keys,
+                data: event.data,
+            },
+            Self::Decoded,
+        )
+    }
+}
+
+impl fmt::Display for ReportedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decoded(event) => write!(f, "{event}"),
+            Self::Raw { keys, data } => write!(
+                f,
+                "keys: [{}], data: [{}]",
+                keys.iter().map(Felt::to_hex_string).join(", "),
+                data.iter().map(Felt::to_hex_string).join(", ")
+            ),
+        }
+    }
+}
+
+/// Load the ABIs of the given contract classes, to decode the events emitted by the tests.
+pub fn load_event_abis(paths: &[PathBuf]) -> anyhow::Result<Vec<ContractAbi>> {
+    paths
+        .iter()
+        .map(|path| {
+            let (_, abi) = fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|class_json| Ok(parse_contract_class(&class_json)?))
+                .with_context(|| format!("failed to read the contract class {}", path.display()))?;
+            let abi = serde_json::from_str(&abi)
+                .with_context(|| format!("invalid ABI in the contract class {}", path.display()))?;
+            Ok(ContractAbi::new(&abi))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestReportStatus {
+    Passed,
+    Failed,
+    Ignored,
 }
 
 /// The result of a ran test.
@@ -35,6 +150,14 @@ struct TestResult {
     status: TestStatus,
     /// The gas usage of the run if relevant.
     gas_usage: Option<i64>,
+    /// The time spent running the test.
+    duration: Duration,
+    /// The panic data, if the test panicked.
+    panic_data: Option<Vec<Felt>>,
+    /// The Cairo backtrace of the panic, if the test panicked and backtraces were enabled.
+    backtrace: Option<PanicBacktrace>,
+    /// The events emitted by the test.
+    events: Vec<StubEvent>,
 }
 
 /// The status of a ran test.
@@ -43,6 +166,36 @@ enum TestStatus {
     Fail(RunResultValue),
 }
 
+/// How tests are selected by name.
+pub enum TestFilter {
+    /// Tests whose name contains the string.
+    Contains(String),
+    /// The test with exactly this (full) name.
+    Exact(String),
+    /// Tests whose name matches the regular expression.
+    Regex(Regex),
+}
+
+impl TestFilter {
+    /// Build a filter from the command line arguments, `exact` and `regex` being exclusive.
+    pub fn new(filter: String, exact: bool, regex: bool) -> anyhow::Result<Self> {
+        Ok(match (exact, regex) {
+            (false, false) => Self::Contains(filter),
+            (true, false) => Self::Exact(filter),
+            (false, true) => Self::Regex(Regex::new(&filter).context("invalid test filter")?),
+            (true, true) => bail!("a test filter can't be both exact and a regex"),
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Contains(filter) => name.contains(filter.as_str()),
+            Self::Exact(filter) => name == filter,
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
 /// Find all testable targets in the Scarb package.
 #[cfg(feature = "scarb")]
 pub fn find_testable_targets(package: &PackageMetadata) -> Vec<&TargetMetadata> {
@@ -59,14 +212,14 @@ pub fn find_testable_targets(package: &PackageMetadata) -> Vec<&TargetMetadata>
 /// * `compiled` - Compiled test cases with metadata.
 /// * `include_ignored` - Include ignored tests as well.
 /// * `ignored` - Run ignored tests only.
-/// * `filter` - Include only tests containing the filter string.
+/// * `filter` - Include only tests matching the filter.
 /// # Returns
 /// * (`TestCompilation`, `usize`) - The filtered test cases and the number of filtered out cases.
 pub fn filter_test_cases(
     compiled: TestCompilation,
     include_ignored: bool,
     ignored: bool,
-    filter: String,
+    filter: &TestFilter,
 ) -> (TestCompilation, usize) {
     let total_tests_count = compiled.metadata.named_tests.len();
     let named_tests = compiled
@@ -82,7 +235,7 @@ pub fn filter_test_cases(
             }
             (func, test)
         })
-        .filter(|(name, _)| name.contains(&filter))
+        .filter(|(name, _)| filter.matches(name))
         .collect_vec();
     let filtered_out = total_tests_count - named_tests.len();
     let tests = TestCompilation {
@@ -107,173 +260,658 @@ pub fn display_tests_summary(summary: &TestsSummary, filtered_out: usize) {
         );
     } else {
         println!("failures:");
-        for (failure, run_result) in summary
-            .failed
-            .iter()
-            .zip_eq(summary.failed_run_results.clone())
-        {
-            print!("   {failure} - ");
-            match run_result {
-                RunResultValue::Success(_) => {
-                    println!("expected panic but finished successfully.");
-                }
-                RunResultValue::Panic(values) => {
-                    println!("{}", format_for_panic(values.into_iter()));
-                }
+        for report in &summary.reports {
+            if let Some(failure) = &report.failure {
+                println!("   {} - {failure}", report.name);
             }
         }
         println!();
     }
 }
 
+/// Display the name of the tests, without running them.
+pub fn display_test_list(named_tests: &[(String, TestConfig)], format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            for (name, _) in named_tests {
+                println!("{name}: test");
+            }
+            println!();
+            println!("{} tests", named_tests.len());
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&named_tests.iter().map(|(name, _)| name).collect_vec())
+                .unwrap()
+        ),
+    }
+}
+
+/// Display the summary of the ran tests as a JSON document.
+pub fn display_tests_summary_json(
+    summary: &TestsSummary,
+    filtered_out: usize,
+) -> anyhow::Result<()> {
+    #[derive(Serialize)]
+    struct JsonSummary<'a> {
+        passed: usize,
+        failed: usize,
+        ignored: usize,
+        filtered_out: usize,
+        /// The time spent compiling the tests, in seconds.
+        compilation_time: f64,
+        tests: &'a [TestReport],
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&JsonSummary {
+            passed: summary.passed.len(),
+            failed: summary.failed.len(),
+            ignored: summary.ignored.len(),
+            filtered_out,
+            compilation_time: summary.compilation_time.as_secs_f64(),
+            tests: &summary.reports,
+        })?
+    );
+    Ok(())
+}
+
+/// Project the tests' statement coverage onto the Cairo source lines, as an LCOV tracefile.
+///
+/// The tests must have been run with coverage instrumentation and compiled with the statements'
+/// code locations in their debug info.
+pub fn lcov_report(
+    summary: &TestsSummary,
+    debug_info: Option<&DebugInfo>,
+) -> anyhow::Result<String> {
+    let coverage = summary
+        .coverage
+        .as_ref()
+        .context("the tests were not run with coverage instrumentation, or some timed out")?;
+    let source_locations = debug_info
+        .and_then(|debug_info| SourceLocations::from_annotations(&debug_info.annotations))
+        .context("the tests' Sierra program has no statements code locations in its debug info")?;
+
+    Ok(coverage.to_lcov(&source_locations))
+}
+
+/// Write the summaries of the ran tests as a JUnit XML report, with a test suite for each of them.
+pub fn write_junit_report(suites: &[(&str, &TestsSummary)], path: &Path) -> anyhow::Result<()> {
+    fs::write(path, junit_report(suites))
+        .with_context(|| format!("failed to write the JUnit report to {}", path.display()))
+}
+
+fn junit_report(suites: &[(&str, &TestsSummary)]) -> String {
+    let counts = |reports: usize, failures: usize, skipped: usize, time: f64| {
+        format!(r#"tests="{reports}" failures="{failures}" skipped="{skipped}" time="{time:.6}""#)
+    };
+    let time = |summary: &TestsSummary| summary.reports.iter().map(|x| x.duration).sum::<f64>();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites {}>",
+        counts(
+            suites.iter().map(|(_, x)| x.reports.len()).sum(),
+            suites.iter().map(|(_, x)| x.failed.len()).sum(),
+            suites.iter().map(|(_, x)| x.ignored.len()).sum(),
+            suites.iter().map(|(_, x)| time(x)).sum(),
+        )
+    )
+    .unwrap();
+    for (suite_name, summary) in suites {
+        writeln!(
+            xml,
+            r#"  <testsuite name="{}" {}>"#,
+            xml_escape(suite_name),
+            counts(
+                summary.reports.len(),
+                summary.failed.len(),
+                summary.ignored.len(),
+                time(summary),
+            )
+        )
+        .unwrap();
+        for report in &summary.reports {
+            let (class_name, name) = report
+                .name
+                .rsplit_once("::")
+                .unwrap_or(("", report.name.as_str()));
+            write!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" time="{:.6}">"#,
+                xml_escape(name),
+                xml_escape(class_name),
+                report.duration
+            )
+            .unwrap();
+            if let Some(gas_usage) = report.gas_usage {
+                write!(
+                    xml,
+                    r#"<properties><property name="gas_usage" value="{gas_usage}"/></properties>"#
+                )
+                .unwrap();
+            }
+            match report.status {
+                TestReportStatus::Passed => {}
+                TestReportStatus::Failed => write!(
+                    xml,
+                    r#"<failure message="{}"/>"#,
+                    xml_escape(report.failure.as_deref().unwrap_or_default())
+                )
+                .unwrap(),
+                TestReportStatus::Ignored => xml.push_str("<skipped/>"),
+            }
+            xml.push_str("</testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 /// Runs the tests and process the results for a summary.
+///
+/// Up to `args.jobs` tests run at the same time, each in its own thread and with its own syscall
+/// handler. Tests exceeding `args.timeout` are reported as failed and left running in the
+/// background, since native code can't be interrupted. Tests which can't be run or whose runner
+/// panics are reported as failed as well.
+///
+/// The coverage isn't collected if any test timed out, since the timed out tests may still be
+/// updating it.
+///
+/// The context must outlive the threads of the timed out tests, hence the `'static` lifetime. It's
+/// meant to be created once and shared by every call.
 pub fn run_tests(
+    native_context: &'static NativeContext,
     named_tests: Vec<(String, TestConfig)>,
     sierra_program: Program,
     function_set_costs: OrderedHashMap<FunctionId, OrderedHashMap<CostTokenType, i32>>,
     args: RunArgs,
 ) -> anyhow::Result<TestsSummary> {
-    let native_context = NativeContext::new();
+    let compilation_start = Instant::now();
 
     // Compile the sierra program into a MLIR module.
+    let debug_options = DebugOptions {
+        coverage: args.coverage,
+        panic_backtraces: args.backtraces,
+        ..Default::default()
+    };
     let native_module = native_context
-        .compile(&sierra_program, false, Some(Default::default()))
-        .unwrap();
+        .compile_with_debug_options(
+            &sierra_program,
+            false,
+            Some(Default::default()),
+            debug_options,
+        )
+        .context("failed to compile the tests")?;
 
-    let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _> = match args.run_mode {
-        RunMode::Aot => {
-            let executor =
-                AotNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
-            Box::new(move |function_id, args, gas, syscall_handler| {
-                executor.invoke_dynamic_with_syscall_handler(
-                    function_id,
-                    args,
-                    gas,
-                    syscall_handler,
+    let (native_executor, take_coverage): (Arc<SharedExecutor>, Box<dyn Fn() -> Option<Coverage>>) =
+        match args.run_mode {
+            RunMode::Aot => {
+                let executor = Arc::new(AotNativeExecutor::from_native_module(
+                    native_module,
+                    args.opt_level.into(),
+                )?);
+                (
+                    Arc::new({
+                        let executor = executor.clone();
+                        move |function_id, args, gas, syscall_handler| {
+                            executor.invoke_dynamic_with_syscall_handler(
+                                function_id,
+                                args,
+                                gas,
+                                syscall_handler,
+                            )
+                        }
+                    }),
+                    Box::new(move || executor.take_coverage()),
                 )
-            })
-        }
-        RunMode::Jit => {
-            let executor =
-                JitNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
-            Box::new(move |function_id, args, gas, syscall_handler| {
-                executor.invoke_dynamic_with_syscall_handler(
-                    function_id,
-                    args,
-                    gas,
-                    syscall_handler,
+            }
+            RunMode::Jit => {
+                let executor = Arc::new(JitNativeExecutor::from_native_module(
+                    native_module,
+                    args.opt_level.into(),
+                )?);
+                (
+                    Arc::new({
+                        let executor = executor.clone();
+                        move |function_id, args, gas, syscall_handler| {
+                            executor.invoke_dynamic_with_syscall_handler(
+                                function_id,
+                                args,
+                                gas,
+                                syscall_handler,
+                            )
+                        }
+                    }),
+                    Box::new(move || executor.take_coverage()),
                 )
-            })
-        }
-    };
+            }
+        };
 
-    let gas_metadata = GasMetadata::new(
-        &sierra_program,
-        Some(MetadataComputationConfig {
-            function_set_costs,
-            linear_ap_change_solver: true,
-            linear_gas_solver: true,
-        }),
-    )
-    .unwrap();
+    let compilation_time = compilation_start.elapsed();
+
+    let gas_metadata = Arc::new(
+        GasMetadata::new(
+            &sierra_program,
+            Some(MetadataComputationConfig {
+                function_set_costs,
+                linear_ap_change_solver: true,
+                linear_gas_solver: true,
+            }),
+        )
+        .context("failed to compute the tests' gas metadata")?,
+    );
+    let sierra_program = Arc::new(sierra_program);
 
-    println!("running {} tests", named_tests.len());
-    let wrapped_summary = Mutex::new(Ok(TestsSummary {
+    if args.format == OutputFormat::Text {
+        println!("running {} tests", named_tests.len());
+    }
+    let mut summary = TestsSummary {
         passed: vec![],
         failed: vec![],
         ignored: vec![],
-        failed_run_results: vec![],
-    }));
-    named_tests
-        .into_iter()
-        .map(
-            |(name, test)| -> anyhow::Result<(String, Option<TestResult>)> {
-                if test.ignored {
-                    return Ok((name, None));
-                }
-                tracing::trace!("running test {name:?}");
-
-                let func = find_function(&sierra_program, name.as_str())?;
+        reports: vec![],
+        compilation_time,
+        coverage: None,
+    };
 
-                let initial_gas = test.available_gas.map(|x| x.try_into().unwrap());
+    // The results are reported in the tests' order, regardless of when they finish.
+    let test_indices = named_tests
+        .iter()
+        .enumerate()
+        .map(|(index, (name, _))| (name.clone(), index))
+        .collect::<HashMap<_, _>>();
 
-                let result = native_executor(
-                    &func.id,
-                    &[],
-                    initial_gas,
-                    &mut StubSyscallHandler::default(),
-                )
-                .with_context(|| format!("Failed to run the function `{}`.", name.as_str()))?;
-
-                let run_result = result_to_runresult(&result)?;
-                Ok((
-                    name,
-                    Some(TestResult {
-                        status: match &run_result {
-                            RunResultValue::Success(_) => match test.expectation {
-                                TestExpectation::Success => TestStatus::Success,
-                                TestExpectation::Panics(_) => TestStatus::Fail(run_result),
-                            },
-                            RunResultValue::Panic(value) => match test.expectation {
-                                TestExpectation::Success => TestStatus::Fail(run_result),
-                                TestExpectation::Panics(panic_expectation) => {
-                                    match panic_expectation {
-                                        PanicExpectation::Exact(expected) if value != &expected => {
-                                            TestStatus::Fail(run_result)
-                                        }
-                                        _ => TestStatus::Success,
-                                    }
-                                }
-                            },
-                        },
-                        gas_usage: test
-                            .available_gas
-                            .zip(result.remaining_gas)
-                            .map(|(before, after)| {
-                                before.into_or_panic::<i64>() - after.to_i64().unwrap()
-                            })
-                            .or_else(|| {
-                                gas_metadata
-                                    .initial_required_gas(&func.id)
-                                    .map(|gas| gas.try_into().unwrap())
-                            }),
-                    }),
-                ))
-            },
-        )
-        .for_each(|r| {
-            let mut wrapped_summary = wrapped_summary.lock().unwrap();
-            if wrapped_summary.is_err() {
-                return;
+    let (sender, receiver) = mpsc::channel();
+    let mut pending_tests = named_tests.into_iter().enumerate();
+    // The name and start time of the running tests, by index.
+    let mut running_tests = HashMap::<usize, (String, Instant)>::new();
+    let mut stop = false;
+    let mut any_timed_out = false;
+    loop {
+        while !stop && running_tests.len() < args.jobs {
+            let Some((index, (name, test))) = pending_tests.next() else {
+                break;
+            };
+            if test.ignored {
+                record_test_result(&mut summary, name, None, &args);
+                continue;
             }
-            let (name, status) = match r {
-                Ok((name, status)) => (name, status),
-                Err(err) => {
-                    *wrapped_summary = Err(err);
-                    return;
+            tracing::trace!("running test {name:?}");
+
+            running_tests.insert(index, (name.clone(), Instant::now()));
+            let sender = sender.clone();
+            let sierra_program = sierra_program.clone();
+            let gas_metadata = gas_metadata.clone();
+            let native_executor = native_executor.clone();
+            thread::spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    run_test(
+                        &name,
+                        test,
+                        &sierra_program,
+                        &gas_metadata,
+                        &*native_executor,
+                    )
+                }))
+                .unwrap_or_else(|_| Err(anyhow!("The test `{name}` panicked.")));
+                // The receiver is gone if the test timed out.
+                let _ = sender.send((index, result));
+            });
+        }
+
+        if running_tests.is_empty() {
+            break;
+        }
+
+        let next_timeout = args.timeout.map(|timeout| {
+            running_tests
+                .values()
+                .map(|(_, start)| (*start + timeout).saturating_duration_since(Instant::now()))
+                .min()
+                .unwrap_or_default()
+        });
+        let received = match next_timeout {
+            Some(next_timeout) => receiver.recv_timeout(next_timeout).ok(),
+            None => receiver.recv().ok(),
+        };
+
+        let failed_count = summary.failed.len();
+        match received {
+            Some((index, result)) => {
+                // Tests which already timed out are ignored.
+                let Some((name, start)) = running_tests.remove(&index) else {
+                    continue;
+                };
+                match result {
+                    Ok(result) => record_test_result(&mut summary, name, Some(result), &args),
+                    // Errors and panics fail the test, not the whole run.
+                    Err(error) => record_failed_test(
+                        &mut summary,
+                        name,
+                        start.elapsed(),
+                        "error",
+                        format!("{error:#}"),
+                        args.format,
+                    ),
                 }
-            };
-            let summary = wrapped_summary.as_mut().unwrap();
-            let (res_type, status_str, gas_usage) = match status {
-                Some(TestResult {
-                    status: TestStatus::Success,
-                    gas_usage,
-                }) => (&mut summary.passed, "ok".bright_green(), gas_usage),
-                Some(TestResult {
-                    status: TestStatus::Fail(run_result),
-                    gas_usage,
-                }) => {
-                    summary.failed_run_results.push(run_result);
-                    (&mut summary.failed, "fail".bright_red(), gas_usage)
+            }
+            None => {
+                let timeout = args.timeout.unwrap_or_default();
+                let timed_out = running_tests
+                    .iter()
+                    .filter(|(_, (_, start))| start.elapsed() >= timeout)
+                    .map(|(index, _)| *index)
+                    .collect_vec();
+                for index in timed_out {
+                    any_timed_out = true;
+                    let (name, _) = running_tests.remove(&index).unwrap();
+                    record_failed_test(
+                        &mut summary,
+                        name,
+                        timeout,
+                        "timed out",
+                        format!("timed out after {}s.", timeout.as_secs_f64()),
+                        args.format,
+                    );
                 }
-                None => (&mut summary.ignored, "ignored".bright_yellow(), None),
-            };
-            if let Some(gas_usage) = gas_usage {
-                println!("test {name} ... {status_str} (gas usage est.: {gas_usage})");
-            } else {
-                println!("test {name} ... {status_str}");
             }
-            res_type.push(name);
-        });
-    wrapped_summary.into_inner().unwrap()
+        }
+        stop |= args.fail_fast && summary.failed.len() > failed_count;
+    }
+
+    for names in [
+        &mut summary.passed,
+        &mut summary.failed,
+        &mut summary.ignored,
+    ] {
+        names.sort_by_key(|name| test_indices[name]);
+    }
+    summary
+        .reports
+        .sort_by_key(|report| test_indices[&report.name]);
+
+    if any_timed_out {
+        tracing::warn!(
+            "not collecting the coverage, some tests timed out and may still be running"
+        );
+    } else {
+        summary.coverage = take_coverage();
+    }
+
+    Ok(summary)
+}
+
+/// Runs a single test.
+fn run_test(
+    name: &str,
+    test: TestConfig,
+    sierra_program: &Program,
+    gas_metadata: &GasMetadata,
+    native_executor: &SharedExecutor,
+) -> anyhow::Result<TestResult> {
+    let func = find_function(sierra_program, name)?;
+
+    let initial_gas = test.available_gas.map(|x| x.try_into().unwrap());
+
+    let mut syscall_handler = StubSyscallHandler::default();
+    let start = Instant::now();
+    let result = native_executor(&func.id, &[], initial_gas, &mut syscall_handler)
+        .with_context(|| format!("Failed to run the function `{name}`."))?;
+    let duration = start.elapsed();
+    let backtrace = take_debug_results().and_then(|debug| debug.backtrace);
+
+    // A revert expected through the `expect_revert` cheatcode overrides the test's
+    // attributes.
+    let expectation = match syscall_handler.expected_revert {
+        Some(expected) if expected.is_empty() => TestExpectation::Panics(PanicExpectation::Any),
+        Some(expected) => TestExpectation::Panics(PanicExpectation::Exact(expected)),
+        None => test.expectation,
+    };
+
+    let run_result = result_to_runresult(&result)?;
+    let panic_data = match &run_result {
+        RunResultValue::Success(_) => None,
+        RunResultValue::Panic(values) => Some(values.clone()),
+    };
+    Ok(TestResult {
+        status: match &run_result {
+            RunResultValue::Success(_) => match expectation {
+                TestExpectation::Success => TestStatus::Success,
+                TestExpectation::Panics(_) => TestStatus::Fail(run_result),
+            },
+            RunResultValue::Panic(value) => match expectation {
+                TestExpectation::Success => TestStatus::Fail(run_result),
+                TestExpectation::Panics(panic_expectation) => match panic_expectation {
+                    PanicExpectation::Exact(expected) if value != &expected => {
+                        TestStatus::Fail(run_result)
+                    }
+                    _ => TestStatus::Success,
+                },
+            },
+        },
+        gas_usage: test
+            .available_gas
+            .zip(result.remaining_gas)
+            .map(|(before, after)| before.into_or_panic::<i64>() - after.to_i64().unwrap())
+            .or_else(|| {
+                gas_metadata
+                    .initial_required_gas(&func.id)
+                    .map(|gas| gas.try_into().unwrap())
+            }),
+        duration,
+        panic_data,
+        backtrace,
+        events: syscall_handler.events,
+    })
+}
+
+/// Adds the result of a test (`None` if ignored) to the summary.
+fn record_test_result(
+    summary: &mut TestsSummary,
+    name: String,
+    result: Option<TestResult>,
+    args: &RunArgs,
+) {
+    let events = match (&result, &args.event_abis) {
+        (Some(result), Some(abis)) => Some(
+            result
+                .events
+                .iter()
+                .map(|event| ReportedEvent::new(event.clone(), abis))
+                .collect_vec(),
+        ),
+        _ => None,
+    };
+
+    let (res_type, status_str, report) = match result {
+        Some(TestResult {
+            status: TestStatus::Success,
+            gas_usage,
+            duration,
+            panic_data,
+            backtrace: _,
+            events: _,
+        }) => (
+            &mut summary.passed,
+            "ok".bright_green(),
+            TestReport {
+                name: name.clone(),
+                status: TestReportStatus::Passed,
+                gas_usage,
+                duration: duration.as_secs_f64(),
+                failure: None,
+                panic_data: panic_data.map(|x| decode_panic_data(x.into_iter())),
+                events,
+            },
+        ),
+        Some(TestResult {
+            status: TestStatus::Fail(run_result),
+            gas_usage,
+            duration,
+            panic_data,
+            backtrace,
+            events: _,
+        }) => (
+            &mut summary.failed,
+            "fail".bright_red(),
+            TestReport {
+                name: name.clone(),
+                status: TestReportStatus::Failed,
+                gas_usage,
+                duration: duration.as_secs_f64(),
+                failure: Some(match run_result {
+                    RunResultValue::Success(_) => {
+                        "expected panic but finished successfully.".to_string()
+                    }
+                    RunResultValue::Panic(values) => {
+                        let mut failure = format_for_panic(values.into_iter());
+                        if let Some(backtrace) = backtrace {
+                            write!(failure, "\nCairo backtrace:\n{backtrace}").unwrap();
+                        }
+                        failure
+                    }
+                }),
+                panic_data: panic_data.map(|x| decode_panic_data(x.into_iter())),
+                events,
+            },
+        ),
+        None => (
+            &mut summary.ignored,
+            "ignored".bright_yellow(),
+            TestReport {
+                name: name.clone(),
+                status: TestReportStatus::Ignored,
+                gas_usage: None,
+                duration: 0.0,
+                failure: None,
+                panic_data: None,
+                events: None,
+            },
+        ),
+    };
+    if args.format == OutputFormat::Text {
+        if let Some(gas_usage) = report.gas_usage {
+            println!("test {name} ... {status_str} (gas usage est.: {gas_usage})");
+        } else {
+            println!("test {name} ... {status_str}");
+        }
+        for event in report.events.iter().flatten() {
+            println!("    event {event}");
+        }
+    }
+    summary.reports.push(report);
+    res_type.push(name);
+}
+
+/// Adds a test which didn't produce a result to the summary, as failed. The reason is shown next
+/// to the test's status and the failure is reported along with it.
+fn record_failed_test(
+    summary: &mut TestsSummary,
+    name: String,
+    duration: Duration,
+    reason: &str,
+    failure: String,
+    format: OutputFormat,
+) {
+    if format == OutputFormat::Text {
+        println!("test {name} ... {} ({reason})", "fail".bright_red());
+    }
+    summary.reports.push(TestReport {
+        name: name.clone(),
+        status: TestReportStatus::Failed,
+        gas_usage: None,
+        duration: duration.as_secs_f64(),
+        failure: Some(failure),
+        panic_data: None,
+        events: None,
+    });
+    summary.failed.push(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_filter() {
+        let name = "crate::tests::test_add";
+
+        assert!(TestFilter::new("test_add".into(), false, false)
+            .unwrap()
+            .matches(name));
+        assert!(!TestFilter::new("test_add".into(), true, false)
+            .unwrap()
+            .matches(name));
+        assert!(TestFilter::new(name.into(), true, false)
+            .unwrap()
+            .matches(name));
+        assert!(TestFilter::new("^crate::.*_add$".into(), false, true)
+            .unwrap()
+            .matches(name));
+        assert!(!TestFilter::new("^tests::".into(), false, true)
+            .unwrap()
+            .matches(name));
+        assert!(TestFilter::new("(".into(), false, true).is_err());
+        assert!(TestFilter::new(name.into(), true, true).is_err());
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let report = |name: &str, status, failure: Option<&str>| TestReport {
+            name: name.to_string(),
+            status,
+            gas_usage: Some(100),
+            duration: 0.5,
+            failure: failure.map(ToString::to_string),
+            panic_data: None,
+            events: None,
+        };
+        let summary = TestsSummary {
+            passed: vec!["tests::a".to_string()],
+            failed: vec!["tests::b".to_string()],
+            ignored: vec![],
+            reports: vec![
+                report("tests::a", TestReportStatus::Passed, None),
+                report(
+                    "tests::b",
+                    TestReportStatus::Failed,
+                    Some("Panicked with \"<oops>\"."),
+                ),
+            ],
+            compilation_time: Duration::ZERO,
+            coverage: None,
+        };
+
+        assert_eq!(
+            junit_report(&[("suite", &summary)]),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" skipped="0" time="1.000000">
+  <testsuite name="suite" tests="2" failures="1" skipped="0" time="1.000000">
+    <testcase name="a" classname="tests" time="0.500000"><properties><property name="gas_usage" value="100"/></properties></testcase>
+    <testcase name="b" classname="tests" time="0.500000"><properties><property name="gas_usage" value="100"/></properties><failure message="Panicked with &quot;&lt;oops&gt;&quot;."/></testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+
+        let report = junit_report(&[("unit", &summary), ("integration", &summary)]);
+        assert!(
+            report.contains(r#"<testsuites tests="4" failures="2" skipped="0" time="2.000000">"#)
+        );
+        assert_eq!(report.matches("<testsuite ").count(), 2);
+    }
 }
@@ -45,6 +45,7 @@ where
             module,
             registry,
             mut metadata,
+            ..
         } = self
             .context
             .compile(program, false, Some(Default::default()))?;
@@ -44,12 +44,19 @@
 //!
 //! [BFS algorithm]: https://en.wikipedia.org/wiki/Breadth-first_search
 
+#[cfg(feature = "with-debug-utils")]
+use crate::metadata::auto_breakpoint::{build_breakpoint, AutoBreakpoint};
 use crate::{
-    debug::libfunc_to_name,
+    debug::{
+        backtrace::is_panic_result, libfunc_to_name, trace::is_traceable, DebugOptions,
+        SourceLocations, TraceLevel,
+    },
     error::{panic::ToNativeAssertError, Error},
     libfuncs::{BranchArg, LibfuncBuilder, LibfuncHelper},
     metadata::{
+        coverage::CoverageMeta,
         gas::{GasCost, GasMetadata},
+        runtime_bindings::RuntimeBindingsMeta,
         tail_recursion::TailRecursionMeta,
         MetadataStorage,
     },
@@ -61,7 +68,7 @@ use bumpalo::Bump;
 use cairo_lang_sierra::{
     edit_state,
     extensions::{
-        core::{CoreConcreteLibfunc, CoreLibfunc, CoreType},
+        core::{CoreConcreteLibfunc, CoreLibfunc, CoreType, CoreTypeConcrete},
         ConcreteLibfunc,
     },
     ids::{ConcreteTypeId, VarId},
@@ -91,14 +98,16 @@ use melior::{
 };
 use mlir_sys::{
     mlirDisctinctAttrCreate, mlirLLVMDICompileUnitAttrGet, mlirLLVMDIFileAttrGet,
-    mlirLLVMDIModuleAttrGet, mlirLLVMDIModuleAttrGetScope, mlirLLVMDISubprogramAttrGet,
-    mlirLLVMDISubroutineTypeAttrGet, MlirLLVMDIEmissionKind_MlirLLVMDIEmissionKindFull,
+    mlirLLVMDILexicalBlockFileAttrGet, mlirLLVMDIModuleAttrGet, mlirLLVMDIModuleAttrGetScope,
+    mlirLLVMDISubprogramAttrGet, mlirLLVMDISubroutineTypeAttrGet,
+    MlirLLVMDIEmissionKind_MlirLLVMDIEmissionKindFull,
     MlirLLVMDINameTableKind_MlirLLVMDINameTableKindDefault,
 };
 use std::{
     cell::Cell,
     collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
     ops::Deref,
+    path::Path,
 };
 
 /// The [BlockStorage] type is used to map each statement into its own entry block (on the right),
@@ -109,6 +118,75 @@ use std::{
 type BlockStorage<'c, 'a> =
     HashMap<StatementIdx, (Option<(BlockRef<'c, 'a>, Vec<VarId>)>, BlockRef<'c, 'a>)>;
 
+/// The file name used for the locations within the Sierra program.
+const SIERRA_FILE_NAME: &str = "program.sierra";
+
+/// Generates the locations of a function's statements.
+///
+/// When the Cairo source locations are available the statements point at the Cairo code which
+/// generated them, otherwise they point at their line within the Sierra program. The DWARF
+/// translation takes the file from the location's scope (not from the location itself), therefore
+/// statements from a file other than the function's are scoped within a lexical block for their
+/// file.
+struct StatementLocations<'c, 'a> {
+    sierra_stmt_start_offset: usize,
+    source_locations: Option<&'a SourceLocations>,
+    fn_file: &'a str,
+    di_subprogram: Attribute<'c>,
+}
+
+impl<'c> StatementLocations<'c, '_> {
+    fn location(&self, context: &'c Context, statement_idx: StatementIdx) -> Location<'c> {
+        let Some(source_location) = self.source_locations.and_then(|x| x.get(statement_idx)) else {
+            return Location::new(
+                context,
+                SIERRA_FILE_NAME,
+                self.sierra_stmt_start_offset + statement_idx.0,
+                0,
+            );
+        };
+
+        let location = Location::new(
+            context,
+            &source_location.file,
+            source_location.line,
+            source_location.col,
+        );
+        if source_location.file == self.fn_file {
+            return location;
+        }
+
+        let lexical_block_file = unsafe {
+            Attribute::from_raw(mlirLLVMDILexicalBlockFileAttrGet(
+                context.to_raw(),
+                self.di_subprogram.to_raw(),
+                di_file_attr(context, &source_location.file).to_raw(),
+                0,
+            ))
+        };
+        Location::fused(context, &[location], lexical_block_file)
+    }
+}
+
+/// Build the DWARF file attribute for a source file path.
+fn di_file_attr<'c>(context: &'c Context, path: &str) -> Attribute<'c> {
+    let path = Path::new(path);
+    let (name, directory) = match (path.file_name(), path.parent()) {
+        (Some(name), Some(directory)) if !directory.as_os_str().is_empty() => {
+            (name.to_string_lossy(), directory.to_string_lossy())
+        }
+        _ => (path.to_string_lossy(), ".".into()),
+    };
+
+    unsafe {
+        Attribute::from_raw(mlirLLVMDIFileAttrGet(
+            context.to_raw(),
+            StringAttribute::new(context, &name).to_raw(),
+            StringAttribute::new(context, &directory).to_raw(),
+        ))
+    }
+}
+
 /// Run the compiler on a program. The compiled program is stored in the MLIR module.
 ///
 /// The generics `TType` and `TLibfunc` contain the information required to generate the MLIR types
@@ -121,6 +199,8 @@ type BlockStorage<'c, 'a> =
 ///
 /// Additionally, it needs a reference to the MLIR context, the output module and the metadata
 /// storage. The last one is passed externally so that stuff can be initialized if necessary.
+///
+/// The debug options control which debug artifacts are written and where debug traps are inserted.
 pub fn compile(
     context: &Context,
     module: &Module,
@@ -129,11 +209,10 @@ pub fn compile(
     metadata: &mut MetadataStorage,
     di_compile_unit_id: Attribute,
     ignore_debug_names: bool,
+    debug_options: &DebugOptions,
 ) -> Result<(), Error> {
-    if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-        if x == "1" || x == "true" {
-            std::fs::write("program.sierra", program.to_string())?;
-        }
+    if debug_options.artifacts_dir.is_some() {
+        debug_options.write_artifact(".sierra", program.to_string())?;
     }
 
     // Sierra programs have the following structure:
@@ -158,6 +237,7 @@ pub fn compile(
             di_compile_unit_id,
             sierra_stmt_start_offset,
             ignore_debug_names,
+            debug_options,
         )?;
     }
 
@@ -184,13 +264,9 @@ fn compile_func(
     di_compile_unit_id: Attribute,
     sierra_stmt_start_offset: usize,
     ignore_debug_names: bool,
+    debug_options: &DebugOptions,
 ) -> Result<(), Error> {
-    let fn_location = Location::new(
-        context,
-        "program.sierra",
-        sierra_stmt_start_offset + function.entry_point.0,
-        0,
-    );
+    let source_locations = debug_options.source_locations.as_ref();
 
     let region = Region::new();
     let blocks_arena = Bump::new();
@@ -289,21 +365,28 @@ fn compile_func(
     // so we don't have to pass the boolean to the function call libfunc.
     let function_name_for_inner = generate_function_name(&function.id, false);
 
+    // The function is declared where its entry statement is, either in the Cairo source (when the
+    // source locations are available) or in the Sierra program.
+    let (fn_file, fn_line) = match source_locations.and_then(|x| x.get(function.entry_point)) {
+        Some(location) => (location.file.as_str(), location.line),
+        None => (
+            SIERRA_FILE_NAME,
+            sierra_stmt_start_offset + function.entry_point.0,
+        ),
+    };
+
     let di_subprogram = unsafe {
         // Various DWARF debug attributes for this function.
         // The unsafe is because this is a method not yet found in upstream LLVM nor melior, so
         // we are using our own bindings to the C++ API.
-        let file_attr = Attribute::from_raw(mlirLLVMDIFileAttrGet(
-            context.to_raw(),
-            StringAttribute::new(context, "program.sierra").to_raw(),
-            StringAttribute::new(context, ".").to_raw(),
-        ));
+        let sierra_file_attr = di_file_attr(context, SIERRA_FILE_NAME);
+        let file_attr = di_file_attr(context, fn_file);
         let compile_unit = {
             Attribute::from_raw(mlirLLVMDICompileUnitAttrGet(
                 context.to_raw(),
                 di_compile_unit_id.to_raw(),
                 0x0002, // lang C (there is no language sierra in DWARF)
-                file_attr.to_raw(),
+                sierra_file_attr.to_raw(),
                 StringAttribute::new(context, "cairo-native").to_raw(),
                 false,
                 MlirLLVMDIEmissionKind_MlirLLVMDIEmissionKindFull,
@@ -313,7 +396,7 @@ fn compile_func(
 
         let di_module = mlirLLVMDIModuleAttrGet(
             context.to_raw(),
-            file_attr.to_raw(),
+            sierra_file_attr.to_raw(),
             compile_unit.to_raw(),
             StringAttribute::new(context, "LLVMDialectModule").to_raw(),
             StringAttribute::new(context, "").to_raw(),
@@ -346,14 +429,22 @@ fn compile_func(
                 StringAttribute::new(context, &function_name).to_raw(),
                 StringAttribute::new(context, &function_name).to_raw(),
                 file_attr.to_raw(),
-                (sierra_stmt_start_offset + function.entry_point.0) as u32,
-                (sierra_stmt_start_offset + function.entry_point.0) as u32,
+                fn_line as u32,
+                fn_line as u32,
                 0x8, // dwarf subprogram flag: definition
                 ty,
             )
         })
     };
 
+    let statement_locations = StatementLocations {
+        sierra_stmt_start_offset,
+        source_locations,
+        fn_file,
+        di_subprogram,
+    };
+    let fn_location = statement_locations.location(context, function.entry_point);
+
     tracing::debug!("Generating function structure (region with blocks).");
     let (entry_block, blocks, is_recursive) = generate_function_structure(
         context,
@@ -363,7 +454,7 @@ fn compile_func(
         function,
         statements,
         metadata,
-        sierra_stmt_start_offset,
+        &statement_locations,
     )?;
 
     tracing::debug!("Generating the function implementation.");
@@ -373,12 +464,7 @@ fn compile_func(
         .map(|ty| {
             (
                 *ty,
-                Location::new(
-                    context,
-                    "program.sierra",
-                    sierra_stmt_start_offset + function.entry_point.0,
-                    0,
-                ),
+                statement_locations.location(context, function.entry_point),
             )
         })
         .collect::<Vec<_>>();
@@ -391,12 +477,7 @@ fn compile_func(
         let mut count = 0;
         for param in &function.params {
             let type_info = registry.get_type(&param.ty)?;
-            let location = Location::new(
-                context,
-                "program.sierra",
-                sierra_stmt_start_offset + function.entry_point.0,
-                0,
-            );
+            let location = statement_locations.location(context, function.entry_point);
 
             values.push((
                 &param.id,
@@ -430,16 +511,16 @@ fn compile_func(
         .iter()
         .map(|x| initial_state[x])
         .collect::<Vec<_>>(),
-        {
-            Location::new(
-                context,
-                "program.sierra",
-                sierra_stmt_start_offset + function.entry_point.0,
-                0,
-            )
-        },
+        statement_locations.location(context, function.entry_point),
     ));
 
+    // The statement hooks need the types of the live variables, which aren't kept in the state.
+    let live_var_types = if debug_options.statement_hooks {
+        compute_live_var_types(statements, registry, function)?
+    } else {
+        HashMap::new()
+    };
+
     let mut tailrec_state = Option::<(Value, BlockRef)>::None;
     foreach_statement_in_function::<_, Error>(
         statements,
@@ -494,27 +575,53 @@ fn compile_func(
                         invocation.libfunc_id
                     );
 
-                    let location = Location::new(
-                        context,
-                        "program.sierra",
-                        sierra_stmt_start_offset + statement_idx.0,
-                        0,
-                    );
+                    let location = statement_locations.location(context, statement_idx);
 
-                    #[cfg(feature = "with-debug-utils")]
-                    {
-                        // If this env var exists and is a valid statement, insert a debug trap before the libfunc call.
-                        // Only on when using with-debug-utils feature.
-                        if let Ok(x) = std::env::var("NATIVE_DEBUG_TRAP_AT_STMT") {
-                            if x.eq_ignore_ascii_case(&statement_idx.0.to_string()) {
-                                block.append_operation(
-                                    melior::dialect::ods::llvm::intr_debugtrap(context, location)
-                                        .into(),
-                                );
-                            }
-                        }
+                    if let Some(coverage) = metadata.get_mut::<CoverageMeta>() {
+                        coverage.increment_counter(
+                            context,
+                            module,
+                            block,
+                            location,
+                            statement_idx,
+                        )?;
+                    }
+                    if debug_options.trace.is_some() {
+                        build_trace_statement_call(
+                            context,
+                            module,
+                            block,
+                            location,
+                            metadata,
+                            statement_idx,
+                        )?;
+                    }
+                    if debug_options.statement_hooks {
+                        build_statement_hook_call(
+                            context,
+                            module,
+                            block,
+                            &pre_entry_block,
+                            location,
+                            registry,
+                            metadata,
+                            statement_idx,
+                            state.iter().map(|(var_id, value)| (var_id, *value)),
+                            &live_var_types[&statement_idx],
+                        )?;
                     }
 
+                    // Insert a debug trap before the libfunc call if requested.
+                    if debug_options.trap_at_statements.contains(&statement_idx) {
+                        block.append_operation(
+                            melior::dialect::ods::llvm::intr_debugtrap(context, location).into(),
+                        );
+                    }
+                    #[cfg(feature = "with-debug-utils")]
+                    build_libfunc_breakpoints(
+                        context, module, block, location, registry, metadata, invocation, &state,
+                    )?;
+
                     let libfunc_name = if invocation.libfunc_id.debug_name.is_some() {
                         format!("{}(stmt_idx={})", invocation.libfunc_id, statement_idx)
                     } else {
@@ -524,19 +631,37 @@ fn compile_func(
 
                     let (state, _) = edit_state::take_args(state, invocation.args.iter())?;
 
+                    let mut branches = generate_branching_targets(
+                        &blocks,
+                        statements,
+                        statement_idx,
+                        invocation,
+                        &state,
+                    );
+                    if let Some(trace_level) = debug_options.trace {
+                        branches = generate_trace_trampolines(
+                            context,
+                            module,
+                            &region,
+                            &blocks_arena,
+                            &pre_entry_block,
+                            registry,
+                            metadata,
+                            statement_idx,
+                            registry.get_libfunc(&invocation.libfunc_id)?,
+                            branches,
+                            trace_level,
+                            location,
+                        )?;
+                    }
+
                     let helper = LibfuncHelper {
                         module,
                         init_block: &pre_entry_block,
                         region: &region,
                         blocks_arena: &blocks_arena,
                         last_block: Cell::new(block),
-                        branches: generate_branching_targets(
-                            &blocks,
-                            statements,
-                            statement_idx,
-                            invocation,
-                            &state,
-                        ),
+                        branches,
                         results: invocation
                             .branches
                             .iter()
@@ -594,6 +719,16 @@ fn compile_func(
                         }
                     }
 
+                    // Keep track of the call site in the shadow call stack.
+                    if debug_options.panic_backtraces && libfunc.is_function_call().is_some() {
+                        let statement_idx =
+                            block.const_int(context, location, statement_idx.0, 64)?;
+                        metadata
+                            .get_mut::<RuntimeBindingsMeta>()
+                            .ok_or(Error::MissingMetadata)?
+                            .backtrace_call(context, module, block, location, statement_idx)?;
+                    }
+
                     libfunc.build(
                         context,
                         registry,
@@ -640,16 +775,66 @@ fn compile_func(
                     let location = Location::name(
                         context,
                         &format!("return(stmt_idx={})", statement_idx),
-                        Location::new(
-                            context,
-                            "program.sierra",
-                            sierra_stmt_start_offset + statement_idx.0,
-                            0,
-                        ),
+                        statement_locations.location(context, statement_idx),
                     );
 
                     let (_, mut values) = edit_state::take_args(state, var_ids.iter())?;
 
+                    // The shadow call stack checks whether the function returns a panic, which is
+                    // always its last return value.
+                    let returned_value = values.last().copied();
+
+                    // The gas counter is recorded by the profiler when the function returns.
+                    let returned_gas = function
+                        .signature
+                        .ret_types
+                        .iter()
+                        .zip(&values)
+                        .find(|(type_id, _)| {
+                            matches!(
+                                registry.get_type(type_id),
+                                Ok(CoreTypeConcrete::GasBuiltin(_))
+                            )
+                        })
+                        .map(|(_, value)| *value);
+
+                    // Deferred return statements are instrumented when they're compiled again.
+                    if !is_recursive || tailrec_state.is_some() {
+                        if let Some(coverage) = metadata.get_mut::<CoverageMeta>() {
+                            coverage.increment_counter(
+                                context,
+                                module,
+                                block,
+                                location,
+                                statement_idx,
+                            )?;
+                        }
+                        if debug_options.trace.is_some() {
+                            build_trace_statement_call(
+                                context,
+                                module,
+                                block,
+                                location,
+                                metadata,
+                                statement_idx,
+                            )?;
+                        }
+                        if debug_options.statement_hooks {
+                            build_statement_hook_call(
+                                context,
+                                module,
+                                block,
+                                &pre_entry_block,
+                                location,
+                                registry,
+                                metadata,
+                                statement_idx,
+                                var_ids.iter().zip(values.iter().copied()),
+                                &live_var_types[&statement_idx],
+                            )?;
+                        }
+                    }
+
                     let mut block = *block;
                     if is_recursive {
                         match tailrec_state {
@@ -664,12 +849,7 @@ fn compile_func(
                                 let location = Location::name(
                                     context,
                                     &format!("return(stmt_idx={}, tail_recursion)", statement_idx),
-                                    Location::new(
-                                        context,
-                                        "program.sierra",
-                                        sierra_stmt_start_offset + statement_idx.0,
-                                        0,
-                                    ),
+                                    statement_locations.location(context, statement_idx),
                                 );
 
                                 // Perform tail recursion.
@@ -806,6 +986,33 @@ fn compile_func(
                         ));
                     }
 
+                    if debug_options.profiler {
+                        build_profiler_call(
+                            context,
+                            module,
+                            &block,
+                            location,
+                            metadata,
+                            function,
+                            returned_gas,
+                            false,
+                        )?;
+                    }
+                    if debug_options.panic_backtraces {
+                        build_backtrace_pop_call(
+                            context,
+                            module,
+                            &block,
+                            &pre_entry_block,
+                            location,
+                            registry,
+                            metadata,
+                            function,
+                            statement_idx,
+                            returned_value,
+                        )?;
+                    }
+
                     block.append_operation(llvm::r#return(
                         Some({
                             let res_ty = llvm::r#type::r#struct(context, &return_types, false);
@@ -834,6 +1041,7 @@ fn compile_func(
     // Load arguments and jump to the entry block.
     {
         let mut arg_values = Vec::with_capacity(function.signature.param_types.len());
+        let mut entry_gas = None;
         for (i, type_id_and_info) in function
             .signature
             .param_types
@@ -883,12 +1091,73 @@ fn compile_func(
                     .into();
             }
 
+            if let CoreTypeConcrete::GasBuiltin(_) = type_info {
+                entry_gas = Some(value);
+            }
             arg_values.push(value);
         }
 
+        // The profiler's entry is recorded before the entry block, which is also the target of the
+        // tail-recursive calls, so a whole tail recursion is recorded as a single call. The same
+        // goes for the shadow call stack and the function entry breakpoints.
+        if debug_options.profiler {
+            build_profiler_call(
+                context,
+                module,
+                &pre_entry_block,
+                fn_location,
+                metadata,
+                function,
+                entry_gas,
+                true,
+            )?;
+        }
+        if debug_options.panic_backtraces {
+            let function_id =
+                pre_entry_block.const_int(context, fn_location, function.id.id, 64)?;
+            let statement_idx =
+                pre_entry_block.const_int(context, fn_location, function.entry_point.0, 64)?;
+            metadata
+                .get_mut::<RuntimeBindingsMeta>()
+                .ok_or(Error::MissingMetadata)?
+                .backtrace_push(
+                    context,
+                    module,
+                    &pre_entry_block,
+                    fn_location,
+                    function_id,
+                    statement_idx,
+                )?;
+        }
+
+        #[cfg(feature = "with-debug-utils")]
+        if let Some(auto_breakpoint) = metadata.get::<AutoBreakpoint>() {
+            for breakpoint in auto_breakpoint.matching(|event| event.matches_function(&function.id))
+            {
+                build_breakpoint(
+                    context,
+                    module,
+                    &pre_entry_block,
+                    fn_location,
+                    metadata,
+                    &breakpoint,
+                    &arg_values,
+                )?;
+            }
+        }
+
         pre_entry_block.append_operation(cf::br(&entry_block, &arg_values, fn_location));
     }
 
+    // Private functions don't get a symbol in the object file. When the symbols are going to be
+    // exported to profilers and debuggers, use the internal linkage (which has the same semantics)
+    // instead so that the functions' code can be attributed to them.
+    let inner_function_linkage = if debug_options.exports_jit_symbols() {
+        "#llvm.linkage<internal>"
+    } else {
+        "#llvm.linkage<private>"
+    };
+
     let inner_function_name = format!("impl${function_name_for_inner}");
     module.body().append_operation(llvm::func(
         context,
@@ -906,7 +1175,7 @@ fn compile_func(
             ),
             (
                 Identifier::new(context, "linkage"),
-                Attribute::parse(context, "#llvm.linkage<private>")
+                Attribute::parse(context, inner_function_linkage)
                     .ok_or(Error::ParseAttributeError)?,
             ),
             (
@@ -917,12 +1186,7 @@ fn compile_func(
         ],
         Location::fused(
             context,
-            &[Location::new(
-                context,
-                "program.sierra",
-                sierra_stmt_start_offset + function.entry_point.0,
-                0,
-            )],
+            &[statement_locations.location(context, function.entry_point)],
             di_subprogram,
         ),
     ));
@@ -934,12 +1198,7 @@ fn compile_func(
         &inner_function_name,
         &pre_entry_block_args,
         &return_types,
-        Location::new(
-            context,
-            "program.sierra",
-            sierra_stmt_start_offset + function.entry_point.0,
-            0,
-        ),
+        statement_locations.location(context, function.entry_point),
     )?;
 
     tracing::debug!("Done generating function {}.", function.id);
@@ -955,7 +1214,7 @@ fn generate_function_structure<'c, 'a>(
     function: &Function,
     statements: &[Statement],
     metadata_storage: &mut MetadataStorage,
-    sierra_stmt_start_offset: usize,
+    statement_locations: &StatementLocations<'c, '_>,
 ) -> Result<(BlockRef<'c, 'a>, BlockStorage<'c, 'a>, bool), Error> {
     let initial_state = edit_state::put_results::<Type>(
         OrderedHashMap::default(),
@@ -1005,12 +1264,7 @@ fn generate_function_structure<'c, 'a>(
                     let (state, types) =
                         edit_state::take_args(state.clone(), invocation.args.iter())?;
 
-                    let location = Location::new(
-                        context,
-                        "program.sierra",
-                        sierra_stmt_start_offset + statement_idx.0,
-                        0,
-                    );
+                    let location = statement_locations.location(context, statement_idx);
 
                     for ty in types {
                         block.add_argument(ty, location);
@@ -1075,12 +1329,7 @@ fn generate_function_structure<'c, 'a>(
                         "State must be empty after a return statement."
                     );
 
-                    let location = Location::new(
-                        context,
-                        "program.sierra",
-                        sierra_stmt_start_offset + statement_idx.0,
-                        0,
-                    );
+                    let location = statement_locations.location(context, statement_idx);
 
                     for ty in types {
                         block.add_argument(ty, location);
@@ -1104,12 +1353,7 @@ fn generate_function_structure<'c, 'a>(
         .map(|ty| {
             Ok((
                 ty?,
-                Location::new(
-                    context,
-                    "program.sierra",
-                    sierra_stmt_start_offset + function.entry_point.0,
-                    0,
-                ),
+                statement_locations.location(context, function.entry_point),
             ))
         })
         .collect::<Result<Vec<_>, Error>>()?
@@ -1138,15 +1382,7 @@ fn generate_function_structure<'c, 'a>(
                                 .collect::<BTreeMap<_, _>>()
                                 .into_values()
                                 .map(|ty| {
-                                    (
-                                        ty,
-                                        Location::new(
-                                            context,
-                                            "program.sierra",
-                                            sierra_stmt_start_offset + statement_idx.0,
-                                            0,
-                                        ),
-                                    )
+                                    (ty, statement_locations.location(context, statement_idx))
                                 })
                                 .collect::<Vec<_>>(),
                         ),
@@ -1255,6 +1491,391 @@ where
     Ok(())
 }
 
+/// Compute the types of the variables which are live before every statement of a function.
+fn compute_live_var_types(
+    statements: &[Statement],
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    function: &Function,
+) -> Result<HashMap<StatementIdx, OrderedHashMap<VarId, ConcreteTypeId>>, Error> {
+    let initial_state = edit_state::put_results(
+        OrderedHashMap::default(),
+        function
+            .params
+            .iter()
+            .map(|param| (&param.id, param.ty.clone())),
+    )?;
+
+    let mut live_var_types = HashMap::new();
+    foreach_statement_in_function::<_, Error>(
+        statements,
+        function.entry_point,
+        initial_state,
+        |statement_idx, state| {
+            let branch_states = match &statements[statement_idx.0] {
+                Statement::Invocation(invocation) => {
+                    let (state, _) = edit_state::take_args(state.clone(), invocation.args.iter())?;
+                    let libfunc = registry.get_libfunc(&invocation.libfunc_id)?;
+
+                    invocation
+                        .branches
+                        .iter()
+                        .zip(libfunc.branch_signatures())
+                        .map(|(branch, branch_signature)| {
+                            Ok(edit_state::put_results(
+                                state.clone(),
+                                branch
+                                    .results
+                                    .iter()
+                                    .zip(branch_signature.vars.iter().map(|x| x.ty.clone())),
+                            )?)
+                        })
+                        .collect::<Result<_, Error>>()?
+                }
+                Statement::Return(_) => Vec::new(),
+            };
+
+            live_var_types.insert(statement_idx, state);
+            Ok(StatementCompileResult::Processed(branch_states))
+        },
+    )?;
+
+    Ok(live_var_types)
+}
+
+/// Pass the variables which are live before a statement to the statement hook, then call it.
+/// Variables whose type refers to memory owned by the program are passed without their values.
+#[allow(clippy::too_many_arguments)]
+fn build_statement_hook_call<'c, 'a>(
+    context: &'c Context,
+    module: &Module<'c>,
+    block: &Block<'c>,
+    init_block: &Block<'c>,
+    location: Location<'c>,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    metadata: &mut MetadataStorage,
+    statement_idx: StatementIdx,
+    live_vars: impl IntoIterator<Item = (&'a VarId, Value<'c, 'a>)>,
+    var_types: &OrderedHashMap<VarId, ConcreteTypeId>,
+) -> Result<(), Error>
+where
+    'c: 'a,
+{
+    for (var_id, value) in live_vars {
+        let type_id = &var_types[var_id];
+        let (value_ptr, size) = build_value_capture(
+            context, module, block, init_block, location, registry, metadata, type_id, value,
+        )?;
+
+        let var_id = block.const_int(context, location, var_id.id, 64)?;
+        let type_id = block.const_int(context, location, type_id.id, 64)?;
+        metadata
+            .get_mut::<RuntimeBindingsMeta>()
+            .ok_or(Error::MissingMetadata)?
+            .debugger_variable(
+                context, module, block, location, var_id, type_id, value_ptr, size,
+            )?;
+    }
+
+    let statement_idx = block.const_int(context, location, statement_idx.0, 64)?;
+    metadata
+        .get_mut::<RuntimeBindingsMeta>()
+        .ok_or(Error::MissingMetadata)?
+        .debugger_statement(context, module, block, location, statement_idx)?;
+
+    Ok(())
+}
+
+/// Store a value so that the runtime can copy it, and return a pointer to it along with its size.
+/// Values whose type refers to memory owned by the program can't be copied, so a null pointer and
+/// a zero size are returned for them instead (see [`is_traceable`]).
+#[allow(clippy::too_many_arguments)]
+fn build_value_capture<'c, 'a>(
+    context: &'c Context,
+    module: &Module<'c>,
+    block: &'a Block<'c>,
+    init_block: &Block<'c>,
+    location: Location<'c>,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    metadata: &mut MetadataStorage,
+    type_id: &ConcreteTypeId,
+    value: Value<'c, '_>,
+) -> Result<(Value<'c, 'a>, Value<'c, 'a>), Error> {
+    if !is_traceable(registry, type_id)? {
+        return Ok((
+            block.append_op_result(llvm::zero(llvm::r#type::pointer(context, 0), location))?,
+            block.const_int(context, location, 0, 64)?,
+        ));
+    }
+
+    let type_info = registry.get_type(type_id)?;
+    let layout = type_info.layout(registry)?;
+    let value_ptr = init_block.alloca1(
+        context,
+        location,
+        type_info.build(context, module, registry, metadata, type_id)?,
+        layout.align(),
+    )?;
+    block.store(context, location, value_ptr, value)?;
+
+    Ok((
+        value_ptr,
+        block.const_int(context, location, layout.size(), 64)?,
+    ))
+}
+
+/// Insert the actions of the breakpoints registered for the invocation of a libfunc, printing its
+/// arguments.
+#[cfg(feature = "with-debug-utils")]
+#[allow(clippy::too_many_arguments)]
+fn build_libfunc_breakpoints<'c, 'a>(
+    context: &'c Context,
+    module: &Module<'c>,
+    block: &'a Block<'c>,
+    location: Location<'c>,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    metadata: &mut MetadataStorage,
+    invocation: &Invocation,
+    state: &OrderedHashMap<VarId, Value<'c, 'a>>,
+) -> Result<(), Error> {
+    let Some(auto_breakpoint) = metadata.get::<AutoBreakpoint>() else {
+        return Ok(());
+    };
+
+    let libfunc_id = &invocation.libfunc_id;
+    let generic_name = libfunc_to_name(registry.get_libfunc(libfunc_id)?);
+    let breakpoints =
+        auto_breakpoint.matching(|event| event.matches_libfunc(libfunc_id, generic_name));
+    if breakpoints.is_empty() {
+        return Ok(());
+    }
+
+    // Only gather the arguments once a breakpoint needs them, since it requires copying the state.
+    let (_, args) = edit_state::take_args(state.clone(), invocation.args.iter())?;
+    for breakpoint in breakpoints {
+        build_breakpoint(
+            context,
+            module,
+            block,
+            location,
+            metadata,
+            &breakpoint,
+            &args,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Record the entry into or the exit from a function in the profiler, along with the function's gas
+/// counter if it has one.
+#[allow(clippy::too_many_arguments)]
+fn build_profiler_call<'c, 'a>(
+    context: &'c Context,
+    module: &Module,
+    block: &'a Block<'c>,
+    location: Location<'c>,
+    metadata: &mut MetadataStorage,
+    function: &Function,
+    gas: Option<Value<'c, 'a>>,
+    is_entry: bool,
+) -> Result<(), Error> {
+    let function_id = block.const_int(context, location, function.id.id, 64)?;
+    let gas = match gas {
+        Some(gas) => gas,
+        None => block.const_int(context, location, 0, 64)?,
+    };
+
+    let runtime_bindings = metadata
+        .get_mut::<RuntimeBindingsMeta>()
+        .ok_or(Error::MissingMetadata)?;
+    if is_entry {
+        runtime_bindings.profiler_enter(context, module, block, location, function_id, gas)?;
+    } else {
+        runtime_bindings.profiler_exit(context, module, block, location, function_id, gas)?;
+    }
+
+    Ok(())
+}
+
+/// Pop the function from the shadow call stack, along with whether it returns a panic.
+#[allow(clippy::too_many_arguments)]
+fn build_backtrace_pop_call<'c>(
+    context: &'c Context,
+    module: &Module<'c>,
+    block: &Block<'c>,
+    init_block: &Block<'c>,
+    location: Location<'c>,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    metadata: &mut MetadataStorage,
+    function: &Function,
+    statement_idx: StatementIdx,
+    returned_value: Option<Value<'c, '_>>,
+) -> Result<(), Error> {
+    let i8_ty = IntegerType::new(context, 8).into();
+    let panicking = match (function.signature.ret_types.last(), returned_value) {
+        (Some(type_id), Some(value)) if is_panic_result(registry, type_id) => {
+            let type_info = registry.get_type(type_id)?;
+            let (layout, (tag_ty, _), _) = crate::types::r#enum::get_type_for_variants(
+                context,
+                module,
+                registry,
+                metadata,
+                type_info
+                    .variants()
+                    .to_native_assert_error("found non-enum type where an enum is required")?,
+            )?;
+
+            let tag = if type_info.is_memory_allocated(registry)? {
+                let stack_ptr = init_block.alloca1(
+                    context,
+                    location,
+                    type_info.build(context, module, registry, metadata, type_id)?,
+                    layout.align(),
+                )?;
+                block.store(context, location, stack_ptr, value)?;
+                block.load(context, location, stack_ptr, tag_ty)?
+            } else {
+                block.extract_value(context, location, value, tag_ty, 0)?
+            };
+
+            // The panic is the second variant.
+            let k0 = block.const_int_from_type(context, location, 0, tag_ty)?;
+            let is_panic = block.cmpi(context, CmpiPredicate::Ne, tag, k0, location)?;
+            block.extui(is_panic, i8_ty, location)?
+        }
+        _ => block.const_int_from_type(context, location, 0, i8_ty)?,
+    };
+
+    let statement_idx = block.const_int(context, location, statement_idx.0, 64)?;
+    metadata
+        .get_mut::<RuntimeBindingsMeta>()
+        .ok_or(Error::MissingMetadata)?
+        .backtrace_pop(context, module, block, location, statement_idx, panicking)?;
+
+    Ok(())
+}
+
+/// Record the execution of a statement in the trace.
+fn build_trace_statement_call<'c>(
+    context: &'c Context,
+    module: &Module,
+    block: &Block<'c>,
+    location: Location<'c>,
+    metadata: &mut MetadataStorage,
+    statement_idx: StatementIdx,
+) -> Result<(), Error> {
+    let statement_idx = block.const_int(context, location, statement_idx.0, 64)?;
+    metadata
+        .get_mut::<RuntimeBindingsMeta>()
+        .ok_or(Error::MissingMetadata)?
+        .trace_statement(context, module, block, location, statement_idx)?;
+
+    Ok(())
+}
+
+/// Route the invocation's branches through blocks which record the branch taken (and, with
+/// [`TraceLevel::Values`], the values it produces) before jumping into the original targets.
+///
+/// Since the libfuncs jump into the trampolines with all their results, the values produced are
+/// available regardless of whether the targets use them.
+#[allow(clippy::too_many_arguments)]
+fn generate_trace_trampolines<'ctx, 'this>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    region: &'this Region<'ctx>,
+    blocks_arena: &'this Bump,
+    init_block: &Block<'ctx>,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    metadata: &mut MetadataStorage,
+    statement_idx: StatementIdx,
+    libfunc: &CoreConcreteLibfunc,
+    branches: Vec<(&'this Block<'ctx>, Vec<BranchArg<'ctx, 'this>>)>,
+    trace_level: TraceLevel,
+    location: Location<'ctx>,
+) -> Result<Vec<(&'this Block<'ctx>, Vec<BranchArg<'ctx, 'this>>)>, Error>
+where
+    'this: 'ctx,
+{
+    branches
+        .into_iter()
+        .zip(libfunc.branch_signatures())
+        .enumerate()
+        .map(|(branch_idx, ((target, operands), branch_signature))| {
+            let mut arg_types = Vec::with_capacity(branch_signature.vars.len());
+            for var_info in &branch_signature.vars {
+                arg_types.push((
+                    registry.get_type(&var_info.ty)?.build(
+                        context,
+                        module,
+                        registry,
+                        metadata,
+                        &var_info.ty,
+                    )?,
+                    location,
+                ));
+            }
+
+            let trampoline: &'this BlockRef<'ctx, 'this> =
+                blocks_arena.alloc(region.append_block(Block::new(&arg_types)));
+
+            let statement_idx = trampoline.const_int(context, location, statement_idx.0, 64)?;
+            let branch_idx = trampoline.const_int(context, location, branch_idx, 64)?;
+            metadata
+                .get_mut::<RuntimeBindingsMeta>()
+                .ok_or(Error::MissingMetadata)?
+                .trace_branch(
+                    context,
+                    module,
+                    trampoline,
+                    location,
+                    statement_idx,
+                    branch_idx,
+                )?;
+
+            if trace_level == TraceLevel::Values {
+                for (idx, var_info) in branch_signature.vars.iter().enumerate() {
+                    // Untraceable values are recorded as missing.
+                    let (value_ptr, size) = build_value_capture(
+                        context,
+                        module,
+                        trampoline,
+                        init_block,
+                        location,
+                        registry,
+                        metadata,
+                        &var_info.ty,
+                        trampoline.arg(idx)?,
+                    )?;
+
+                    let type_id = trampoline.const_int(context, location, var_info.ty.id, 64)?;
+                    metadata
+                        .get_mut::<RuntimeBindingsMeta>()
+                        .ok_or(Error::MissingMetadata)?
+                        .trace_value(
+                            context, module, trampoline, location, type_id, value_ptr, size,
+                        )?;
+                }
+            }
+
+            let operands = operands
+                .into_iter()
+                .map(|operand| match operand {
+                    BranchArg::External(x) => Ok(x),
+                    BranchArg::Returned(i) => trampoline.arg(i),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            trampoline.append_operation(cf::br(target, &operands, location));
+
+            Ok((
+                trampoline.deref(),
+                (0..branch_signature.vars.len())
+                    .map(BranchArg::Returned)
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
 fn generate_branching_targets<'ctx, 'this, 'a>(
     blocks: &'this BlockStorage<'ctx, 'this>,
     statements: &'this [Statement],
@@ -1,7 +1,9 @@
 use crate::{
+    debug::DebugOptions,
     error::{panic::ToNativeAssertError, Error},
     ffi::{get_data_layout_rep, get_target_triple},
     metadata::{
+        coverage::CoverageMeta,
         gas::{GasMetadata, MetadataComputationConfig},
         runtime_bindings::RuntimeBindingsMeta,
         MetadataStorage,
@@ -72,8 +74,67 @@ impl NativeContext {
         program: &Program,
         ignore_debug_names: bool,
         gas_metadata_config: Option<MetadataComputationConfig>,
+    ) -> Result<NativeModule, Error> {
+        self.compile_module(
+            program,
+            ignore_debug_names,
+            gas_metadata_config,
+            DebugOptions::new(),
+            true,
+        )
+    }
+
+    /// Compiles a sierra program into MLIR and then lowers to LLVM, using the given debug options.
+    /// Returns the corresponding NativeModule struct.
+    ///
+    /// See [`DebugOptions`] for the artifacts which can be written and the debug code which can be
+    /// inserted. The options are kept in the module for the later stages.
+    pub fn compile_with_debug_options(
+        &self,
+        program: &Program,
+        ignore_debug_names: bool,
+        gas_metadata_config: Option<MetadataComputationConfig>,
+        debug_options: DebugOptions,
+    ) -> Result<NativeModule, Error> {
+        self.compile_module(
+            program,
+            ignore_debug_names,
+            gas_metadata_config,
+            debug_options,
+            true,
+        )
+    }
+
+    /// Compiles a sierra program into MLIR, without running the MLIR passes which lower it to
+    /// LLVM.
+    ///
+    /// The resulting module can't be executed nor converted to LLVM IR, but it's useful to inspect
+    /// the code generated for each libfunc.
+    pub fn compile_without_passes(
+        &self,
+        program: &Program,
+        ignore_debug_names: bool,
+        gas_metadata_config: Option<MetadataComputationConfig>,
+    ) -> Result<NativeModule, Error> {
+        self.compile_module(
+            program,
+            ignore_debug_names,
+            gas_metadata_config,
+            DebugOptions::new(),
+            false,
+        )
+    }
+
+    fn compile_module(
+        &self,
+        program: &Program,
+        ignore_debug_names: bool,
+        gas_metadata_config: Option<MetadataComputationConfig>,
+        mut debug_options: DebugOptions,
+        run_passes: bool,
     ) -> Result<NativeModule, Error> {
         trace!("starting sierra to mlir compilation");
+        debug_options.resolve_program_name(program);
         let pre_sierra_compilation_instant = Instant::now();
 
         static INITIALIZED: OnceLock<()> = OnceLock::new();
@@ -166,6 +227,13 @@ impl NativeContext {
         // Unwrapping here is not necessary since the insertion will only fail if there was
         // already some metadata of the same type.
         metadata.insert(gas_metadata);
+        if debug_options.coverage {
+            metadata.insert(CoverageMeta::new(program.statements.len()));
+        }
+        #[cfg(feature = "with-debug-utils")]
+        if !debug_options.auto_breakpoint.is_empty() {
+            metadata.insert(debug_options.auto_breakpoint.clone());
+        }
 
         // Create the Sierra program registry
         let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(program)?;
@@ -178,6 +246,7 @@ impl NativeContext {
             &mut metadata,
             unsafe { Attribute::from_raw(di_unit_id) },
             ignore_debug_names,
+            &debug_options,
         )?;
 
         let sierra_compilation_time = pre_sierra_compilation_instant.elapsed().as_millis();
@@ -186,22 +255,12 @@ impl NativeContext {
             "sierra to mlir compilation finished"
         );
 
-        if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-            if x == "1" || x == "true" {
-                std::fs::write("dump-prepass.mlir", module.as_operation().to_string())?;
-                std::fs::write(
-                    "dump-prepass-debug-valid.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-                std::fs::write(
-                    "dump-prepass-debug-pretty.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-            }
+        write_mlir_artifacts(&debug_options, &module, "-prepass")?;
+
+        if !run_passes {
+            return Ok(
+                NativeModule::new(module, registry, metadata).with_debug_options(debug_options)
+            );
         }
 
         trace!("starting mlir passes");
@@ -210,28 +269,32 @@ impl NativeContext {
         let passes_time = pre_passes_instant.elapsed().as_millis();
         trace!(time = passes_time, "mlir passes finished");
 
-        if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-            if x == "1" || x == "true" {
-                std::fs::write("dump.mlir", module.as_operation().to_string())?;
-                std::fs::write(
-                    "dump-debug-pretty.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-                std::fs::write(
-                    "dump-debug.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-            }
-        }
+        write_mlir_artifacts(&debug_options, &module, "")?;
 
-        Ok(NativeModule::new(module, registry, metadata))
+        Ok(NativeModule::new(module, registry, metadata).with_debug_options(debug_options))
     }
 }
 
+/// Write the MLIR module, without and with locations, as debug artifacts.
+fn write_mlir_artifacts(
+    debug_options: &DebugOptions,
+    module: &Module,
+    stage: &str,
+) -> Result<(), Error> {
+    if debug_options.artifacts_dir.is_some() {
+        debug_options
+            .write_artifact(&format!("{stage}.mlir"), module.as_operation().to_string())?;
+        debug_options.write_artifact(
+            &format!("{stage}-debug.mlir"),
+            module.as_operation().to_string_with_flags(
+                OperationPrintingFlags::new().enable_debug_info(true, false),
+            )?,
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Initialize an MLIR context.
 pub fn initialize_mlir() -> Context {
     let context = Context::new();
@@ -1,3 +1,24 @@
+//! # Debugging utilities
+//!
+//! Contains the per-compilation [`DebugOptions`], which control the debug artifacts written during
+//! a compilation, the debug traps, breakpoints and the coverage, profiling, tracing and backtrace
+//! instrumentation inserted into the generated code and the source locations used for its debug
+//! info, the [`Coverage`], [`Profile`], [`ExecutionTrace`] and [`PanicBacktrace`] collected from
+//! instrumented runs (the latter three bundled into their [`DebugResults`]), the
+//! [`StatementHook`] used by interactive debuggers, and the libfunc naming used in the generated
+//! code.
+
+pub use self::{
+    backtrace::{BacktraceFrame, PanicBacktrace},
+    coverage::Coverage,
+    debugger::{with_statement_hook, LiveVariable, StatementHook},
+    profiler::{FunctionProfile, Profile, ProfileMetric, ProfileNode},
+    source_locations::{SourceLocation, SourceLocations},
+    trace::{ExecutionTrace, TraceLevel, TraceStep},
+};
+
+#[cfg(feature = "with-debug-utils")]
+use crate::metadata::auto_breakpoint::AutoBreakpoint;
 use cairo_lang_sierra::extensions::{
     array::ArrayConcreteLibfunc,
     boolean::BoolConcreteLibfunc,
@@ -32,6 +53,264 @@ use cairo_lang_sierra::extensions::{
     },
     structure::StructConcreteLibfunc,
 };
+use cairo_lang_sierra::program::{Program, StatementIdx};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+pub(crate) mod backtrace;
+mod coverage;
+mod debugger;
+pub(crate) mod profiler;
+mod source_locations;
+pub(crate) mod trace;
+
+/// Debugging options for a single compilation.
+///
+/// By default nothing is written to disk and the generated code is left untouched. When an
+/// artifacts directory is set, the following files are written into it, named after the program:
+///
+/// - `{name}.sierra`: The Sierra program being compiled.
+/// - `{name}-prepass.mlir`, `{name}-prepass-debug.mlir`: The MLIR module before the passes,
+///   without and with locations.
+/// - `{name}.mlir`, `{name}-debug.mlir`: The MLIR module after the passes, without and with
+///   locations.
+/// - `{name}.o`: The object file linked into the shared library (AOT only). It's kept so that
+///   debuggers can load its debug info.
+///
+/// Do note that the MLIR with locations is in pretty form and thus not suitable to pass to
+/// `mlir-opt`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugOptions {
+    /// Directory where the debug artifacts are written. Nothing is written if `None`.
+    pub artifacts_dir: Option<PathBuf>,
+    /// Name used for the artifacts' file names. If `None`, a name is derived from a hash of the
+    /// Sierra program so that different programs don't overwrite each other's artifacts.
+    pub program_name: Option<String>,
+    /// Sierra statements before which a debugger trap is inserted. The trap instruction may not end
+    /// up exactly where the statement is.
+    pub trap_at_statements: Vec<StatementIdx>,
+    /// The Cairo source locations of the Sierra statements. When present, the generated debug info
+    /// points at the Cairo code instead of the Sierra statements, so that debuggers can step
+    /// through, break at and backtrace Cairo source lines.
+    pub source_locations: Option<SourceLocations>,
+    /// Append the JIT-compiled functions' symbols to `/tmp/perf-<pid>.map` so that `perf` can
+    /// attribute samples to them. Only used by the JIT executor on Linux.
+    pub perf_map: bool,
+    /// Register the JIT-compiled functions' symbols with the GDB JIT interface so that debuggers
+    /// can symbolize their frames. Only used by the JIT executor on Linux.
+    pub gdb_jit_interface: bool,
+    /// Instrument every statement with a hit counter. The executors can then return the
+    /// [`Coverage`] of the runs.
+    pub coverage: bool,
+    /// Record the gas and the time at every function's entry and exit. The executors then return
+    /// the [`Profile`] of each run in its [`DebugResults`].
+    pub profiler: bool,
+    /// Record every executed statement and the branch it takes, and with [`TraceLevel::Values`]
+    /// also the values it produces. The executors then return the [`ExecutionTrace`] of each run
+    /// in its [`DebugResults`].
+    pub trace: Option<TraceLevel>,
+    /// Maintain a shadow stack of the Cairo functions being executed. The executors then return
+    /// the [`PanicBacktrace`] of the runs which panic in their [`DebugResults`].
+    pub panic_backtraces: bool,
+    /// Call the current thread's [`StatementHook`] before every statement, with the variables
+    /// which are live at the statement (see [`with_statement_hook`]).
+    pub statement_hooks: bool,
+    /// Breakpoints inserted into the generated code when the program reaches certain events.
+    #[cfg(feature = "with-debug-utils")]
+    pub auto_breakpoint: AutoBreakpoint,
+}
+
+impl DebugOptions {
+    pub const fn new() -> Self {
+        Self {
+            artifacts_dir: None,
+            program_name: None,
+            trap_at_statements: Vec::new(),
+            source_locations: None,
+            perf_map: false,
+            gdb_jit_interface: false,
+            coverage: false,
+            profiler: false,
+            trace: None,
+            panic_backtraces: false,
+            statement_hooks: false,
+            #[cfg(feature = "with-debug-utils")]
+            auto_breakpoint: AutoBreakpoint::new(),
+        }
+    }
+
+    /// Write the debug artifacts of the compilation into `artifacts_dir`.
+    pub fn with_artifacts_dir(mut self, artifacts_dir: impl Into<PathBuf>) -> Self {
+        self.artifacts_dir = Some(artifacts_dir.into());
+        self
+    }
+
+    /// Use `program_name` for the artifacts' file names.
+    pub fn with_program_name(mut self, program_name: impl Into<String>) -> Self {
+        self.program_name = Some(program_name.into());
+        self
+    }
+
+    /// Insert a debugger trap before the given statement.
+    pub fn with_trap_at_statement(mut self, statement_idx: StatementIdx) -> Self {
+        self.trap_at_statements.push(statement_idx);
+        self
+    }
+
+    /// Use the given Cairo source locations for the debug info.
+    pub fn with_source_locations(mut self, source_locations: SourceLocations) -> Self {
+        self.source_locations = Some(source_locations);
+        self
+    }
+
+    /// Write the JIT-compiled functions' symbols into a perf map file.
+    pub fn with_perf_map(mut self) -> Self {
+        self.perf_map = true;
+        self
+    }
+
+    /// Register the JIT-compiled functions' symbols with the GDB JIT interface.
+    pub fn with_gdb_jit_interface(mut self) -> Self {
+        self.gdb_jit_interface = true;
+        self
+    }
+
+    /// Instrument every statement with a hit counter.
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = true;
+        self
+    }
+
+    /// Record the gas and the time at every function's entry and exit.
+    pub fn with_profiler(mut self) -> Self {
+        self.profiler = true;
+        self
+    }
+
+    /// Record every executed statement at the given level of detail.
+    pub fn with_trace(mut self, level: TraceLevel) -> Self {
+        self.trace = Some(level);
+        self
+    }
+
+    /// Maintain a shadow stack of the Cairo functions being executed.
+    pub fn with_panic_backtraces(mut self) -> Self {
+        self.panic_backtraces = true;
+        self
+    }
+
+    /// Call the current thread's [`StatementHook`] before every statement.
+    pub fn with_statement_hooks(mut self) -> Self {
+        self.statement_hooks = true;
+        self
+    }
+
+    /// Insert the given breakpoints into the generated code.
+    #[cfg(feature = "with-debug-utils")]
+    pub fn with_auto_breakpoint(mut self, auto_breakpoint: AutoBreakpoint) -> Self {
+        self.auto_breakpoint = auto_breakpoint;
+        self
+    }
+
+    /// Whether the JIT-compiled functions' symbols will be exported to profilers or debuggers,
+    /// which is only supported on Linux.
+    pub const fn exports_jit_symbols(&self) -> bool {
+        cfg!(target_os = "linux") && (self.perf_map || self.gdb_jit_interface)
+    }
+
+    /// Fill in the program name, if missing, using a hash of the program. Does nothing if no
+    /// artifacts are being written.
+    pub(crate) fn resolve_program_name(&mut self, program: &Program) {
+        if self.artifacts_dir.is_some() && self.program_name.is_none() {
+            let mut hasher = DefaultHasher::new();
+            program.to_string().hash(&mut hasher);
+            self.program_name = Some(format!("program-{:016x}", hasher.finish()));
+        }
+    }
+
+    /// Return the path of an artifact given its suffix (including the extension), or `None` if no
+    /// artifacts are being written.
+    pub fn artifact_path(&self, suffix: &str) -> Option<PathBuf> {
+        let name = self.program_name.as_deref().unwrap_or("program");
+        self.artifacts_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{name}{suffix}")))
+    }
+
+    /// Write an artifact if artifacts are being written. The directory is created if necessary.
+    pub(crate) fn write_artifact(
+        &self,
+        suffix: &str,
+        contents: impl AsRef<[u8]>,
+    ) -> std::io::Result<()> {
+        if let Some(path) = self.artifact_path(suffix) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The data collected by the instrumentation of a single run: the profile, trace and backtrace
+/// enabled by the [`DebugOptions`] the program was compiled with.
+///
+/// It's kept aside from the execution results, and can be taken after a run with
+/// [`take_debug_results`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DebugResults {
+    /// The call tree profile, if the program was compiled with the profiler enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+    /// The executed statements, if the program was compiled with tracing enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<ExecutionTrace>,
+    /// The Cairo functions being executed when the panic originated, if the program panicked and
+    /// was compiled with panic backtraces enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<PanicBacktrace>,
+}
+
+impl DebugResults {
+    /// Bundle the collected data, or return `None` if nothing was collected.
+    pub(crate) fn new(
+        profile: Option<Profile>,
+        trace: Option<ExecutionTrace>,
+        backtrace: Option<PanicBacktrace>,
+    ) -> Option<Self> {
+        (profile.is_some() || trace.is_some() || backtrace.is_some()).then_some(Self {
+            profile,
+            trace,
+            backtrace,
+        })
+    }
+}
+
+thread_local! {
+    /// The debug results of the last run on each thread.
+    static DEBUG_RESULTS: RefCell<Option<DebugResults>> = const { RefCell::new(None) };
+}
+
+/// Take the [`DebugResults`] of the last run on the current thread.
+///
+/// Returns `None` if the run's instrumentation didn't collect anything. Every run replaces the
+/// results of the previous one on the same thread, so they should be taken right after the run.
+pub fn take_debug_results() -> Option<DebugResults> {
+    DEBUG_RESULTS.with(|debug_results| debug_results.borrow_mut().take())
+}
+
+/// Keep the results of a run until they're taken.
+pub(crate) fn set_debug_results(debug_results: Option<DebugResults>) {
+    DEBUG_RESULTS.with(|x| *x.borrow_mut() = debug_results);
+}
 
 pub const fn libfunc_to_name(value: &CoreConcreteLibfunc) -> &'static str {
     match value {
@@ -417,3 +696,114 @@ pub const fn libfunc_to_name(value: &CoreConcreteLibfunc) -> &'static str {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::NativeContext, utils::test::load_cairo};
+    use cairo_lang_sierra_generator::statements_code_locations::{
+        SourceCodeSpan, SourceFileFullPath, StatementsSourceCodeLocations,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_debug_options_artifacts() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        // No artifacts are written by default.
+        assert_eq!(DebugOptions::new().artifact_path(".mlir"), None);
+
+        let dir = TempDir::new().unwrap();
+        let context = NativeContext::new();
+
+        let module = context
+            .compile_with_debug_options(
+                &program,
+                false,
+                None,
+                DebugOptions::new()
+                    .with_artifacts_dir(dir.path())
+                    .with_program_name("named"),
+            )
+            .unwrap();
+        assert_eq!(
+            module.debug_options().artifact_path(".o"),
+            Some(dir.path().join("named.o"))
+        );
+        for suffix in [
+            ".sierra",
+            "-prepass.mlir",
+            "-prepass-debug.mlir",
+            ".mlir",
+            "-debug.mlir",
+        ] {
+            assert!(dir.path().join(format!("named{suffix}")).exists());
+        }
+
+        // Unnamed programs are named after their hash.
+        let module = context
+            .compile_with_debug_options(
+                &program,
+                false,
+                None,
+                DebugOptions::new().with_artifacts_dir(dir.path()),
+            )
+            .unwrap();
+        let name = module.debug_options().program_name.clone().unwrap();
+        assert!(name.starts_with("program-"));
+        assert!(dir.path().join(format!("{name}.mlir")).exists());
+    }
+
+    #[test]
+    fn test_debug_options_source_locations() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        // Alternate the statements between two files to check both the function's file and the
+        // lexical blocks for other files.
+        let source_locations = SourceLocations::from(StatementsSourceCodeLocations {
+            statements_to_code_location_map: (0..program.statements.len())
+                .map(|idx| {
+                    let file = if idx % 2 == 0 {
+                        "lib.cairo"
+                    } else {
+                        "other.cairo"
+                    };
+                    (
+                        StatementIdx(idx),
+                        vec![(
+                            SourceFileFullPath(format!("/tmp/src/{file}")),
+                            SourceCodeSpan::default(),
+                        )],
+                    )
+                })
+                .collect(),
+        });
+
+        let context = NativeContext::new();
+        let module = context
+            .compile_with_debug_options(
+                &program,
+                false,
+                None,
+                DebugOptions::new().with_source_locations(source_locations),
+            )
+            .unwrap();
+
+        let mlir = module
+            .module()
+            .as_operation()
+            .to_string_with_flags(
+                melior::ir::operation::OperationPrintingFlags::new().enable_debug_info(true, false),
+            )
+            .unwrap();
+        assert!(mlir.contains("/tmp/src/lib.cairo"));
+    }
+}
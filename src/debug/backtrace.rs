@@ -0,0 +1,231 @@
+use super::SourceLocations;
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+    ids::{ConcreteTypeId, FunctionId},
+    program::StatementIdx,
+    program_registry::ProgramRegistry,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+/// The shadow call stack maintained by the backtrace instrumentation.
+#[derive(Debug, Default)]
+pub(crate) struct ShadowStack {
+    /// The function id and the statement being executed of every frame, outermost first.
+    frames: Vec<(u64, u64)>,
+    /// The frames as they were when a panic was first returned.
+    panic_frames: Option<Vec<(u64, u64)>>,
+}
+
+impl ShadowStack {
+    /// Enter a function at its entry point.
+    pub fn push(&mut self, function_id: u64, statement_idx: u64) {
+        self.frames.push((function_id, statement_idx));
+    }
+
+    /// Update the statement being executed by the innermost function, before it calls another one.
+    pub fn set_statement(&mut self, statement_idx: u64) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.1 = statement_idx;
+        }
+    }
+
+    /// Return from the innermost function at the given statement.
+    ///
+    /// Panics propagate through every caller, so the stack is only captured at the first function
+    /// which returns one: the function where the panic originated.
+    pub fn pop(&mut self, statement_idx: u64, panicking: bool) {
+        self.set_statement(statement_idx);
+        if panicking && self.panic_frames.is_none() {
+            self.panic_frames = Some(self.frames.clone());
+        }
+
+        self.frames.pop();
+    }
+
+    /// Build the backtrace of the panic, unless no panic was returned. Without a registry, the
+    /// functions are named after their ids.
+    pub fn into_backtrace(
+        self,
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Option<PanicBacktrace> {
+        let frames = self.panic_frames?;
+
+        Some(PanicBacktrace {
+            frames: frames
+                .into_iter()
+                .rev()
+                .map(|(function_id, statement_idx)| {
+                    let function_id = FunctionId::new(function_id);
+                    BacktraceFrame {
+                        // Recover the debug name from the program.
+                        function_id: registry
+                            .and_then(|registry| registry.get_function(&function_id).ok())
+                            .map_or(function_id, |function| function.id.clone()),
+                        statement_idx: StatementIdx(statement_idx as usize),
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+/// A function in a [`PanicBacktrace`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BacktraceFrame {
+    pub function_id: FunctionId,
+    /// The statement being executed by the function: the return statement for the innermost
+    /// frame, and the call to the next frame for the others.
+    pub statement_idx: StatementIdx,
+}
+
+/// The Cairo functions which were being executed when a panic originated, innermost first.
+///
+/// It's collected from programs compiled with backtraces enabled (see
+/// [`DebugOptions::with_panic_backtraces`](super::DebugOptions::with_panic_backtraces)) and taken
+/// with [`take_debug_results`](super::take_debug_results) after the runs which panicked. Functions
+/// inlined by the Cairo compiler don't have frames of their own, and tail-recursive calls are
+/// compiled into loops and therefore merged into their first call.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanicBacktrace {
+    pub frames: Vec<BacktraceFrame>,
+}
+
+impl PanicBacktrace {
+    /// Format the backtrace with a frame per line, followed by the frame's Cairo source location
+    /// when the program's [`SourceLocations`] are given and contain the frame's statement.
+    pub fn format(&self, source_locations: Option<&SourceLocations>) -> String {
+        let mut output = String::new();
+        for (idx, frame) in self.frames.iter().enumerate() {
+            writeln!(
+                output,
+                "{idx:>4}: {} (statement {})",
+                frame.function_id, frame.statement_idx
+            )
+            .unwrap();
+            if let Some(location) =
+                source_locations.and_then(|locations| locations.get(frame.statement_idx))
+            {
+                writeln!(
+                    output,
+                    "          at {}:{}:{}",
+                    location.file, location.line, location.col
+                )
+                .unwrap();
+            }
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for PanicBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(None))
+    }
+}
+
+/// Whether a type is a `PanicResult`: an enum whose second variant holds the `Panic` marker (an
+/// empty struct) and the panic data.
+pub(crate) fn is_panic_result(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    type_id: &ConcreteTypeId,
+) -> bool {
+    let Ok(CoreTypeConcrete::Enum(info)) = registry.get_type(type_id) else {
+        return false;
+    };
+    let [_, err_ty] = info.variants.as_slice() else {
+        return false;
+    };
+    let Ok(CoreTypeConcrete::Struct(err_info)) = registry.get_type(err_ty) else {
+        return false;
+    };
+
+    match err_info.members.as_slice() {
+        [panic_ty, data_ty] => {
+            matches!(
+                registry.get_type(panic_ty),
+                Ok(CoreTypeConcrete::Struct(panic_info)) if panic_info.members.is_empty()
+            ) && matches!(registry.get_type(data_ty), Ok(CoreTypeConcrete::Array(_)))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_cairo;
+    use cairo_lang_sierra_generator::statements_code_locations::{
+        SourceCodeLocation, SourceCodeSpan, SourceFileFullPath, StatementsSourceCodeLocations,
+    };
+
+    #[test]
+    fn test_shadow_stack_backtrace() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let registry = ProgramRegistry::new(&program).unwrap();
+        let root_id = program.funcs[0].id.id;
+
+        // The callee at the top returns normally, then the next one panics and the panic
+        // propagates.
+        let mut stack = ShadowStack::default();
+        stack.push(root_id, 0);
+        stack.set_statement(3);
+        stack.push(1000, 10);
+        stack.set_statement(12);
+        stack.push(2000, 20);
+        stack.pop(21, false);
+        stack.set_statement(13);
+        stack.push(3000, 30);
+        stack.pop(35, true);
+        stack.pop(14, true);
+        stack.pop(4, true);
+
+        let backtrace = stack.into_backtrace(Some(&registry)).unwrap();
+        assert_eq!(
+            backtrace
+                .frames
+                .iter()
+                .map(|frame| (frame.function_id.id, frame.statement_idx.0))
+                .collect::<Vec<_>>(),
+            [(3000, 35), (1000, 13), (root_id, 3)]
+        );
+        assert_eq!(
+            backtrace.frames[2].function_id.debug_name,
+            program.funcs[0].id.debug_name
+        );
+
+        let source_locations = SourceLocations::from(StatementsSourceCodeLocations {
+            statements_to_code_location_map: [(
+                StatementIdx(35),
+                vec![(
+                    SourceFileFullPath("/tmp/lib.cairo".to_string()),
+                    SourceCodeSpan {
+                        start: SourceCodeLocation { line: 6, col: 8 },
+                        end: SourceCodeLocation { line: 6, col: 20 },
+                    },
+                )],
+            )]
+            .into_iter()
+            .collect(),
+        });
+        assert_eq!(
+            backtrace.format(Some(&source_locations)),
+            format!(
+                "   0: [3000] (statement 35)\n          at /tmp/lib.cairo:7:9\n   \
+                 1: [1000] (statement 13)\n   2: {} (statement 3)\n",
+                program.funcs[0].id
+            )
+        );
+
+        // Nothing is captured unless a panic is returned.
+        let mut stack = ShadowStack::default();
+        stack.push(root_id, 0);
+        stack.pop(1, false);
+        assert_eq!(stack.into_backtrace(Some(&registry)), None);
+    }
+}
@@ -0,0 +1,149 @@
+use super::SourceLocations;
+use cairo_lang_sierra::program::StatementIdx;
+use std::{collections::BTreeMap, fmt::Write};
+
+/// The number of times each Sierra statement was executed.
+///
+/// It's collected from programs compiled with coverage instrumentation (see
+/// [`DebugOptions::with_coverage`](super::DebugOptions::with_coverage)) and can be projected onto
+/// the Cairo source lines using the program's [`SourceLocations`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    hits: Vec<u64>,
+}
+
+impl Coverage {
+    /// Create the coverage from the hit counts of every statement, indexed by statement.
+    pub fn from_hits(hits: Vec<u64>) -> Self {
+        Self { hits }
+    }
+
+    /// Return the number of times the statement was executed.
+    pub fn hits(&self, statement_idx: StatementIdx) -> u64 {
+        self.hits.get(statement_idx.0).copied().unwrap_or_default()
+    }
+
+    /// Return the number of instrumented statements.
+    pub fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    /// Return the number of statements executed at least once.
+    pub fn covered_statements(&self) -> usize {
+        self.hits.iter().filter(|hits| **hits > 0).count()
+    }
+
+    /// Iterate over the hit counts of every statement.
+    pub fn iter(&self) -> impl Iterator<Item = (StatementIdx, u64)> + '_ {
+        self.hits
+            .iter()
+            .enumerate()
+            .map(|(idx, hits)| (StatementIdx(idx), *hits))
+    }
+
+    /// Add the hit counts of another run of the same program.
+    pub fn merge(&mut self, other: &Self) {
+        if self.hits.len() < other.hits.len() {
+            self.hits.resize(other.hits.len(), 0);
+        }
+        for (hits, other_hits) in self.hits.iter_mut().zip(&other.hits) {
+            *hits += other_hits;
+        }
+    }
+
+    /// Project the hit counts onto the Cairo source lines, grouped by file.
+    ///
+    /// Statements are attributed to the code which generated them, not to the call sites they were
+    /// inlined into. Since a line usually generates several statements, a line's hit count is the
+    /// highest among its statements.
+    pub fn line_hits(
+        &self,
+        source_locations: &SourceLocations,
+    ) -> BTreeMap<String, BTreeMap<usize, u64>> {
+        let mut files = BTreeMap::<String, BTreeMap<usize, u64>>::new();
+        for (statement_idx, hits) in self.iter() {
+            if let Some(location) = source_locations.get(statement_idx) {
+                let line_hits = files
+                    .entry(location.file.clone())
+                    .or_default()
+                    .entry(location.line)
+                    .or_default();
+                *line_hits = (*line_hits).max(hits);
+            }
+        }
+
+        files
+    }
+
+    /// Export the line coverage in the LCOV tracefile format.
+    pub fn to_lcov(&self, source_locations: &SourceLocations) -> String {
+        let mut lcov = String::from("TN:\n");
+        for (file, lines) in self.line_hits(source_locations) {
+            writeln!(lcov, "SF:{file}").unwrap();
+            for (line, hits) in &lines {
+                writeln!(lcov, "DA:{line},{hits}").unwrap();
+            }
+            writeln!(lcov, "LF:{}", lines.len()).unwrap();
+            writeln!(
+                lcov,
+                "LH:{}",
+                lines.values().filter(|hits| **hits > 0).count()
+            )
+            .unwrap();
+            lcov.push_str("end_of_record\n");
+        }
+
+        lcov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_lang_sierra_generator::statements_code_locations::{
+        SourceCodeLocation, SourceCodeSpan, SourceFileFullPath, StatementsSourceCodeLocations,
+    };
+
+    #[test]
+    fn test_coverage_to_lcov() {
+        let location = |file: &str, line| {
+            (
+                SourceFileFullPath(file.to_string()),
+                SourceCodeSpan {
+                    start: SourceCodeLocation { line, col: 0 },
+                    end: SourceCodeLocation { line, col: 1 },
+                },
+            )
+        };
+        let source_locations = SourceLocations::from(StatementsSourceCodeLocations {
+            statements_to_code_location_map: [
+                (StatementIdx(0), vec![location("/tmp/lib.cairo", 0)]),
+                (StatementIdx(1), vec![location("/tmp/lib.cairo", 0)]),
+                (
+                    StatementIdx(2),
+                    vec![location("/tmp/a.cairo", 4), location("/tmp/lib.cairo", 1)],
+                ),
+                (StatementIdx(3), vec![location("/tmp/lib.cairo", 2)]),
+            ]
+            .into_iter()
+            .collect(),
+        });
+
+        let mut coverage = Coverage::from_hits(vec![1, 3, 2, 0, 5]);
+        coverage.merge(&Coverage::from_hits(vec![1, 0, 0, 0]));
+        assert_eq!(coverage.hits(StatementIdx(0)), 2);
+        assert_eq!(coverage.hits(StatementIdx(7)), 0);
+        assert_eq!(coverage.covered_statements(), 4);
+
+        assert_eq!(
+            coverage.to_lcov(&source_locations),
+            "TN:\n\
+             SF:/tmp/a.cairo\nDA:5,2\nLF:1\nLH:1\nend_of_record\n\
+             SF:/tmp/lib.cairo\nDA:1,3\nDA:3,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+}
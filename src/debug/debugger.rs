@@ -0,0 +1,117 @@
+use crate::{error::Result, runtime::STATEMENT_HOOK, values::Value};
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType},
+    ids::{ConcreteTypeId, VarId},
+    program::StatementIdx,
+    program_registry::ProgramRegistry,
+};
+use std::ptr::NonNull;
+
+/// A hook called before every statement of the programs compiled with statement hooks, with the
+/// variables which are live at the statement.
+pub type StatementHook = dyn FnMut(StatementIdx, &[LiveVariable]);
+
+/// A variable which is live at a statement, as passed to a [`StatementHook`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiveVariable {
+    pub var_id: VarId,
+    pub type_id: ConcreteTypeId,
+    /// A copy of the variable's value, as taken by the runtime. It's missing for the types which
+    /// refer to memory owned by the program.
+    data: Option<Vec<u128>>,
+}
+
+impl LiveVariable {
+    pub(crate) fn new(var_id: u64, type_id: u64, data: Option<Vec<u128>>) -> Self {
+        Self {
+            var_id: VarId::new(var_id),
+            type_id: ConcreteTypeId::new(type_id),
+            data,
+        }
+    }
+
+    /// Decode the variable's value, or return `None` if its type refers to memory owned by the
+    /// program (for example, arrays, boxes or dictionaries), in which case it isn't available.
+    pub fn value(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<Option<Value>> {
+        match &self.data {
+            Some(data) => {
+                // Decoding may modify the data in place.
+                let mut data = data.clone();
+                Ok(Some(Value::from_ptr(
+                    NonNull::from(data.as_mut_slice()).cast(),
+                    &self.type_id,
+                    registry,
+                    false,
+                )?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Call `f` with `hook` installed as the current thread's [`StatementHook`], and restore the
+/// previous one afterwards.
+///
+/// The hook is called from the generated code, so it's only called by the programs which are
+/// compiled with statement hooks (see
+/// [`DebugOptions::with_statement_hooks`](super::DebugOptions::with_statement_hooks)) and executed
+/// by `f` in the current thread. Execution resumes when the hook returns, which makes it suitable
+/// to implement interactive debuggers.
+pub fn with_statement_hook<T>(
+    hook: impl FnMut(StatementIdx, &[LiveVariable]) + 'static,
+    f: impl FnOnce() -> T,
+) -> T {
+    /// Restores the previous hook, even if `f` panics.
+    struct HookGuard(Option<Box<StatementHook>>);
+
+    impl Drop for HookGuard {
+        fn drop(&mut self) {
+            STATEMENT_HOOK.set(self.0.take());
+        }
+    }
+
+    let _guard = HookGuard(STATEMENT_HOOK.replace(Some(Box::new(hook))));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_cairo;
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn test_live_variable_value() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let registry = ProgramRegistry::new(&program).unwrap();
+        let felt252_id = program
+            .type_declarations
+            .iter()
+            .find(|decl| decl.long_id.generic_id.0 == "felt252")
+            .unwrap()
+            .id
+            .id;
+
+        let variable = LiveVariable::new(3, felt252_id, Some(vec![42, 0]));
+        assert_eq!(variable.var_id, VarId::new(3));
+        assert_eq!(
+            variable.value(&registry).unwrap(),
+            Some(Value::Felt252(Felt::from(42)))
+        );
+        // Decoding twice yields the same value.
+        assert_eq!(
+            variable.value(&registry).unwrap(),
+            Some(Value::Felt252(Felt::from(42)))
+        );
+
+        let variable = LiveVariable::new(4, felt252_id, None);
+        assert_eq!(variable.value(&registry).unwrap(), None);
+    }
+}
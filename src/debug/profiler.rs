@@ -0,0 +1,331 @@
+use crate::utils::generate_function_name;
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType},
+    ids::FunctionId,
+    program_registry::ProgramRegistry,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// A function entry or exit recorded by the profiling instrumentation.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProfilerEvent {
+    pub kind: ProfilerEventKind,
+    pub function_id: u64,
+    pub gas: u64,
+    pub instant: Instant,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ProfilerEventKind {
+    Enter,
+    Exit,
+}
+
+/// The metric used to weight the stacks of a folded profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileMetric {
+    /// The gas consumed, in gas units.
+    Gas,
+    /// The execution time, in nanoseconds.
+    Time,
+}
+
+/// A node in the call tree, which aggregates every call to a function from the same call stack.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileNode {
+    pub function_id: FunctionId,
+    /// The number of times the function was called from this call stack.
+    pub calls: u64,
+    /// The gas consumed by the function, including its callees.
+    pub inclusive_gas: u64,
+    /// The time spent in the function, including its callees.
+    pub inclusive_time: Duration,
+    pub children: Vec<ProfileNode>,
+}
+
+impl ProfileNode {
+    /// Return the gas consumed by the function itself, excluding its callees.
+    pub fn exclusive_gas(&self) -> u64 {
+        self.inclusive_gas.saturating_sub(
+            self.children
+                .iter()
+                .map(|child| child.inclusive_gas)
+                .sum::<u64>(),
+        )
+    }
+
+    /// Return the time spent in the function itself, excluding its callees.
+    pub fn exclusive_time(&self) -> Duration {
+        self.inclusive_time.saturating_sub(
+            self.children
+                .iter()
+                .map(|child| child.inclusive_time)
+                .sum::<Duration>(),
+        )
+    }
+}
+
+/// The totals of a function across the whole call tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// The gas consumed by the function, including its callees. Recursive calls aren't counted
+    /// twice.
+    pub inclusive_gas: u64,
+    pub exclusive_gas: u64,
+    /// The time spent in the function, including its callees. Recursive calls aren't counted
+    /// twice.
+    pub inclusive_time: Duration,
+    pub exclusive_time: Duration,
+}
+
+/// The call tree of an execution with the gas consumed and the time spent in each function.
+///
+/// It's collected from programs compiled with profiling instrumentation (see
+/// [`DebugOptions::with_profiler`](super::DebugOptions::with_profiler)) and taken after the run
+/// with [`take_debug_results`](super::take_debug_results).
+///
+/// The gas is read from each function's gas builtin, so functions without one (whose cost is
+/// withdrawn by their callers) don't report any gas. Tail-recursive calls are compiled into loops
+/// which jump back past the instrumented function entry, so a whole tail recursion is counted as a
+/// single call, with the gas and time of every iteration.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// The functions called by the entry point wrapper; usually only the entry point itself.
+    pub roots: Vec<ProfileNode>,
+}
+
+impl Profile {
+    /// Build the call tree from the recorded events. Calls which didn't return, because the
+    /// execution was aborted, end at the last event. Without a registry, the functions are named
+    /// after their ids.
+    pub(crate) fn from_events(
+        events: &[ProfilerEvent],
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Self {
+        struct Node {
+            function_id: u64,
+            calls: u64,
+            gas: u64,
+            time: Duration,
+            children: Vec<usize>,
+        }
+
+        // The first node is a placeholder for the roots' parent.
+        let mut nodes = vec![Node {
+            function_id: 0,
+            calls: 0,
+            gas: 0,
+            time: Duration::ZERO,
+            children: Vec::new(),
+        }];
+        let mut stack = Vec::<(usize, &ProfilerEvent)>::new();
+        let close = |node: &mut Node, enter: &ProfilerEvent, gas: u64, instant: Instant| {
+            node.gas += enter.gas.saturating_sub(gas);
+            node.time += instant.saturating_duration_since(enter.instant);
+        };
+
+        for event in events {
+            match event.kind {
+                ProfilerEventKind::Enter => {
+                    let parent = stack.last().map_or(0, |(node, _)| *node);
+                    let node = match nodes[parent]
+                        .children
+                        .iter()
+                        .copied()
+                        .find(|child| nodes[*child].function_id == event.function_id)
+                    {
+                        Some(node) => node,
+                        None => {
+                            let node = nodes.len();
+                            nodes.push(Node {
+                                function_id: event.function_id,
+                                calls: 0,
+                                gas: 0,
+                                time: Duration::ZERO,
+                                children: Vec::new(),
+                            });
+                            nodes[parent].children.push(node);
+                            node
+                        }
+                    };
+
+                    nodes[node].calls += 1;
+                    stack.push((node, event));
+                }
+                ProfilerEventKind::Exit => {
+                    if let Some((node, enter)) = stack.pop() {
+                        close(&mut nodes[node], enter, event.gas, event.instant);
+                    }
+                }
+            }
+        }
+        if let Some(last) = events.last() {
+            while let Some((node, enter)) = stack.pop() {
+                close(&mut nodes[node], enter, last.gas, last.instant);
+            }
+        }
+
+        fn build(
+            nodes: &[Node],
+            node: usize,
+            registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+        ) -> ProfileNode {
+            let function_id = FunctionId::new(nodes[node].function_id);
+            ProfileNode {
+                // Recover the debug name from the program.
+                function_id: registry
+                    .and_then(|registry| registry.get_function(&function_id).ok())
+                    .map_or(function_id, |function| function.id.clone()),
+                calls: nodes[node].calls,
+                inclusive_gas: nodes[node].gas,
+                inclusive_time: nodes[node].time,
+                children: nodes[node]
+                    .children
+                    .iter()
+                    .map(|child| build(nodes, *child, registry))
+                    .collect(),
+            }
+        }
+
+        Self {
+            roots: nodes[0]
+                .children
+                .iter()
+                .map(|node| build(&nodes, *node, registry))
+                .collect(),
+        }
+    }
+
+    /// Return the totals of every called function.
+    pub fn functions(&self) -> HashMap<FunctionId, FunctionProfile> {
+        fn visit(
+            node: &ProfileNode,
+            stack: &mut Vec<u64>,
+            functions: &mut HashMap<FunctionId, FunctionProfile>,
+        ) {
+            let function = functions.entry(node.function_id.clone()).or_default();
+            function.calls += node.calls;
+            function.exclusive_gas += node.exclusive_gas();
+            function.exclusive_time += node.exclusive_time();
+            if !stack.contains(&node.function_id.id) {
+                function.inclusive_gas += node.inclusive_gas;
+                function.inclusive_time += node.inclusive_time;
+            }
+
+            stack.push(node.function_id.id);
+            for child in &node.children {
+                visit(child, stack, functions);
+            }
+            stack.pop();
+        }
+
+        let mut functions = HashMap::new();
+        for root in &self.roots {
+            visit(root, &mut Vec::new(), &mut functions);
+        }
+
+        functions
+    }
+
+    /// Export the call tree in the folded stacks format used by flamegraph tools (for example,
+    /// `inferno-flamegraph` or `flamegraph.pl`). Each line contains a call stack, with the
+    /// functions named as in the generated code, followed by its exclusive gas or time.
+    pub fn to_folded(&self, metric: ProfileMetric) -> String {
+        fn visit(node: &ProfileNode, path: &str, metric: ProfileMetric, folded: &mut String) {
+            let path = match path {
+                "" => generate_function_name(&node.function_id, false).into_owned(),
+                path => format!(
+                    "{path};{}",
+                    generate_function_name(&node.function_id, false)
+                ),
+            };
+
+            let value = match metric {
+                ProfileMetric::Gas => u128::from(node.exclusive_gas()),
+                ProfileMetric::Time => node.exclusive_time().as_nanos(),
+            };
+            if value > 0 {
+                writeln!(folded, "{path} {value}").unwrap();
+            }
+
+            for child in &node.children {
+                visit(child, &path, metric, folded);
+            }
+        }
+
+        let mut folded = String::new();
+        for root in &self.roots {
+            visit(root, "", metric, &mut folded);
+        }
+
+        folded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_cairo;
+
+    #[test]
+    fn test_profile_from_events() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let registry = ProgramRegistry::new(&program).unwrap();
+
+        // The callee isn't part of the program, so it's named after its id.
+        let root_id = program.funcs[0].id.id;
+        let start = Instant::now();
+        let event = |kind, function_id, gas, millis| ProfilerEvent {
+            kind,
+            function_id,
+            gas,
+            instant: start + Duration::from_millis(millis),
+        };
+        let profile = Profile::from_events(
+            &[
+                event(ProfilerEventKind::Enter, root_id, 1000, 0),
+                event(ProfilerEventKind::Enter, 1000, 900, 1),
+                event(ProfilerEventKind::Exit, 1000, 800, 3),
+                event(ProfilerEventKind::Enter, 1000, 800, 4),
+                event(ProfilerEventKind::Enter, 1000, 700, 5),
+                event(ProfilerEventKind::Exit, 1000, 650, 6),
+                event(ProfilerEventKind::Exit, 1000, 600, 7),
+                event(ProfilerEventKind::Exit, root_id, 500, 10),
+            ],
+            Some(&registry),
+        );
+
+        let root = &profile.roots[0];
+        assert_eq!(root.function_id.debug_name, program.funcs[0].id.debug_name);
+        assert_eq!((root.calls, root.inclusive_gas), (1, 500));
+        assert_eq!(root.exclusive_gas(), 200);
+        assert_eq!(root.exclusive_time(), Duration::from_millis(5));
+
+        let callee = &root.children[0];
+        assert_eq!((callee.calls, callee.inclusive_gas), (2, 300));
+        assert_eq!(callee.children[0].inclusive_gas, 50);
+
+        // Recursive calls are only counted once in the inclusive totals.
+        let callee = &profile.functions()[&FunctionId::new(1000)];
+        assert_eq!(
+            (callee.calls, callee.inclusive_gas, callee.exclusive_gas),
+            (3, 300, 300)
+        );
+
+        let root_name = generate_function_name(&program.funcs[0].id, false);
+        assert_eq!(
+            profile.to_folded(ProfileMetric::Gas),
+            format!("{root_name} 200\n{root_name};f1000 250\n{root_name};f1000;f1000 50\n")
+        );
+    }
+}
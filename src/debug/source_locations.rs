@@ -0,0 +1,138 @@
+use cairo_lang_sierra::{debug_info::Annotations, program::StatementIdx};
+use cairo_lang_sierra_generator::statements_code_locations::{
+    SourceCodeSpan, SourceFileFullPath, StatementsSourceCodeLocations,
+};
+use std::collections::HashMap;
+
+/// The annotation key under which the Cairo compiler stores the statements' code locations.
+const CODE_LOCATIONS_ANNOTATION: &str = "github.com/software-mansion/cairo-coverage";
+
+/// A location in a Cairo source file. Lines and columns are 1-based, like in DWARF and most
+/// editors.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl From<(SourceFileFullPath, SourceCodeSpan)> for SourceLocation {
+    fn from((file, span): (SourceFileFullPath, SourceCodeSpan)) -> Self {
+        Self {
+            file: file.0,
+            line: span.start.line + 1,
+            col: span.start.col + 1,
+        }
+    }
+}
+
+/// The mapping between Sierra statements and the Cairo code which generated them.
+///
+/// It's built from the statements' code locations the Cairo compiler adds to the program's debug
+/// info (see `CompilerConfig::add_statements_code_locations`). Each statement maps to a chain of
+/// locations: the code which generated it first, followed by the call sites it was inlined into.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceLocations {
+    statements: HashMap<StatementIdx, Vec<SourceLocation>>,
+}
+
+impl SourceLocations {
+    /// Extract the statements' code locations from the debug info annotations, if present.
+    pub fn from_annotations(annotations: &Annotations) -> Option<Self> {
+        let locations = annotations
+            .get(CODE_LOCATIONS_ANNOTATION)?
+            .get("statements_code_locations")?;
+
+        serde_json::from_value::<HashMap<StatementIdx, Vec<(SourceFileFullPath, SourceCodeSpan)>>>(
+            locations.clone(),
+        )
+        .ok()
+        .map(|statements_to_code_location_map| {
+            StatementsSourceCodeLocations {
+                statements_to_code_location_map,
+            }
+            .into()
+        })
+    }
+
+    /// Return the location of the Cairo code which generated the statement.
+    pub fn get(&self, statement_idx: StatementIdx) -> Option<&SourceLocation> {
+        self.get_all(statement_idx).first()
+    }
+
+    /// Return the location of the Cairo code which generated the statement, followed by the call
+    /// sites it was inlined into.
+    pub fn get_all(&self, statement_idx: StatementIdx) -> &[SourceLocation] {
+        self.statements
+            .get(&statement_idx)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Iterate over the statements which have a location.
+    pub fn iter(&self) -> impl Iterator<Item = (StatementIdx, &[SourceLocation])> {
+        self.statements
+            .iter()
+            .map(|(statement_idx, locations)| (*statement_idx, locations.as_slice()))
+    }
+}
+
+impl From<StatementsSourceCodeLocations> for SourceLocations {
+    fn from(value: StatementsSourceCodeLocations) -> Self {
+        Self {
+            statements: value
+                .statements_to_code_location_map
+                .into_iter()
+                .map(|(statement_idx, locations)| {
+                    (
+                        statement_idx,
+                        locations.into_iter().map(SourceLocation::from).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+    use serde_json::json;
+
+    #[test]
+    fn test_source_locations_from_annotations() {
+        let annotations: Annotations = OrderedHashMap::from([(
+            CODE_LOCATIONS_ANNOTATION.to_string(),
+            json!({
+                "statements_code_locations": {
+                    "3": [
+                        ["/tmp/lib.cairo", { "start": { "line": 4, "col": 8 }, "end": { "line": 4, "col": 13 } }],
+                        ["/tmp/main.cairo", { "start": { "line": 0, "col": 0 }, "end": { "line": 0, "col": 5 } }],
+                    ],
+                },
+            }),
+        )]);
+
+        let locations = SourceLocations::from_annotations(&annotations).unwrap();
+        assert_eq!(
+            locations.get(StatementIdx(3)),
+            Some(&SourceLocation {
+                file: "/tmp/lib.cairo".to_string(),
+                line: 5,
+                col: 9,
+            })
+        );
+        assert_eq!(locations.get_all(StatementIdx(3)).len(), 2);
+        assert_eq!(locations.get(StatementIdx(0)), None);
+
+        assert_eq!(
+            SourceLocations::from_annotations(&Annotations::default()),
+            None
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{error::Result, values::Value};
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+    ids::ConcreteTypeId,
+    program::StatementIdx,
+    program_registry::ProgramRegistry,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, ptr::NonNull};
+
+/// An event recorded by the tracing instrumentation.
+#[derive(Clone, Debug)]
+pub(crate) enum TraceEvent {
+    Statement(u64),
+    Branch {
+        statement_idx: u64,
+        branch_idx: u64,
+    },
+    /// A value produced by the last recorded branch, as copied by the runtime. The data is missing
+    /// for the types which aren't traced.
+    Value {
+        type_id: u64,
+        data: Option<Vec<u128>>,
+    },
+}
+
+/// How much is recorded by the tracing instrumentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceLevel {
+    /// Record the executed statements and the branches taken.
+    Statements,
+    /// Also record the values produced by every statement.
+    Values,
+}
+
+/// An executed statement.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub statement_idx: StatementIdx,
+    /// The branch taken by the invocation, or `None` for return statements and for invocations
+    /// which didn't complete because the execution was aborted.
+    pub branch: Option<usize>,
+    /// The values produced by the branch taken, in the order of the branch's results. Only
+    /// recorded with [`TraceLevel::Values`].
+    ///
+    /// Values which refer to memory owned by the program (for example, arrays, boxes or
+    /// dictionaries) may be released before the execution ends and are therefore not recorded;
+    /// they're `None` instead.
+    pub values: Vec<Option<Value>>,
+}
+
+/// The sequence of statements executed by a run, with the branches they took and optionally the
+/// values they produced.
+///
+/// It's collected from programs compiled with tracing instrumentation (see
+/// [`DebugOptions::with_trace`](super::DebugOptions::with_trace)) and taken after the run with
+/// [`take_debug_results`](super::take_debug_results). The steps of a called function
+/// appear between the call statement and the branch it takes when the callee returns, so that a
+/// run can be lined up statement by statement with another implementation's.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl ExecutionTrace {
+    /// Build the trace from the recorded events. Without a registry the values can't be decoded,
+    /// so they're left empty.
+    pub(crate) fn from_events(
+        events: Vec<TraceEvent>,
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Result<Self> {
+        let mut steps = Vec::<TraceStep>::new();
+        // The steps waiting for their branch, by statement. A statement may be waiting more than
+        // once when it's a call within a recursion, in which case the innermost call returns first.
+        let mut pending = HashMap::<u64, Vec<usize>>::new();
+        let mut current = None;
+
+        for event in events {
+            match event {
+                TraceEvent::Statement(statement_idx) => {
+                    pending.entry(statement_idx).or_default().push(steps.len());
+                    steps.push(TraceStep {
+                        statement_idx: StatementIdx(statement_idx as usize),
+                        branch: None,
+                        values: Vec::new(),
+                    });
+                }
+                TraceEvent::Branch {
+                    statement_idx,
+                    branch_idx,
+                } => {
+                    current = pending.get_mut(&statement_idx).and_then(Vec::pop);
+                    if let Some(step) = current {
+                        steps[step].branch = Some(branch_idx as usize);
+                    }
+                }
+                TraceEvent::Value { type_id, data } => {
+                    if let Some(step) = current {
+                        let value = match (data, registry) {
+                            (Some(mut data), Some(registry)) => Some(Value::from_ptr(
+                                NonNull::from(data.as_mut_slice()).cast(),
+                                &ConcreteTypeId::new(type_id),
+                                registry,
+                                false,
+                            )?),
+                            _ => None,
+                        };
+                        steps[step].values.push(value);
+                    }
+                }
+            }
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// Return the index of the first step in which both traces differ, or `None` if they're equal.
+    /// A trace which is a prefix of the other differs at the step following its end.
+    pub fn first_divergence(&self, other: &Self) -> Option<usize> {
+        self.steps
+            .iter()
+            .zip(&other.steps)
+            .position(|(lhs, rhs)| lhs != rhs)
+            .or_else(|| {
+                (self.steps.len() != other.steps.len())
+                    .then(|| self.steps.len().min(other.steps.len()))
+            })
+    }
+}
+
+impl fmt::Display for ExecutionTrace {
+    /// Write a step per line, with the statement index, the branch taken and the values produced.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            write!(f, "{}", step.statement_idx)?;
+            if let Some(branch) = step.branch {
+                write!(f, " -> {branch}")?;
+            }
+            for value in &step.values {
+                match value {
+                    Some(value) => write!(f, " {value:?}")?,
+                    None => write!(f, " _")?,
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the values of a type can be recorded in a trace, which requires them not to refer to
+/// any memory owned by the program.
+pub(crate) fn is_traceable(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    type_id: &ConcreteTypeId,
+) -> Result<bool> {
+    Ok(match registry.get_type(type_id)? {
+        CoreTypeConcrete::Felt252(_)
+        | CoreTypeConcrete::Bytes31(_)
+        | CoreTypeConcrete::BoundedInt(_)
+        | CoreTypeConcrete::EcPoint(_)
+        | CoreTypeConcrete::EcState(_)
+        | CoreTypeConcrete::Uint8(_)
+        | CoreTypeConcrete::Uint16(_)
+        | CoreTypeConcrete::Uint32(_)
+        | CoreTypeConcrete::Uint64(_)
+        | CoreTypeConcrete::Uint128(_)
+        | CoreTypeConcrete::Sint8(_)
+        | CoreTypeConcrete::Sint16(_)
+        | CoreTypeConcrete::Sint32(_)
+        | CoreTypeConcrete::Sint64(_)
+        | CoreTypeConcrete::Sint128(_) => true,
+        CoreTypeConcrete::NonZero(info) | CoreTypeConcrete::Snapshot(info) => {
+            is_traceable(registry, &info.ty)?
+        }
+        CoreTypeConcrete::Struct(info) => {
+            for member in &info.members {
+                if !is_traceable(registry, member)? {
+                    return Ok(false);
+                }
+            }
+            true
+        }
+        CoreTypeConcrete::Enum(info) => {
+            for variant in &info.variants {
+                if !is_traceable(registry, variant)? {
+                    return Ok(false);
+                }
+            }
+            true
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_cairo;
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn test_trace_from_events() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let registry = ProgramRegistry::new(&program).unwrap();
+        let felt252_id = program
+            .type_declarations
+            .iter()
+            .find(|decl| decl.long_id.generic_id.0 == "felt252")
+            .unwrap()
+            .id
+            .id;
+
+        // A call at statement 1 which recursively calls itself.
+        let trace = ExecutionTrace::from_events(
+            vec![
+                TraceEvent::Statement(0),
+                TraceEvent::Branch {
+                    statement_idx: 0,
+                    branch_idx: 1,
+                },
+                TraceEvent::Statement(1),
+                TraceEvent::Statement(1),
+                TraceEvent::Statement(2),
+                TraceEvent::Branch {
+                    statement_idx: 1,
+                    branch_idx: 0,
+                },
+                TraceEvent::Value {
+                    type_id: felt252_id,
+                    data: Some(vec![5, 0]),
+                },
+                TraceEvent::Branch {
+                    statement_idx: 1,
+                    branch_idx: 0,
+                },
+                TraceEvent::Value {
+                    type_id: felt252_id,
+                    data: Some(vec![6, 0]),
+                },
+                TraceEvent::Value {
+                    type_id: 0,
+                    data: None,
+                },
+                TraceEvent::Statement(3),
+            ],
+            Some(&registry),
+        )
+        .unwrap();
+
+        let step = |statement_idx, branch, values| TraceStep {
+            statement_idx: StatementIdx(statement_idx),
+            branch,
+            values,
+        };
+        let felt = |value: u64| Some(Value::Felt252(Felt::from(value)));
+        assert_eq!(
+            trace.steps,
+            [
+                step(0, Some(1), vec![]),
+                step(1, Some(0), vec![felt(6), None]),
+                step(1, Some(0), vec![felt(5)]),
+                step(2, None, vec![]),
+                step(3, None, vec![]),
+            ]
+        );
+        assert_eq!(
+            trace.to_string(),
+            "0 -> 1\n1 -> 0 Felt252(6) _\n1 -> 0 Felt252(5)\n2\n3\n"
+        );
+
+        let mut other = trace.clone();
+        assert_eq!(trace.first_divergence(&other), None);
+        other.steps[2].values[0] = felt(7);
+        assert_eq!(trace.first_divergence(&other), Some(2));
+        other.steps.truncate(2);
+        assert_eq!(trace.first_divergence(&other), Some(2));
+    }
+}
@@ -64,6 +64,9 @@ pub enum Error {
     #[error("ld link error: {0}")]
     LinkError(String),
 
+    #[error("failed to extract the JIT symbols: {0}")]
+    JitSymbols(String),
+
     #[error("cairo const data mismatch")]
     ConstDataMismatch,
 
@@ -79,6 +82,21 @@ pub enum Error {
     #[error("selector not found in the AotContractExecutor mappings")]
     SelectorNotFound,
 
+    #[error("invalid nonce for account {account}: expected {expected}, got {actual}")]
+    InvalidNonce {
+        account: starknet_types_core::felt::Felt,
+        expected: starknet_types_core::felt::Felt,
+        actual: starknet_types_core::felt::Felt,
+    },
+
+    #[error(transparent)]
+    AbiError(#[from] crate::starknet::abi::AbiError),
+
+    #[error(transparent)]
+    StarknetSierraCompilationError(
+        #[from] cairo_lang_starknet_classes::casm_contract_class::StarknetSierraCompilationError,
+    ),
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -58,6 +58,19 @@ pub struct ContractExecutionResult {
     pub error_msg: Option<String>,
 }
 
+/// Starknet contract execution result, with the return values deserialized according to the
+/// contract's ABI.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AbiExecutionResult {
+    pub remaining_gas: u64,
+    pub failure_flag: bool,
+    /// The deserialized return values. Empty if the execution failed.
+    pub return_values: Vec<serde_json::Value>,
+    /// The return data as returned by the contract (the panic data if the execution failed).
+    pub raw_return_values: Vec<Felt>,
+    pub error_msg: Option<String>,
+}
+
 impl ContractExecutionResult {
     /// Convert an [`ExecutionResult`] into a [`ContractExecutionResult`]
     pub fn from_execution_result(result: ExecutionResult) -> Result<Self, Error> {
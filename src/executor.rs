@@ -6,10 +6,14 @@
 pub use self::{aot::AotNativeExecutor, contract::AotContractExecutor, jit::JitNativeExecutor};
 use crate::{
     arch::{AbiArgument, ValueWithInfoWrapper},
+    debug::{
+        backtrace::ShadowStack, profiler::ProfilerEvent, set_debug_results, trace::TraceEvent,
+        DebugResults, ExecutionTrace, PanicBacktrace, Profile,
+    },
     error::{panic::ToNativeAssertError, Error},
     execution_result::{BuiltinStats, ExecutionResult},
     native_panic,
-    runtime::BUILTIN_COSTS,
+    runtime::{BUILTIN_COSTS, PROFILER_EVENTS, SHADOW_STACK, TRACE_EVENTS},
     starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
     types::TypeBuilder,
     utils::{libc_free, BuiltinCosts, RangeExt},
@@ -148,6 +152,11 @@ fn invoke_dynamic(
     // We may be inside a recursive contract, save the possible saved builtin costs to restore it after our call.
     let builtin_costs = BuiltinCosts::default();
     let builtin_costs_guard = BuiltinCostsGuard::install(builtin_costs);
+    // Same for the profiler and trace events and the shadow call stack, if the program records
+    // them.
+    let profiler_guard = ProfilerGuard::install();
+    let trace_guard = TraceGuard::install();
+    let shadow_stack_guard = ShadowStackGuard::install();
 
     // Generate argument list.
     let mut iter = args.iter();
@@ -232,6 +241,10 @@ fn invoke_dynamic(
     #[cfg(feature = "with-cheatcode")]
     drop(syscall_handler_guard);
     drop(builtin_costs_guard);
+    let profile = profiler_guard.finish(Some(registry));
+    let trace = trace_guard.finish(Some(registry))?;
+    let backtrace = shadow_stack_guard.finish(Some(registry));
+    set_debug_results(DebugResults::new(profile, trace, backtrace));
 
     // Parse final gas.
     unsafe fn read_value<T>(ptr: &mut NonNull<()>) -> &T {
@@ -380,6 +393,95 @@ impl Drop for BuiltinCostsGuard {
     }
 }
 
+/// Installs an empty buffer for the profiler events, and restores the previous one once finished
+/// or dropped.
+#[derive(Debug)]
+struct ProfilerGuard(Option<Vec<ProfilerEvent>>);
+
+impl ProfilerGuard {
+    pub fn install() -> Self {
+        Self(PROFILER_EVENTS.replace(Some(Vec::new())))
+    }
+
+    /// Restore the previous buffer and build the profile, unless no events were recorded.
+    pub fn finish(
+        mut self,
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Option<Profile> {
+        let events = PROFILER_EVENTS.replace(self.0.take());
+        std::mem::forget(self);
+
+        events
+            .filter(|events| !events.is_empty())
+            .map(|events| Profile::from_events(&events, registry))
+    }
+}
+
+impl Drop for ProfilerGuard {
+    fn drop(&mut self) {
+        PROFILER_EVENTS.set(self.0.take());
+    }
+}
+
+/// Installs an empty buffer for the trace events, and restores the previous one once finished or
+/// dropped.
+#[derive(Debug)]
+struct TraceGuard(Option<Vec<TraceEvent>>);
+
+impl TraceGuard {
+    pub fn install() -> Self {
+        Self(TRACE_EVENTS.replace(Some(Vec::new())))
+    }
+
+    /// Restore the previous buffer and build the trace, unless no events were recorded.
+    pub fn finish(
+        mut self,
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Result<Option<ExecutionTrace>, Error> {
+        let events = TRACE_EVENTS.replace(self.0.take());
+        std::mem::forget(self);
+
+        events
+            .filter(|events| !events.is_empty())
+            .map(|events| ExecutionTrace::from_events(events, registry))
+            .transpose()
+    }
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        TRACE_EVENTS.set(self.0.take());
+    }
+}
+
+/// Installs an empty shadow call stack, and restores the previous one once finished or dropped.
+#[derive(Debug)]
+struct ShadowStackGuard(Option<ShadowStack>);
+
+impl ShadowStackGuard {
+    pub fn install() -> Self {
+        Self(SHADOW_STACK.replace(Some(ShadowStack::default())))
+    }
+
+    /// Restore the previous shadow call stack and build the backtrace, unless no panic was
+    /// returned.
+    pub fn finish(
+        mut self,
+        registry: Option<&ProgramRegistry<CoreType, CoreLibfunc>>,
+    ) -> Option<PanicBacktrace> {
+        let stack = SHADOW_STACK.replace(self.0.take());
+        std::mem::forget(self);
+
+        stack.and_then(|stack| stack.into_backtrace(registry))
+    }
+}
+
+impl Drop for ShadowStackGuard {
+    fn drop(&mut self) {
+        SHADOW_STACK.set(self.0.take());
+    }
+}
+
 /// Parses the result by reading from the return ptr the given type.
 fn parse_result(
     type_id: &ConcreteTypeId,
@@ -669,12 +771,21 @@ fn parse_result(
 mod tests {
     use super::*;
     use crate::{
-        context::NativeContext, starknet_stub::StubSyscallHandler, utils::test::load_cairo,
-        utils::test::load_starknet, OptLevel,
+        context::NativeContext,
+        debug::{
+            take_debug_results, with_statement_hook, Coverage, DebugOptions, ProfileMetric,
+            TraceLevel,
+        },
+        starknet_stub::StubSyscallHandler,
+        utils::test::load_cairo,
+        utils::test::load_starknet,
+        utils::test::{find_test_function, run_program_with_debug_options},
+        OptLevel,
     };
-    use cairo_lang_sierra::program::Program;
+    use cairo_lang_sierra::program::{Program, Statement};
     use rstest::*;
     use starknet_types_core::felt::Felt;
+    use std::{cell::RefCell, rc::Rc};
 
     #[fixture]
     fn program() -> Program {
@@ -805,4 +916,307 @@ mod tests {
 
         assert_eq!(result.return_values, vec![Felt::from(42)]);
     }
+
+    #[rstest]
+    fn test_coverage(program: Program) {
+        let native_context = NativeContext::new();
+        let compile = || {
+            native_context
+                .compile_with_debug_options(
+                    &program,
+                    false,
+                    Some(Default::default()),
+                    DebugOptions::new().with_coverage(),
+                )
+                .expect("failed to compile context")
+        };
+        let entrypoint_function_id = &program.funcs.first().expect("should have a function").id;
+
+        let check_coverage = |take_coverage: &dyn Fn() -> Option<Coverage>| {
+            let coverage = take_coverage().unwrap();
+            assert_eq!(coverage.len(), program.statements.len());
+            assert_eq!(coverage.hits(program.funcs[0].entry_point), 2);
+
+            // Taking the coverage resets the counts.
+            assert_eq!(take_coverage().unwrap().covered_statements(), 0);
+        };
+
+        let executor =
+            JitNativeExecutor::from_native_module(compile(), OptLevel::default()).unwrap();
+        for _ in 0..2 {
+            executor
+                .invoke_dynamic(entrypoint_function_id, &[], Some(u64::MAX))
+                .unwrap();
+        }
+        check_coverage(&|| executor.take_coverage());
+
+        let executor =
+            AotNativeExecutor::from_native_module(compile(), OptLevel::default()).unwrap();
+        for _ in 0..2 {
+            executor
+                .invoke_dynamic(entrypoint_function_id, &[], Some(u64::MAX))
+                .unwrap();
+        }
+        check_coverage(&|| executor.take_coverage());
+
+        // Programs compiled without instrumentation have no coverage.
+        let module = native_context.compile(&program, false, None).unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+        assert_eq!(executor.take_coverage(), None);
+    }
+
+    #[fixture]
+    fn fib_program() -> (String, Program) {
+        load_cairo! {
+            fn run_test() -> felt252 {
+                fib(10)
+            }
+
+            #[inline(never)]
+            fn fib(n: felt252) -> felt252 {
+                if n == 0 {
+                    0
+                } else if n == 1 {
+                    1
+                } else {
+                    fib(n - 1) + fib(n - 2)
+                }
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_profiler(fib_program: (String, Program)) {
+        let result = run_program_with_debug_options(
+            &fib_program,
+            "run_test",
+            &[],
+            DebugOptions::new().with_profiler(),
+        );
+        assert_eq!(result.return_value, Value::Felt252(Felt::from(55)));
+
+        let run_test = &find_test_function(&fib_program, "run_test").id;
+        let profile = take_debug_results().unwrap().profile.unwrap();
+        assert_eq!(profile.roots.len(), 1);
+        assert_eq!(profile.roots[0].function_id.id, run_test.id);
+        assert_eq!(profile.roots[0].calls, 1);
+        assert_eq!(
+            profile.roots[0].inclusive_gas,
+            u64::MAX - result.remaining_gas.unwrap()
+        );
+
+        let fib = &find_test_function(&fib_program, "fib").id;
+        let functions = profile.functions();
+        assert_eq!(functions[fib].calls, 177);
+        assert!(functions[fib].inclusive_gas > 0);
+        assert!(profile
+            .to_folded(ProfileMetric::Gas)
+            .lines()
+            .any(|line| line.contains(";fib(")));
+    }
+
+    #[rstest]
+    fn test_trace(fib_program: (String, Program)) {
+        let run = || {
+            run_program_with_debug_options(
+                &fib_program,
+                "run_test",
+                &[],
+                DebugOptions::new().with_trace(TraceLevel::Values),
+            )
+        };
+        let program = &fib_program.1;
+
+        let result = run();
+        assert_eq!(result.return_value, Value::Felt252(Felt::from(55)));
+
+        let trace = take_debug_results().unwrap().trace.unwrap();
+        assert_eq!(
+            trace.steps[0].statement_idx,
+            find_test_function(&fib_program, "run_test").entry_point
+        );
+        for step in &trace.steps {
+            match &program.statements[step.statement_idx.0] {
+                Statement::Invocation(invocation) => {
+                    assert!(step.branch.unwrap() < invocation.branches.len());
+                    assert_eq!(
+                        step.values.len(),
+                        invocation.branches[step.branch.unwrap()].results.len()
+                    );
+                }
+                Statement::Return(_) => assert_eq!(step.branch, None),
+            }
+        }
+        assert!(matches!(
+            program.statements[trace.steps.last().unwrap().statement_idx.0],
+            Statement::Return(_)
+        ));
+
+        let fib = find_test_function(&fib_program, "fib");
+        assert_eq!(
+            trace
+                .steps
+                .iter()
+                .filter(|step| step.statement_idx == fib.entry_point)
+                .count(),
+            177
+        );
+        assert!(trace
+            .steps
+            .iter()
+            .any(|step| step.values.contains(&Some(Value::Felt252(Felt::from(55))))));
+
+        // Running it again yields the same trace.
+        run();
+        assert_eq!(
+            trace.first_divergence(&take_debug_results().unwrap().trace.unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_panic_backtrace() {
+        let program = load_cairo! {
+            fn run_test() -> felt252 {
+                outer(3)
+            }
+
+            fn run_ok() -> felt252 {
+                outer(2)
+            }
+
+            #[inline(never)]
+            fn outer(n: felt252) -> felt252 {
+                inner(n) + 1
+            }
+
+            #[inline(never)]
+            fn inner(n: felt252) -> felt252 {
+                if n == 3 {
+                    panic_with_felt252(42);
+                }
+                n
+            }
+        };
+        let run = |entry_point| {
+            run_program_with_debug_options(
+                &program,
+                entry_point,
+                &[],
+                DebugOptions::new().with_panic_backtraces(),
+            )
+        };
+        let function_id = |name| find_test_function(&program, name).id.id;
+
+        let result = run("run_test");
+        assert!(matches!(result.return_value, Value::Enum { tag: 1, .. }));
+        let backtrace = take_debug_results().unwrap().backtrace.unwrap();
+
+        // The innermost frame returns the panic, and the others call the next one.
+        assert!(matches!(
+            program.1.statements[backtrace.frames[0].statement_idx.0],
+            Statement::Return(_)
+        ));
+        let function_ids = backtrace
+            .frames
+            .iter()
+            .map(|frame| frame.function_id.id)
+            .collect::<Vec<_>>();
+        let inner = function_ids
+            .iter()
+            .position(|id| *id == function_id("inner"))
+            .unwrap();
+        assert_eq!(
+            function_ids[inner..],
+            [
+                function_id("inner"),
+                function_id("outer"),
+                function_id("run_test")
+            ]
+        );
+        assert!(backtrace.to_string().contains("::outer"));
+
+        // Runs which don't panic have no backtrace.
+        let result = run("run_ok");
+        assert!(matches!(result.return_value, Value::Enum { tag: 0, .. }));
+        assert_eq!(take_debug_results(), None);
+    }
+
+    #[test]
+    fn test_statement_hook() {
+        let program = load_cairo! {
+            fn run_test() -> felt252 {
+                add(3, 4)
+            }
+
+            #[inline(never)]
+            fn add(a: felt252, b: felt252) -> felt252 {
+                a + b
+            }
+        };
+        let run = || {
+            run_program_with_debug_options(
+                &program,
+                "run_test",
+                &[],
+                DebugOptions::new().with_statement_hooks(),
+            )
+        };
+        let registry = ProgramRegistry::new(&program.1).unwrap();
+
+        let recorded = Rc::new(RefCell::new(Vec::new()));
+        let result = with_statement_hook(
+            {
+                let recorded = recorded.clone();
+                move |statement_idx, variables| {
+                    recorded
+                        .borrow_mut()
+                        .push((statement_idx, variables.to_vec()));
+                }
+            },
+            run,
+        );
+        assert_eq!(result.return_value, Value::Felt252(Felt::from(7)));
+
+        let stops = recorded.take();
+        assert_eq!(
+            stops[0].0,
+            find_test_function(&program, "run_test").entry_point
+        );
+        assert!(matches!(
+            program.1.statements[stops.last().unwrap().0 .0],
+            Statement::Return(_)
+        ));
+
+        // The arguments are live when `add` is entered.
+        let add = find_test_function(&program, "add");
+        let (_, variables) = stops
+            .iter()
+            .find(|(statement_idx, _)| *statement_idx == add.entry_point)
+            .unwrap();
+        assert_eq!(
+            variables
+                .iter()
+                .map(|variable| variable.var_id.clone())
+                .collect::<Vec<_>>(),
+            add.params
+                .iter()
+                .map(|param| param.id.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            variables
+                .iter()
+                .map(|variable| variable.value(&registry).unwrap())
+                .collect::<Vec<_>>(),
+            [
+                Some(Value::Felt252(Felt::from(3))),
+                Some(Value::Felt252(Felt::from(4)))
+            ]
+        );
+
+        // Nothing is called once the hook is uninstalled.
+        run();
+        assert!(recorded.borrow().is_empty());
+    }
 }
@@ -1,8 +1,10 @@
 use crate::{
+    debug::Coverage,
     error::Error,
     execution_result::{ContractExecutionResult, ExecutionResult},
     metadata::{
-        felt252_dict::Felt252DictOverrides, gas::GasMetadata, runtime_bindings::setup_runtime,
+        coverage::take_coverage, felt252_dict::Felt252DictOverrides, gas::GasMetadata,
+        runtime_bindings::setup_runtime,
     },
     module::NativeModule,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
@@ -63,6 +65,7 @@ impl AotNativeExecutor {
             module,
             registry,
             mut metadata,
+            debug_options,
         } = module;
 
         let library_path = NamedTempFile::new()?
@@ -71,7 +74,11 @@ impl AotNativeExecutor {
             .map_err(io::Error::from)?;
 
         let object_data = crate::module_to_object(&module, opt_level)?;
-        crate::object_to_shared_lib(&object_data, &library_path)?;
+        crate::object_to_shared_lib_with_debug_options(
+            &object_data,
+            &library_path,
+            &debug_options,
+        )?;
 
         Ok(Self::new(
             unsafe { Library::new(&library_path)? },
@@ -177,6 +184,15 @@ impl AotNativeExecutor {
         }
     }
 
+    /// Return the number of times each statement was executed since the executor was created or the
+    /// coverage was last taken, and reset the counts.
+    ///
+    /// Returns `None` unless the program was compiled with coverage instrumentation. Since the
+    /// counts are shared, concurrent invocations are accumulated together.
+    pub fn take_coverage(&self) -> Option<Coverage> {
+        take_coverage(|name| self.find_symbol_ptr(name))
+    }
+
     fn extract_signature(&self, function_id: &FunctionId) -> Result<&FunctionSignature, Error> {
         Ok(&self.registry.get_function(function_id)?.signature)
     }
@@ -294,7 +310,7 @@ mod tests {
 
         let mut syscall_handler = &mut StubSyscallHandler::default();
 
-        let expected_value = syscall_handler.get_block_hash(1, &mut 0).unwrap();
+        let expected_value = syscall_handler.get_block_hash(1, &mut u64::MAX).unwrap();
 
         let result = executor
             .invoke_dynamic_with_syscall_handler(
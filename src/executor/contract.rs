@@ -20,10 +20,10 @@
 //! The API provides two more methods: [`ContractExecutor::save`] and [`ContractExecutor::load`].
 //!
 //! Save can be used to save the compiled program into the given path, alongside it will be saved
-//! a json file with the entry points and their builtins (as seen in the example)
+//! a json file with the entry points, their builtins and their type (as seen in the example)
 //!
 //! ```json
-//! {"0":{"builtins":[]},"1":{"builtins":["RangeCheck","Gas","System"]}}
+//! {"0":{"builtins":[],"entry_point_type":"Constructor"},"1":{"builtins":["RangeCheck","Gas","System"],"entry_point_type":"External"}}
 //! ```
 //!
 //! If the given path is "program.so", then at the same location, "program.json" will be saved.
@@ -34,12 +34,15 @@
 use crate::{
     arch::AbiArgument,
     context::NativeContext,
+    debug::{set_debug_results, DebugOptions, DebugResults},
     error::{panic::ToNativeAssertError, Error, Result},
-    execution_result::{BuiltinStats, ContractExecutionResult},
-    executor::{invoke_trampoline, BuiltinCostsGuard},
+    execution_result::{AbiExecutionResult, BuiltinStats, ContractExecutionResult},
+    executor::{invoke_trampoline, BuiltinCostsGuard, ProfilerGuard, ShadowStackGuard, TraceGuard},
     metadata::{gas::MetadataComputationConfig, runtime_bindings::setup_runtime},
     module::NativeModule,
-    starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
+    starknet::{
+        abi::ContractAbi, handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler,
+    },
     types::TypeBuilder,
     utils::{
         decode_error_message, generate_function_name, get_integer_layout, libc_free, libc_malloc,
@@ -58,7 +61,7 @@ use cairo_lang_sierra::{
 };
 use cairo_lang_starknet_classes::contract_class::ContractEntryPoints;
 use cairo_lang_starknet_classes::{
-    casm_contract_class::ENTRY_POINT_COST, compiler_version::VersionId,
+    casm_contract_class::ENTRY_POINT_COST, compiler_version::VersionId, keccak::starknet_keccak,
 };
 use educe::Educe;
 use itertools::chain;
@@ -89,11 +92,62 @@ pub struct AotContractExecutor {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "SavedContractInfo")]
 pub struct NativeContractInfo {
     pub version: ContractInfoVersion,
     pub entry_points: BTreeMap<Felt, EntryPointInfo>,
 }
 
+/// The contract info as saved by any version, used to fill in what older versions didn't save.
+#[derive(Deserialize)]
+struct SavedContractInfo {
+    version: ContractInfoVersion,
+    entry_points: BTreeMap<Felt, SavedEntryPointInfo>,
+}
+
+#[derive(Deserialize)]
+struct SavedEntryPointInfo {
+    function_id: u64,
+    builtins: Vec<BuiltinType>,
+    #[serde(default)]
+    entry_point_type: Option<EntryPointType>,
+}
+
+impl From<SavedContractInfo> for NativeContractInfo {
+    fn from(value: SavedContractInfo) -> Self {
+        // Older versions didn't save the entry point types. The constructor is recognized by its
+        // selector, which is fixed, while L1 handlers can't be told apart from external entry
+        // points.
+        let constructor_selector = Felt::from(&starknet_keccak(b"constructor"));
+
+        Self {
+            version: value.version,
+            entry_points: value
+                .entry_points
+                .into_iter()
+                .map(|(selector, x)| {
+                    let entry_point_type =
+                        x.entry_point_type
+                            .unwrap_or(if selector == constructor_selector {
+                                EntryPointType::Constructor
+                            } else {
+                                EntryPointType::External
+                            });
+
+                    (
+                        selector,
+                        EntryPointInfo {
+                            function_id: x.function_id,
+                            builtins: x.builtins,
+                            entry_point_type,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ContractInfoVersion {
     V0,
@@ -103,6 +157,16 @@ pub enum ContractInfoVersion {
 pub struct EntryPointInfo {
     pub function_id: u64,
     pub builtins: Vec<BuiltinType>,
+    /// Missing in contract info files saved by older versions, in which case it's inferred from
+    /// the selector when loading them.
+    pub entry_point_type: EntryPointType,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryPointType {
+    External,
+    L1Handler,
+    Constructor,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -134,18 +198,40 @@ impl AotContractExecutor {
         entry_points: &ContractEntryPoints,
         sierra_version: VersionId,
         opt_level: OptLevel,
+    ) -> Result<Self> {
+        Self::new_with_debug_options(
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            DebugOptions::new(),
+        )
+    }
+
+    /// Compile and load a program using a temporary shared library, with the given debug options.
+    ///
+    /// The profile, trace and panic backtrace they enable are returned in the results of the runs.
+    /// Since the contract's program isn't kept, the functions in them are named after their ids
+    /// and the values of the trace are left empty.
+    pub fn new_with_debug_options(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        opt_level: OptLevel,
+        debug_options: DebugOptions,
     ) -> Result<Self> {
         let output_path = NamedTempFile::new()?
             .into_temp_path()
             .keep()
             .to_native_assert_error("can only fail on windows")?;
 
-        let executor = Self::new_into(
+        let executor = Self::new_into_with_debug_options(
             program,
             entry_points,
             sierra_version,
             output_path,
             opt_level,
+            debug_options,
         )?
         .to_native_assert_error("temporary contract path collision")?;
 
@@ -166,6 +252,29 @@ impl AotContractExecutor {
         sierra_version: VersionId,
         output_path: impl Into<PathBuf>,
         opt_level: OptLevel,
+    ) -> Result<Option<Self>> {
+        Self::new_into_with_debug_options(
+            program,
+            entry_points,
+            sierra_version,
+            output_path,
+            opt_level,
+            DebugOptions::new(),
+        )
+    }
+
+    /// Compile and load a program into a shared library, with the given debug options.
+    ///
+    /// See [`AotContractExecutor::new_into`] for the locking and
+    /// [`AotContractExecutor::new_with_debug_options`] for the debug options. Contracts loaded
+    /// later from the library keep the instrumentation.
+    pub fn new_into_with_debug_options(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        output_path: impl Into<PathBuf>,
+        opt_level: OptLevel,
+        debug_options: DebugOptions,
     ) -> Result<Option<Self>> {
         let output_path = output_path.into();
         let lock_file = match LockFile::new(&output_path)? {
@@ -183,8 +292,11 @@ impl AotContractExecutor {
 
         // Compile the Sierra program.
         let NativeModule {
-            module, registry, ..
-        } = context.compile(
+            module,
+            registry,
+            debug_options,
+            ..
+        } = context.compile_with_debug_options(
             program,
             true,
             Some(MetadataComputationConfig {
@@ -203,15 +315,25 @@ impl AotContractExecutor {
                 linear_gas_solver: no_eq_solver,
                 linear_ap_change_solver: no_eq_solver,
             }),
+            debug_options,
         )?;
 
         // Generate mappings between the entry point's selectors and their function indexes.
         let entry_point_mappings = chain!(
-            entry_points.constructor.iter(),
-            entry_points.external.iter(),
-            entry_points.l1_handler.iter(),
+            entry_points
+                .constructor
+                .iter()
+                .map(|x| (x, EntryPointType::Constructor)),
+            entry_points
+                .external
+                .iter()
+                .map(|x| (x, EntryPointType::External)),
+            entry_points
+                .l1_handler
+                .iter()
+                .map(|x| (x, EntryPointType::L1Handler)),
         )
-        .map(|x| {
+        .map(|(x, entry_point_type)| {
             let function_id = x.function_idx as u64;
             let function = registry
                 .get_function(&FunctionId::new(function_id))
@@ -251,6 +373,7 @@ impl AotContractExecutor {
                 EntryPointInfo {
                     function_id: x.function_idx as u64,
                     builtins,
+                    entry_point_type,
                 },
             ))
         })
@@ -259,7 +382,7 @@ impl AotContractExecutor {
         let object_data = crate::module_to_object(&module, opt_level)?;
 
         // Build the shared library into the lockfile, to avoid using a tmp file.
-        crate::object_to_shared_lib(&object_data, &lock_file.0)?;
+        crate::object_to_shared_lib_with_debug_options(&object_data, &lock_file.0, &debug_options)?;
 
         // Write the contract info.
         fs::write(
@@ -338,6 +461,11 @@ impl AotContractExecutor {
         let mut syscall_handler = StarknetSyscallHandlerCallbacks::new(&mut syscall_handler);
         let builtin_costs = builtin_costs.unwrap_or_default();
         let builtin_costs_guard = BuiltinCostsGuard::install(builtin_costs);
+        // Same for the profiler and trace events and the shadow call stack, if the contract
+        // records them.
+        let profiler_guard = ProfilerGuard::install();
+        let trace_guard = TraceGuard::install();
+        let shadow_stack_guard = ShadowStackGuard::install();
 
         //  it can vary from contract to contract thats why we need to store/ load it.
         let builtins_size: usize = self.contract_info.entry_points[&selector]
@@ -460,6 +588,13 @@ impl AotContractExecutor {
         #[cfg(not(feature = "with-segfault-catcher"))]
         run_trampoline();
 
+        // The contract's program isn't available to name the functions and decode the values.
+        set_debug_results(DebugResults::new(
+            profiler_guard.finish(None),
+            trace_guard.finish(None)?,
+            shadow_stack_guard.finish(None),
+        ));
+
         // Parse final gas.
         unsafe fn read_value<T>(ptr: &mut NonNull<()>) -> &T {
             let align_offset = ptr
@@ -585,6 +720,108 @@ impl AotContractExecutor {
         })
     }
 
+    /// Returns the type of the entry point with the given selector, if it exists.
+    pub fn entry_point_type(&self, selector: Felt) -> Option<EntryPointType> {
+        self.contract_info
+            .entry_points
+            .get(&selector)
+            .map(|x| x.entry_point_type)
+    }
+
+    /// Runs the contract's constructor.
+    ///
+    /// Contracts without a constructor succeed without doing anything, as long as no calldata is
+    /// given.
+    pub fn run_constructor(
+        &self,
+        args: &[Felt],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
+        let selector = self
+            .contract_info
+            .entry_points
+            .iter()
+            .find(|(_, x)| x.entry_point_type == EntryPointType::Constructor)
+            .map(|(selector, _)| *selector);
+
+        match selector {
+            Some(selector) => self.run(selector, args, gas, builtin_costs, syscall_handler),
+            None if args.is_empty() => {
+                // Nothing was run, so there's nothing to debug either.
+                set_debug_results(None);
+                Ok(ContractExecutionResult {
+                    remaining_gas: gas,
+                    failure_flag: false,
+                    return_values: Vec::new(),
+                    error_msg: None,
+                })
+            }
+            None => Err(Error::SelectorNotFound),
+        }
+    }
+
+    /// Runs an L1 handler as if it was invoked by a message from L1.
+    ///
+    /// - selector: The selector of the L1 handler.
+    /// - from_address: The L1 address which sent the message. It's prepended to the payload to
+    ///   build the calldata.
+    /// - payload: The message's payload.
+    ///
+    /// Messages sent back to L1 go through the syscall handler's `send_message_to_l1`. The
+    /// [`StubSyscallHandler`](crate::starknet_stub::StubSyscallHandler) collects them in its
+    /// state diff.
+    pub fn run_l1_handler(
+        &self,
+        selector: Felt,
+        from_address: Felt,
+        payload: &[Felt],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
+        if self.entry_point_type(selector) != Some(EntryPointType::L1Handler) {
+            return Err(Error::SelectorNotFound);
+        }
+
+        let calldata = [&[from_address], payload].concat();
+        self.run(selector, &calldata, gas, builtin_costs, syscall_handler)
+    }
+
+    /// Runs the entry point with the given name, serializing the arguments and deserializing the
+    /// return values according to the contract's ABI.
+    ///
+    /// Please look at the [`abi`](crate::starknet::abi) module for how values are represented.
+    /// Returns an error if the function doesn't exist or the arguments don't match its inputs.
+    pub fn run_by_name(
+        &self,
+        abi: &ContractAbi,
+        function: &str,
+        args: &[serde_json::Value],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<AbiExecutionResult> {
+        let calldata = abi.serialize_calldata(function, args)?;
+        let selector = abi.function(function)?.selector;
+
+        let result = self.run(selector, &calldata, gas, builtin_costs, syscall_handler)?;
+        let return_values = if result.failure_flag {
+            Vec::new()
+        } else {
+            abi.deserialize_return_values(function, &result.return_values)?
+        };
+
+        Ok(AbiExecutionResult {
+            remaining_gas: result.remaining_gas,
+            failure_flag: result.failure_flag,
+            return_values,
+            raw_return_values: result.return_values,
+            error_msg: result.error_msg,
+        })
+    }
+
     pub fn find_function_ptr(
         &self,
         function_id: &FunctionId,
@@ -646,7 +883,12 @@ impl Drop for LockFile {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{starknet_stub::StubSyscallHandler, utils::test::load_starknet_contract};
+    use crate::{
+        debug::take_debug_results,
+        starknet::abi::AbiError,
+        starknet_stub::{state_diff::OrderedL2ToL1Message, StubSyscallHandler},
+        utils::test::load_starknet_contract,
+    };
     use cairo_lang_starknet_classes::contract_class::{
         version_id_from_serialized_sierra_program, ContractClass,
     };
@@ -711,6 +953,35 @@ mod tests {
         program
     }
 
+    #[fixture]
+    fn starknet_program_messaging() -> ContractClass {
+        let (_, program) = load_starknet_contract! {
+            #[starknet::contract]
+            mod contract {
+                use starknet::storage::{StoragePointerReadAccess, StoragePointerWriteAccess};
+                use starknet::syscalls::send_message_to_l1_syscall;
+
+                #[storage]
+                struct Storage {
+                    balance: felt252,
+                }
+
+                #[constructor]
+                fn constructor(ref self: ContractState, balance: felt252) {
+                    self.balance.write(balance);
+                }
+
+                #[l1_handler]
+                fn deposit(ref self: ContractState, from_address: felt252, amount: felt252) {
+                    let balance = self.balance.read() + amount;
+                    self.balance.write(balance);
+                    send_message_to_l1_syscall(from_address, array![balance].span()).unwrap();
+                }
+            }
+        };
+        program
+    }
+
     #[fixture]
     fn starknet_program_empty() -> ContractClass {
         let (_, program) = load_starknet_contract! {
@@ -814,6 +1085,56 @@ mod tests {
         assert_eq!(result.return_values, vec![Felt::from(2), Felt::from(4)]);
     }
 
+    #[rstest]
+    fn test_contract_executor_debug_options(starknet_program: ContractClass) {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program.sierra_program).unwrap();
+        let executor = AotContractExecutor::new_with_debug_options(
+            &starknet_program.extract_sierra_program().unwrap(),
+            &starknet_program.entry_points_by_type,
+            sierra_version,
+            OptLevel::default(),
+            DebugOptions::new().with_profiler().with_panic_backtraces(),
+        )
+        .unwrap();
+        let selector = Felt::from(
+            &starknet_program
+                .entry_points_by_type
+                .external
+                .last()
+                .unwrap()
+                .selector,
+        );
+
+        let result = executor
+            .run(
+                selector,
+                &[2.into()],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+        let debug = take_debug_results().unwrap();
+        assert_eq!(debug.profile.unwrap().roots.len(), 1);
+        assert_eq!(debug.backtrace, None);
+
+        // Running out of gas panics in the entry point's wrapper.
+        let result = executor
+            .run(
+                selector,
+                &[2.into()],
+                0,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert!(result.failure_flag);
+        let backtrace = take_debug_results().unwrap().backtrace.unwrap();
+        assert!(!backtrace.frames.is_empty());
+    }
+
     #[rstest]
     #[case(OptLevel::Aggressive)]
     fn test_contract_executor_factorial(
@@ -853,6 +1174,46 @@ mod tests {
         assert_eq!(result.remaining_gas, 18446744073709537615);
     }
 
+    #[rstest]
+    fn test_contract_executor_run_by_name(starknet_program_factorial: ContractClass) {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program_factorial.sierra_program)
+                .unwrap();
+        let executor = AotContractExecutor::new(
+            &starknet_program_factorial.extract_sierra_program().unwrap(),
+            &starknet_program_factorial.entry_points_by_type,
+            sierra_version,
+            OptLevel::default(),
+        )
+        .unwrap();
+        let abi = ContractAbi::from_contract_class(&starknet_program_factorial).unwrap();
+
+        let result = executor
+            .run_by_name(
+                &abi,
+                "get",
+                &[serde_json::json!(10)],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![serde_json::json!("0x375f00")]);
+        assert_eq!(result.raw_return_values, vec![Felt::from(3628800)]);
+
+        assert!(matches!(
+            executor.run_by_name(
+                &abi,
+                "get",
+                &[],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            ),
+            Err(Error::AbiError(AbiError::ArgumentCount { .. }))
+        ));
+    }
+
     #[rstest]
     #[case(OptLevel::None)]
     #[case(OptLevel::Default)]
@@ -893,4 +1254,89 @@ mod tests {
 
         assert_eq!(result.return_values, vec![]);
     }
+
+    #[rstest]
+    fn test_contract_executor_messaging(starknet_program_messaging: ContractClass) {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program_messaging.sierra_program)
+                .unwrap();
+        let executor = AotContractExecutor::new(
+            &starknet_program_messaging.extract_sierra_program().unwrap(),
+            &starknet_program_messaging.entry_points_by_type,
+            sierra_version,
+            OptLevel::default(),
+        )
+        .unwrap();
+        let selector = Felt::from(&starknet_keccak(b"deposit"));
+        assert_eq!(
+            executor.entry_point_type(selector),
+            Some(EntryPointType::L1Handler)
+        );
+
+        let mut syscall_handler = StubSyscallHandler::default();
+        let result = executor
+            .run_constructor(&[Felt::from(10)], u64::MAX, None, &mut syscall_handler)
+            .unwrap();
+        assert!(!result.failure_flag);
+
+        let result = executor
+            .run_l1_handler(
+                selector,
+                Felt::from(0x1234),
+                &[Felt::from(5)],
+                u64::MAX,
+                None,
+                &mut syscall_handler,
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+        assert_eq!(
+            syscall_handler.state_diff.l2_to_l1_messages,
+            [OrderedL2ToL1Message {
+                from_address: syscall_handler.execution_info.contract_address,
+                to_address: Felt::from(0x1234),
+                payload: vec![Felt::from(15)],
+            }]
+        );
+
+        // Only L1 handlers can be run as such.
+        assert!(matches!(
+            executor.run_l1_handler(
+                Felt::ZERO,
+                Felt::ZERO,
+                &[],
+                u64::MAX,
+                None,
+                &mut syscall_handler,
+            ),
+            Err(Error::SelectorNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_contract_info_without_entry_point_types() {
+        let constructor_selector = Felt::from(&starknet_keccak(b"constructor"));
+        let contract_info: NativeContractInfo = serde_json::from_value(serde_json::json!({
+            "version": "V0",
+            "entry_points": {
+                format!("{constructor_selector:#x}"): { "function_id": 0, "builtins": [] },
+                "0x1": { "function_id": 1, "builtins": ["Gas", "System"] },
+                "0x2": { "function_id": 2, "builtins": [], "entry_point_type": "L1Handler" },
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            contract_info
+                .entry_points
+                .iter()
+                .map(|(selector, x)| (*selector, x.entry_point_type))
+                .collect::<Vec<_>>(),
+            [
+                (Felt::ONE, EntryPointType::External),
+                (Felt::TWO, EntryPointType::L1Handler),
+                (constructor_selector, EntryPointType::Constructor),
+            ]
+        );
+    }
 }
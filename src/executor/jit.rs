@@ -1,8 +1,12 @@
+#[cfg(target_os = "linux")]
+use self::symbols::{GdbJitRegistration, JitSymbols};
 use crate::{
+    debug::Coverage,
     error::Error,
     execution_result::{ContractExecutionResult, ExecutionResult},
     metadata::{
-        felt252_dict::Felt252DictOverrides, gas::GasMetadata, runtime_bindings::setup_runtime,
+        coverage::take_coverage, felt252_dict::Felt252DictOverrides, gas::GasMetadata,
+        runtime_bindings::setup_runtime,
     },
     module::NativeModule,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
@@ -21,8 +25,14 @@ use melior::{ir::Module, ExecutionEngine};
 use starknet_types_core::felt::Felt;
 use std::mem::transmute;
 
+#[cfg(target_os = "linux")]
+mod symbols;
+
 /// A MLIR JIT execution engine in the context of Cairo Native.
 pub struct JitNativeExecutor<'m> {
+    // Declared before the engine so that the code is unregistered before it's freed.
+    #[cfg(target_os = "linux")]
+    _gdb_jit_registration: Option<GdbJitRegistration>,
     engine: ExecutionEngine,
 
     module: Module<'m>,
@@ -45,6 +55,10 @@ impl std::fmt::Debug for JitNativeExecutor<'_> {
 }
 
 impl<'m> JitNativeExecutor<'m> {
+    /// Create the executor from a compiled module.
+    ///
+    /// If the module's debug options ask for it, the JIT-compiled functions' symbols are written to
+    /// a perf map file and registered with the GDB JIT interface (only on Linux).
     pub fn from_native_module(
         native_module: NativeModule<'m>,
         opt_level: OptLevel,
@@ -53,10 +67,33 @@ impl<'m> JitNativeExecutor<'m> {
             module,
             registry,
             mut metadata,
+            debug_options,
         } = native_module;
 
+        let engine = create_engine(
+            &module,
+            &metadata,
+            opt_level,
+            debug_options.exports_jit_symbols(),
+        );
+
+        #[cfg(target_os = "linux")]
+        let mut gdb_jit_registration = None;
+        #[cfg(target_os = "linux")]
+        if debug_options.exports_jit_symbols() {
+            let symbols = JitSymbols::from_engine(&engine)?;
+            if debug_options.perf_map {
+                symbols.write_perf_map()?;
+            }
+            if debug_options.gdb_jit_interface {
+                gdb_jit_registration = Some(GdbJitRegistration::register(&symbols));
+            }
+        }
+
         let executor = Self {
-            engine: create_engine(&module, &metadata, opt_level),
+            #[cfg(target_os = "linux")]
+            _gdb_jit_registration: gdb_jit_registration,
+            engine,
             module,
             registry,
             gas_metadata: metadata.remove().ok_or(Error::MissingMetadata)?,
@@ -169,6 +206,15 @@ impl<'m> JitNativeExecutor<'m> {
         }
     }
 
+    /// Return the number of times each statement was executed since the executor was created or the
+    /// coverage was last taken, and reset the counts.
+    ///
+    /// Returns `None` unless the program was compiled with coverage instrumentation. Since the
+    /// counts are shared, concurrent invocations are accumulated together.
+    pub fn take_coverage(&self) -> Option<Coverage> {
+        take_coverage(|name| self.find_symbol_ptr(name))
+    }
+
     fn extract_signature(&self, function_id: &FunctionId) -> Result<&FunctionSignature, Error> {
         Ok(self
             .program_registry()
@@ -0,0 +1,468 @@
+//! # JIT symbols
+//!
+//! Makes the JIT-compiled functions visible to external profilers and debuggers, which otherwise
+//! only see anonymous addresses:
+//!
+//! - `perf` reads the symbols from `/tmp/perf-<pid>.map` files.
+//! - GDB (and LLDB) read them from in-memory object files registered using the GDB JIT interface.
+//!
+//! The symbols and their sizes are read from the object file generated by the execution engine.
+//! Their addresses are then relocated using the address of an exported symbol, since the engine
+//! loads each section contiguously.
+//!
+//! Both the object files and the GDB JIT interface are ELF-specific, so this module is only built
+//! on Linux.
+
+use crate::error::{Error, Result};
+use melior::ExecutionEngine;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    ptr,
+    sync::Mutex,
+};
+use tempfile::NamedTempFile;
+
+/// A JIT-compiled function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JitSymbol {
+    pub name: String,
+    /// Offset of the function within the text section.
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The functions in the text section of the JIT-compiled code.
+#[derive(Clone, Debug)]
+pub(crate) struct JitSymbols {
+    /// The ELF machine of the object file.
+    machine: u16,
+    /// The address where the text section was loaded.
+    text_address: u64,
+    text_size: u64,
+    symbols: Vec<JitSymbol>,
+}
+
+impl JitSymbols {
+    /// Extract the symbols from an execution engine, which must have been created with the object
+    /// dump enabled.
+    pub fn from_engine(engine: &ExecutionEngine) -> Result<Self> {
+        let object_file = NamedTempFile::new()?.into_temp_path();
+        engine.dump_to_object_file(&object_file.to_string_lossy());
+        let object = fs::read(&object_file)?;
+
+        let elf = ElfSymbols::parse(&object)?;
+
+        // Find the address of an exported symbol to know where the text section was loaded. Only
+        // the C interface wrappers are looked up since they're always exported.
+        let text_address = elf
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.global && symbol.name.starts_with("_mlir_ciface_"))
+            .find_map(|symbol| {
+                let address = engine.lookup(&symbol.name) as u64;
+                (address != 0).then(|| address - symbol.offset)
+            })
+            .ok_or_else(|| Error::JitSymbols("no exported symbol was found".to_string()))?;
+
+        Ok(Self {
+            machine: elf.machine,
+            text_address,
+            text_size: elf.text_size,
+            symbols: elf
+                .symbols
+                .into_iter()
+                .map(|symbol| JitSymbol {
+                    name: symbol.name,
+                    offset: symbol.offset,
+                    size: symbol.size,
+                })
+                .collect(),
+        })
+    }
+
+    /// Append the symbols to the process' perf map file.
+    pub fn write_perf_map(&self) -> Result<()> {
+        let mut output = String::new();
+        for symbol in &self.symbols {
+            output.push_str(&format!(
+                "{:x} {:x} {}\n",
+                self.text_address + symbol.offset,
+                symbol.size,
+                symbol.name
+            ));
+        }
+
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(output.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Build a minimal ELF object file, containing only the symbols, for the GDB JIT interface.
+    ///
+    /// The text section has no contents and is placed where the code was loaded, therefore the
+    /// symbols' values are relative to it.
+    fn to_elf(&self) -> Vec<u8> {
+        const EHDR_SIZE: usize = 64;
+        const SHDR_SIZE: usize = 64;
+        const SYM_SIZE: usize = 24;
+
+        let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; SYM_SIZE];
+        for symbol in &self.symbols {
+            let name = strtab.len() as u32;
+            strtab.extend_from_slice(symbol.name.as_bytes());
+            strtab.push(0);
+
+            symtab.extend_from_slice(&name.to_le_bytes());
+            symtab.push((STB_GLOBAL << 4) | STT_FUNC);
+            symtab.push(0);
+            symtab.extend_from_slice(&1u16.to_le_bytes()); // .text
+            symtab.extend_from_slice(&symbol.offset.to_le_bytes());
+            symtab.extend_from_slice(&symbol.size.to_le_bytes());
+        }
+
+        let symtab_offset = EHDR_SIZE;
+        let strtab_offset = symtab_offset + symtab.len();
+        let shstrtab_offset = strtab_offset + strtab.len();
+        let shdrs_offset = (shstrtab_offset + shstrtab.len()).next_multiple_of(8);
+
+        let mut elf = Vec::with_capacity(shdrs_offset + 5 * SHDR_SIZE);
+        elf.extend_from_slice(b"\x7fELF");
+        elf.extend_from_slice(&[2, 1, 1, 0]); // 64 bits, little endian, version 1, System V ABI.
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&ET_REL.to_le_bytes());
+        elf.extend_from_slice(&self.machine.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes()); // version
+        elf.extend_from_slice(&0u64.to_le_bytes()); // entry
+        elf.extend_from_slice(&0u64.to_le_bytes()); // program headers offset
+        elf.extend_from_slice(&(shdrs_offset as u64).to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes()); // flags
+        elf.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+        elf.extend_from_slice(&0u16.to_le_bytes()); // program header size
+        elf.extend_from_slice(&0u16.to_le_bytes()); // program header count
+        elf.extend_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+        elf.extend_from_slice(&5u16.to_le_bytes()); // section header count
+        elf.extend_from_slice(&4u16.to_le_bytes()); // .shstrtab index
+
+        elf.extend_from_slice(&symtab);
+        elf.extend_from_slice(&strtab);
+        elf.extend_from_slice(shstrtab);
+        elf.resize(shdrs_offset, 0);
+
+        let mut section_header = |name: u32,
+                                  ty: u32,
+                                  flags: u64,
+                                  addr: u64,
+                                  offset: usize,
+                                  size: u64,
+                                  link: u32,
+                                  info: u32,
+                                  entsize: u64| {
+            elf.extend_from_slice(&name.to_le_bytes());
+            elf.extend_from_slice(&ty.to_le_bytes());
+            elf.extend_from_slice(&flags.to_le_bytes());
+            elf.extend_from_slice(&addr.to_le_bytes());
+            elf.extend_from_slice(&(offset as u64).to_le_bytes());
+            elf.extend_from_slice(&size.to_le_bytes());
+            elf.extend_from_slice(&link.to_le_bytes());
+            elf.extend_from_slice(&info.to_le_bytes());
+            elf.extend_from_slice(&1u64.to_le_bytes()); // alignment
+            elf.extend_from_slice(&entsize.to_le_bytes());
+        };
+        section_header(0, 0, 0, 0, 0, 0, 0, 0, 0);
+        section_header(
+            1,
+            SHT_NOBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            self.text_address,
+            0,
+            self.text_size,
+            0,
+            0,
+            0,
+        );
+        section_header(
+            7,
+            SHT_SYMTAB,
+            0,
+            0,
+            symtab_offset,
+            symtab.len() as u64,
+            3,
+            1, // index of the first global symbol
+            SYM_SIZE as u64,
+        );
+        section_header(
+            15,
+            SHT_STRTAB,
+            0,
+            0,
+            strtab_offset,
+            strtab.len() as u64,
+            0,
+            0,
+            0,
+        );
+        section_header(
+            23,
+            SHT_STRTAB,
+            0,
+            0,
+            shstrtab_offset,
+            shstrtab.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        elf
+    }
+}
+
+const ET_REL: u16 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const STB_GLOBAL: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+/// A function symbol read from an ELF object file.
+struct ElfSymbol {
+    name: String,
+    offset: u64,
+    size: u64,
+    global: bool,
+}
+
+/// The function symbols of the text section of a 64-bit little-endian ELF object file.
+struct ElfSymbols {
+    machine: u16,
+    text_size: u64,
+    symbols: Vec<ElfSymbol>,
+}
+
+impl ElfSymbols {
+    fn parse(object: &[u8]) -> Result<Self> {
+        let error = |message: &str| Error::JitSymbols(message.to_string());
+        let read = |offset: usize, size: usize| {
+            object
+                .get(offset..offset + size)
+                .ok_or_else(|| error("unexpected end of the object file"))
+        };
+        let read_u16 = |offset| read(offset, 2).map(|x| u16::from_le_bytes(x.try_into().unwrap()));
+        let read_u32 = |offset| read(offset, 4).map(|x| u32::from_le_bytes(x.try_into().unwrap()));
+        let read_u64 = |offset| read(offset, 8).map(|x| u64::from_le_bytes(x.try_into().unwrap()));
+
+        if read(0, 6)? != b"\x7fELF\x02\x01" {
+            return Err(error("not a 64-bit little-endian ELF object file"));
+        }
+
+        let machine = read_u16(0x12)?;
+        let shdrs_offset = read_u64(0x28)? as usize;
+        let shdr_size = read_u16(0x3A)? as usize;
+        let shdrs_count = read_u16(0x3C)? as usize;
+        let shstrtab_index = read_u16(0x3E)? as usize;
+
+        let section = |index: usize| -> Result<(u32, u32, usize, usize, usize)> {
+            let shdr = shdrs_offset + index * shdr_size;
+            Ok((
+                read_u32(shdr)?,
+                read_u32(shdr + 4)?,
+                read_u64(shdr + 24)? as usize,
+                read_u64(shdr + 32)? as usize,
+                read_u32(shdr + 40)? as usize,
+            ))
+        };
+        let read_str = |offset: usize| -> Result<String> {
+            let bytes = object
+                .get(offset..)
+                .ok_or_else(|| error("invalid string offset"))?;
+            let len = bytes
+                .iter()
+                .position(|x| *x == 0)
+                .ok_or_else(|| error("unterminated string"))?;
+            Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+        };
+
+        let (_, _, shstrtab_offset, _, _) = section(shstrtab_index)?;
+        let mut text = None;
+        let mut symtab = None;
+        for index in 0..shdrs_count {
+            let (name, ty, offset, size, link) = section(index)?;
+            match ty {
+                SHT_SYMTAB => symtab = Some((offset, size, link)),
+                _ if read_str(shstrtab_offset + name as usize)? == ".text" => {
+                    text = Some((index, size))
+                }
+                _ => {}
+            }
+        }
+        let (text_index, text_size) = text.ok_or_else(|| error("missing text section"))?;
+        let (symtab_offset, symtab_size, strtab_index) =
+            symtab.ok_or_else(|| error("missing symbol table"))?;
+        let (_, _, strtab_offset, _, _) = section(strtab_index)?;
+
+        let mut symbols = Vec::new();
+        for symbol in (symtab_offset..symtab_offset + symtab_size).step_by(24) {
+            let info = read(symbol + 4, 1)?[0];
+            let section_index = read_u16(symbol + 6)? as usize;
+            let size = read_u64(symbol + 16)?;
+            if info & 0xF != STT_FUNC || section_index != text_index || size == 0 {
+                continue;
+            }
+
+            symbols.push(ElfSymbol {
+                name: read_str(strtab_offset + read_u32(symbol)? as usize)?,
+                offset: read_u64(symbol + 8)?,
+                size,
+                global: info >> 4 == STB_GLOBAL,
+            });
+        }
+
+        Ok(Self {
+            machine,
+            text_size: text_size as u64,
+            symbols,
+        })
+    }
+}
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+// Defined by LLVM's GDB registration listener, which is linked along with the execution engine.
+// Debuggers set a breakpoint on `__jit_debug_register_code` and read the descriptor when it's hit.
+extern "C" {
+    static mut __jit_debug_descriptor: JitDescriptor;
+    fn __jit_debug_register_code();
+}
+
+/// Serializes the accesses to the GDB JIT descriptor from this crate.
+static JIT_DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+
+/// An object file registered with the GDB JIT interface. It's unregistered when dropped.
+pub(crate) struct GdbJitRegistration {
+    entry: *mut JitCodeEntry,
+    _object: Box<[u8]>,
+}
+
+impl GdbJitRegistration {
+    pub fn register(symbols: &JitSymbols) -> Self {
+        let object = symbols.to_elf().into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: object.as_ptr(),
+            symfile_size: object.len() as u64,
+        }));
+
+        let _guard = JIT_DESCRIPTOR_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            let descriptor = &raw mut __jit_debug_descriptor;
+            (*entry).next_entry = (*descriptor).first_entry;
+            if let Some(next_entry) = (*entry).next_entry.as_mut() {
+                next_entry.prev_entry = entry;
+            }
+            (*descriptor).first_entry = entry;
+            (*descriptor).relevant_entry = entry;
+            (*descriptor).action_flag = JIT_REGISTER_FN;
+            __jit_debug_register_code();
+        }
+
+        Self {
+            entry,
+            _object: object,
+        }
+    }
+}
+
+impl Drop for GdbJitRegistration {
+    fn drop(&mut self) {
+        let _guard = JIT_DESCRIPTOR_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            let descriptor = &raw mut __jit_debug_descriptor;
+            let entry = &mut *self.entry;
+            match entry.prev_entry.as_mut() {
+                Some(prev_entry) => prev_entry.next_entry = entry.next_entry,
+                None => (*descriptor).first_entry = entry.next_entry,
+            }
+            if let Some(next_entry) = entry.next_entry.as_mut() {
+                next_entry.prev_entry = entry.prev_entry;
+            }
+            (*descriptor).relevant_entry = self.entry;
+            (*descriptor).action_flag = JIT_UNREGISTER_FN;
+            __jit_debug_register_code();
+
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elf_roundtrip() {
+        let symbols = JitSymbols {
+            machine: 62, // x86-64
+            text_address: 0x1000,
+            text_size: 0x30,
+            symbols: vec![
+                JitSymbol {
+                    name: "impl$program::main(f0)".to_string(),
+                    offset: 0,
+                    size: 0x20,
+                },
+                JitSymbol {
+                    name: "_mlir_ciface_program::main(f0)".to_string(),
+                    offset: 0x20,
+                    size: 0x10,
+                },
+            ],
+        };
+
+        let elf = ElfSymbols::parse(&symbols.to_elf()).unwrap();
+        assert_eq!(elf.machine, 62);
+        assert_eq!(elf.text_size, 0x30);
+        assert_eq!(
+            elf.symbols
+                .iter()
+                .map(|x| (x.name.as_str(), x.offset, x.size))
+                .collect::<Vec<_>>(),
+            [
+                ("impl$program::main(f0)", 0, 0x20),
+                ("_mlir_ciface_program::main(f0)", 0x20, 0x10),
+            ],
+        );
+    }
+}
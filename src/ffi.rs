@@ -3,11 +3,14 @@
 //! This is a "hotfix" for missing Rust interfaces to the C/C++ libraries we use, namely LLVM/MLIR
 //! APIs that are missing from melior.
 
-use crate::error::{panic::ToNativeAssertError, Error, Result};
+use crate::{
+    debug::DebugOptions,
+    error::{panic::ToNativeAssertError, Error, Result},
+};
 use llvm_sys::{
     core::{
         LLVMContextCreate, LLVMContextDispose, LLVMDisposeMemoryBuffer, LLVMDisposeMessage,
-        LLVMDisposeModule, LLVMGetBufferSize, LLVMGetBufferStart,
+        LLVMDisposeModule, LLVMGetBufferSize, LLVMGetBufferStart, LLVMPrintModuleToString,
     },
     error::LLVMGetErrorMessage,
     prelude::LLVMMemoryBufferRef,
@@ -94,8 +97,39 @@ impl From<u8> for OptLevel {
     }
 }
 
+/// The output of the LLVM compilation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LlvmOutput {
+    /// Textual LLVM IR, after the LLVM passes.
+    Ir,
+    /// Textual assembly for the host target.
+    Assembly,
+    /// An object file for the host target.
+    Object,
+}
+
 /// Converts a MLIR module to a compile object, that can be linked with a linker.
 pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<u8>> {
+    compile_llvm(module, opt_level, LlvmOutput::Object)
+}
+
+/// Converts a MLIR module to LLVM IR, optimized according to the given optimization level.
+///
+/// The module must have been lowered to the LLVM dialect already.
+pub fn module_to_llvm_ir(module: &Module<'_>, opt_level: OptLevel) -> Result<String> {
+    let data = compile_llvm(module, opt_level, LlvmOutput::Ir)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Converts a MLIR module to assembly for the host target.
+///
+/// The module must have been lowered to the LLVM dialect already.
+pub fn module_to_assembly(module: &Module<'_>, opt_level: OptLevel) -> Result<String> {
+    let data = compile_llvm(module, opt_level, LlvmOutput::Assembly)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn compile_llvm(module: &Module<'_>, opt_level: OptLevel, output: LlvmOutput) -> Result<Vec<u8>> {
     static INITIALIZED: OnceLock<()> = OnceLock::new();
 
     INITIALIZED.get_or_init(|| unsafe {
@@ -181,6 +215,18 @@ pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<
 
         LLVMDisposePassBuilderOptions(opts);
 
+        if output == LlvmOutput::Ir {
+            let llvm_ir = LLVMPrintModuleToString(llvm_module);
+            let data = CStr::from_ptr(llvm_ir).to_bytes().to_vec();
+
+            LLVMDisposeMessage(llvm_ir);
+            LLVMDisposeTargetMachine(machine);
+            LLVMDisposeModule(llvm_module);
+            LLVMContextDispose(llvm_context);
+
+            return Ok(data);
+        }
+
         let mut out_buf: MaybeUninit<LLVMMemoryBufferRef> = MaybeUninit::uninit();
 
         trace!("starting llvm to object compilation");
@@ -188,7 +234,10 @@ pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<
         let ok = LLVMTargetMachineEmitToMemoryBuffer(
             machine,
             llvm_module,
-            LLVMCodeGenFileType::LLVMObjectFile,
+            match output {
+                LlvmOutput::Assembly => LLVMCodeGenFileType::LLVMAssemblyFile,
+                _ => LLVMCodeGenFileType::LLVMObjectFile,
+            },
             error_buffer,
             out_buf.as_mut_ptr(),
         );
@@ -226,21 +275,35 @@ pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<
 
 /// Links the passed object into a shared library, stored on the given path.
 pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()> {
+    object_to_shared_lib_with_debug_options(object, output_filename, &DebugOptions::new())
+}
+
+/// Links the passed object into a shared library, stored on the given path.
+///
+/// If the debug options have an artifacts directory, the object is written there and kept after
+/// linking so that debuggers can load its debug info.
+pub fn object_to_shared_lib_with_debug_options(
+    object: &[u8],
+    output_filename: &Path,
+    debug_options: &DebugOptions,
+) -> Result<()> {
     // linker seems to need a file and doesn't accept stdin
-    let mut file = NamedTempFile::new()?;
-    file.write_all(object)?;
-    let file = file.into_temp_path();
+    let temp_file;
+    let file_path = match debug_options.artifact_path(".o") {
+        Some(path) => {
+            debug_options.write_artifact(".o", object)?;
+            path
+        }
+        None => {
+            let mut file = NamedTempFile::new()?;
+            file.write_all(object)?;
+            temp_file = file.into_temp_path();
+            temp_file.to_path_buf()
+        }
+    };
 
-    let file_path = file.display().to_string();
+    let file_path = file_path.display().to_string();
     let output_path = output_filename.display().to_string();
-    if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-        if x == "1" || x == "true" {
-            // forget so the temp file is not deleted and the debugger can load it.
-            // its still in a temp file directory so eventually the OS will delete it, but just not instantly.
-            // todo: maybe remove it when exiting, for example using atexit.
-            std::mem::forget(file);
-        }
-    }
 
     let args: Vec<Cow<'static, str>> = {
         #[cfg(target_os = "macos")]
@@ -361,6 +424,7 @@ pub fn get_data_layout_rep() -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{context::NativeContext, utils::test::load_cairo};
 
     #[test]
     fn test_opt_level_default() {
@@ -396,4 +460,23 @@ mod tests {
         assert_eq!(OptLevel::from(3u8), OptLevel::Aggressive);
         assert_eq!(OptLevel::from(30u8), OptLevel::Aggressive);
     }
+
+    #[test]
+    fn test_module_to_llvm_ir_and_assembly() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        let context = NativeContext::new();
+        let module = context.compile(&program, true, None).unwrap();
+
+        let llvm_ir = module_to_llvm_ir(module.module(), OptLevel::None).unwrap();
+        assert!(llvm_ir.contains("define"));
+
+        let assembly = module_to_assembly(module.module(), OptLevel::Default).unwrap();
+        assert!(!assembly.is_empty());
+        assert_ne!(assembly, llvm_ir);
+    }
 }
@@ -10,7 +10,10 @@
 
 pub use self::{
     compiler::compile,
-    ffi::{module_to_object, object_to_shared_lib, OptLevel},
+    ffi::{
+        module_to_assembly, module_to_llvm_ir, module_to_object, object_to_shared_lib,
+        object_to_shared_lib_with_debug_options, OptLevel,
+    },
     runtime::FormattedItem,
     values::Value,
 };
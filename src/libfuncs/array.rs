@@ -500,6 +500,51 @@ pub fn build_append<'ctx, 'this>(
     let ptr_ty = llvm::r#type::pointer(context, 0);
     let len_ty = IntegerType::new(context, 32).into();
 
+    #[cfg(feature = "with-debug-utils")]
+    {
+        use crate::metadata::auto_breakpoint::{
+            build_conditional_breakpoint, AutoBreakpoint, BreakpointEvent,
+        };
+
+        let breakpoints = metadata
+            .get::<AutoBreakpoint>()
+            .map(|auto_breakpoint| {
+                auto_breakpoint
+                    .matching(|event| matches!(event, BreakpointEvent::ArrayLength { .. }))
+            })
+            .unwrap_or_default();
+        if !breakpoints.is_empty() {
+            let array_start =
+                entry.extract_value(context, location, entry.argument(0)?.into(), len_ty, 1)?;
+            let array_end =
+                entry.extract_value(context, location, entry.argument(0)?.into(), len_ty, 2)?;
+            let array_len =
+                entry.append_op_result(arith::subi(array_end, array_start, location))?;
+
+            for breakpoint in &breakpoints {
+                let BreakpointEvent::ArrayLength { max_len } = breakpoint.event else {
+                    continue;
+                };
+
+                // The array grows past `max_len` elements when appending to one which has exactly
+                // `max_len` elements.
+                let max_len = entry.const_int_from_type(context, location, max_len, len_ty)?;
+                let is_growing_past =
+                    entry.cmpi(context, CmpiPredicate::Eq, array_len, max_len, location)?;
+                build_conditional_breakpoint(
+                    context,
+                    helper,
+                    entry,
+                    location,
+                    metadata,
+                    breakpoint,
+                    is_growing_past,
+                    &[array_len],
+                )?;
+            }
+        }
+    }
+
     let (_, elem_layout) = registry.build_type_with_layout(context, helper, metadata, &info.ty)?;
     let elem_stride = entry.const_int(context, location, elem_layout.pad_to_align().size(), 64)?;
 
@@ -68,18 +68,35 @@ pub fn build_init<'ctx, 'this>(
     info: &EnumInitConcreteLibfunc,
 ) -> Result<()> {
     #[cfg(feature = "with-debug-utils")]
-    if let Some(auto_breakpoint) =
-        metadata.get::<crate::metadata::auto_breakpoint::AutoBreakpoint>()
     {
-        auto_breakpoint.maybe_breakpoint(
+        use crate::metadata::auto_breakpoint::{AutoBreakpoint, BreakpointEvent};
+
+        let enum_ty = &info.signature.branch_signatures[0].vars[0].ty;
+        AutoBreakpoint::maybe_breakpoint(
+            context,
+            helper,
             entry,
             location,
             metadata,
-            &crate::metadata::auto_breakpoint::BreakpointEvent::EnumInit {
-                type_id: info.signature.branch_signatures[0].vars[0].ty.clone(),
+            &BreakpointEvent::EnumInit {
+                type_id: enum_ty.clone(),
                 variant_idx: info.index,
             },
+            &[],
         )?;
+
+        // The second variant of a `PanicResult` is the panic.
+        if info.index == 1 && crate::debug::backtrace::is_panic_result(registry, enum_ty) {
+            AutoBreakpoint::maybe_breakpoint(
+                context,
+                helper,
+                entry,
+                location,
+                metadata,
+                &BreakpointEvent::Panic,
+                &[],
+            )?;
+        }
     }
 
     let val = build_enum_value(
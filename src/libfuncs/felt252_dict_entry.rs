@@ -82,6 +82,50 @@ pub fn build_get<'ctx, 'this>(
     let dict_ptr = entry.arg(0)?;
     let entry_key = entry.arg(1)?;
 
+    #[cfg(feature = "with-debug-utils")]
+    {
+        use crate::metadata::auto_breakpoint::{
+            build_conditional_breakpoint, AutoBreakpoint, BreakpointEvent,
+        };
+
+        let breakpoints = metadata
+            .get::<AutoBreakpoint>()
+            .map(|auto_breakpoint| {
+                auto_breakpoint
+                    .matching(|event| matches!(event, BreakpointEvent::DictAccess { .. }))
+            })
+            .unwrap_or_default();
+        for breakpoint in &breakpoints {
+            let BreakpointEvent::DictAccess { key } = &breakpoint.event else {
+                continue;
+            };
+
+            let key = entry.const_int_from_type(
+                context,
+                location,
+                num_bigint::BigInt::from(key.to_biguint()),
+                key_ty,
+            )?;
+            let is_key = entry.cmpi(
+                context,
+                melior::dialect::arith::CmpiPredicate::Eq,
+                entry_key,
+                key,
+                location,
+            )?;
+            build_conditional_breakpoint(
+                context,
+                helper,
+                entry,
+                location,
+                metadata,
+                breakpoint,
+                is_key,
+                &[entry_key],
+            )?;
+        }
+    }
+
     let entry_key_ptr =
         helper
             .init_block()
@@ -15,6 +15,7 @@ use std::{
 };
 
 pub mod auto_breakpoint;
+pub mod coverage;
 pub mod debug_utils;
 pub mod drop_overrides;
 pub mod dup_overrides;
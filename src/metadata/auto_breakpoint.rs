@@ -1,47 +1,417 @@
+//! # Automatic breakpoints
+//!
+//! Breakpoints which are inserted into the generated code when the program reaches certain events,
+//! like a libfunc being invoked or a dictionary entry with a specific key being accessed. Each one
+//! either traps, prints the values involved in the event or aborts the execution after printing
+//! them.
+//!
+//! They're registered using [`AutoBreakpoint::add_breakpoint`] or loaded from a JSON file with
+//! [`AutoBreakpoint::from_config`], which contains a list of breakpoints:
+//!
+//! ```json
+//! [
+//!     { "event": { "libfunc_invocation": { "name": "felt252_div" } }, "action": "print" },
+//!     { "event": { "function_entry": { "name": "fib" } } },
+//!     { "event": "panic", "action": "abort" },
+//!     { "event": { "array_length": { "max_len": 1000 } }, "action": "print" },
+//!     { "event": { "dict_access": { "key": "0x2a" } }, "action": "trap" }
+//! ]
+//! ```
+//!
+//! The breakpoints are inserted when they're set in the
+//! [`DebugOptions`](crate::debug::DebugOptions) of a compilation.
+
 #![cfg(feature = "with-debug-utils")]
 
 use super::{debug_utils::DebugUtils, MetadataStorage};
-use crate::error::Error;
-use cairo_lang_sierra::ids::ConcreteTypeId;
-use melior::ir::{Block, Location};
-use std::collections::HashSet;
+use crate::{
+    error::{Error, Result},
+    utils::BlockExt,
+};
+use cairo_lang_sierra::ids::{ConcreteLibfuncId, ConcreteTypeId, FunctionId};
+use melior::{
+    dialect::{llvm, scf},
+    ir::{r#type::IntegerType, Block, BlockLike, Location, Module, Region, TypeLike, Value},
+    Context,
+};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{fmt, fs, path::Path};
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// An event which triggers a breakpoint.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BreakpointEvent {
+    /// An enum variant is constructed.
     EnumInit {
         type_id: ConcreteTypeId,
         variant_idx: usize,
     },
+    /// A libfunc is invoked. The name is matched against the libfunc's debug name, if any, and
+    /// its generic name (for example, `felt252_div` or `array_append`).
+    LibfuncInvocation { name: String },
+    /// A function is entered. The name is matched against the function's whole debug name or its
+    /// last path segments (for example, `fib` or `program::fib`).
+    FunctionEntry { name: String },
+    /// A panic is constructed.
+    Panic,
+    /// An array grows past the given number of elements.
+    ArrayLength { max_len: u32 },
+    /// A dictionary entry with the given key is accessed.
+    DictAccess { key: Felt },
+}
+
+impl BreakpointEvent {
+    /// Whether the event is the invocation of the given libfunc, whose generic name is given.
+    pub fn matches_libfunc(&self, libfunc_id: &ConcreteLibfuncId, generic_name: &str) -> bool {
+        match self {
+            Self::LibfuncInvocation { name } => {
+                name == generic_name || libfunc_id.debug_name.as_deref() == Some(name.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the event is the entry into the given function.
+    pub fn matches_function(&self, function_id: &FunctionId) -> bool {
+        match self {
+            Self::FunctionEntry { name } => {
+                function_id.debug_name.as_ref().is_some_and(|debug_name| {
+                    debug_name == name || debug_name.ends_with(&format!("::{name}"))
+                })
+            }
+            _ => false,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Default)]
+impl fmt::Display for BreakpointEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EnumInit {
+                type_id,
+                variant_idx,
+            } => write!(f, "variant {variant_idx} of {type_id} constructed"),
+            Self::LibfuncInvocation { name } => write!(f, "libfunc {name} invoked"),
+            Self::FunctionEntry { name } => write!(f, "function {name} entered"),
+            Self::Panic => write!(f, "panic constructed"),
+            Self::ArrayLength { max_len } => write!(f, "array grew past {max_len} elements"),
+            Self::DictAccess { key } => write!(f, "dictionary entry {key} accessed"),
+        }
+    }
+}
+
+/// What a breakpoint does when it's hit.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakpointAction {
+    /// Insert a debugger trap.
+    #[default]
+    Trap,
+    /// Print the event and the values involved in it, then continue.
+    Print,
+    /// Print the event and the values involved in it, then abort the execution.
+    Abort,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub event: BreakpointEvent,
+    #[serde(default)]
+    pub action: BreakpointAction,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct AutoBreakpoint {
-    events: HashSet<BreakpointEvent>,
+    breakpoints: Vec<Breakpoint>,
 }
 
 impl AutoBreakpoint {
+    pub const fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Load the breakpoints from a JSON file (see the [module documentation](self)).
+    pub fn from_config(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Add a breakpoint which traps when the event happens.
     pub fn add_event(&mut self, event: BreakpointEvent) {
-        self.events.insert(event);
+        self.add_breakpoint(event, BreakpointAction::Trap);
+    }
+
+    pub fn add_breakpoint(&mut self, event: BreakpointEvent, action: BreakpointAction) {
+        let breakpoint = Breakpoint { event, action };
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
     }
 
     pub fn has_event(&self, event: &BreakpointEvent) -> bool {
-        self.events.contains(event)
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| &breakpoint.event == event)
     }
 
-    pub fn maybe_breakpoint(
-        &self,
-        block: &Block,
-        location: Location,
-        metadata: &MetadataStorage,
+    /// Return the breakpoints whose events satisfy the predicate.
+    pub fn matching(&self, predicate: impl Fn(&BreakpointEvent) -> bool) -> Vec<Breakpoint> {
+        self.breakpoints
+            .iter()
+            .filter(|breakpoint| predicate(&breakpoint.event))
+            .cloned()
+            .collect()
+    }
+
+    /// Insert the actions of the breakpoints registered for the event, if any.
+    pub fn maybe_breakpoint<'c, 'a>(
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        metadata: &mut MetadataStorage,
         event: &BreakpointEvent,
-    ) -> Result<(), Error> {
-        if self.has_event(event) {
-            metadata
-                .get::<DebugUtils>()
-                .ok_or(Error::MissingMetadata)?
-                .debug_breakpoint_trap(block, location)?;
+        values: &[Value<'c, 'a>],
+    ) -> Result<()> {
+        let breakpoints = match metadata.get::<Self>() {
+            Some(auto_breakpoint) => auto_breakpoint.matching(|x| x == event),
+            None => return Ok(()),
+        };
+
+        for breakpoint in &breakpoints {
+            build_breakpoint(
+                context, module, block, location, metadata, breakpoint, values,
+            )?;
         }
 
         Ok(())
     }
 }
+
+/// Insert the action of a breakpoint, printing the given values if it prints or aborts.
+pub fn build_breakpoint<'c, 'a>(
+    context: &'c Context,
+    module: &Module,
+    block: &'a Block<'c>,
+    location: Location<'c>,
+    metadata: &mut MetadataStorage,
+    breakpoint: &Breakpoint,
+    values: &[Value<'c, 'a>],
+) -> Result<()> {
+    let debug_utils = metadata
+        .get_mut::<DebugUtils>()
+        .ok_or(Error::MissingMetadata)?;
+
+    match breakpoint.action {
+        BreakpointAction::Trap => debug_utils.debug_breakpoint_trap(block, location)?,
+        BreakpointAction::Print | BreakpointAction::Abort => {
+            let message = match breakpoint.action {
+                BreakpointAction::Abort => {
+                    format!("Breakpoint hit, aborting: {}", breakpoint.event)
+                }
+                _ => format!("Breakpoint hit: {}", breakpoint.event),
+            };
+            debug_utils.debug_print(context, module, block, &message, location)?;
+            for value in values {
+                print_value(context, module, block, location, debug_utils, *value)?;
+            }
+
+            if breakpoint.action == BreakpointAction::Abort {
+                debug_utils.abort(context, module, block, location)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert the action of a breakpoint which only runs when `condition` (an `i1`) is true.
+#[allow(clippy::too_many_arguments)]
+pub fn build_conditional_breakpoint<'c, 'a>(
+    context: &'c Context,
+    module: &Module,
+    block: &'a Block<'c>,
+    location: Location<'c>,
+    metadata: &mut MetadataStorage,
+    breakpoint: &Breakpoint,
+    condition: Value<'c, 'a>,
+    values: &[Value<'c, 'a>],
+) -> Result<()> {
+    block.append_operation(scf::r#if(
+        condition,
+        &[],
+        {
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+
+            build_breakpoint(
+                context, module, &block, location, metadata, breakpoint, values,
+            )?;
+
+            block.append_operation(scf::r#yield(&[], location));
+            region
+        },
+        {
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+            block.append_operation(scf::r#yield(&[], location));
+            region
+        },
+        location,
+    ));
+
+    Ok(())
+}
+
+/// Print an integer, felt or pointer value. Other values are printed as a placeholder.
+fn print_value<'c, 'a>(
+    context: &'c Context,
+    module: &Module,
+    block: &'a Block<'c>,
+    location: Location<'c>,
+    debug_utils: &mut DebugUtils,
+    value: Value<'c, 'a>,
+) -> Result<()> {
+    let value_ty = value.r#type();
+    if value_ty == llvm::r#type::pointer(context, 0) {
+        return debug_utils.print_pointer(context, module, block, value, location);
+    }
+
+    let width = match value_ty.is_integer() {
+        true => IntegerType::try_from(value_ty)?.width(),
+        false => 0,
+    };
+    // Integers are zero-extended to the width expected by the print functions.
+    let print_width = match width {
+        1 | 8 | 32 | 64 | 128 | 252 => width,
+        2..=7 => 8,
+        9..=31 => 32,
+        33..=63 => 64,
+        65..=127 => 128,
+        _ => {
+            return debug_utils.debug_print(context, module, block, "<unprintable value>", location)
+        }
+    };
+    let value = match width == print_width {
+        true => value,
+        false => block.extui(
+            value,
+            IntegerType::new(context, print_width).into(),
+            location,
+        )?,
+    };
+
+    match print_width {
+        1 => debug_utils.print_i1(context, module, block, value, location),
+        8 => debug_utils.print_i8(context, module, block, value, location),
+        32 => debug_utils.print_i32(context, module, block, value, location),
+        64 => debug_utils.print_i64(context, module, block, value, location),
+        128 => debug_utils.print_i128(context, module, block, value, location),
+        _ => debug_utils.print_felt252(context, module, block, value, location),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoint_config() {
+        let auto_breakpoint: AutoBreakpoint = serde_json::from_str(
+            r#"[
+                { "event": { "libfunc_invocation": { "name": "felt252_div" } }, "action": "print" },
+                { "event": { "function_entry": { "name": "fib" } } },
+                { "event": "panic", "action": "abort" },
+                { "event": { "array_length": { "max_len": 1000 } }, "action": "print" },
+                { "event": { "dict_access": { "key": "0x2a" } }, "action": "trap" }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            auto_breakpoint.matching(|_| true),
+            [
+                Breakpoint {
+                    event: BreakpointEvent::LibfuncInvocation {
+                        name: "felt252_div".to_string()
+                    },
+                    action: BreakpointAction::Print,
+                },
+                Breakpoint {
+                    event: BreakpointEvent::FunctionEntry {
+                        name: "fib".to_string()
+                    },
+                    action: BreakpointAction::Trap,
+                },
+                Breakpoint {
+                    event: BreakpointEvent::Panic,
+                    action: BreakpointAction::Abort,
+                },
+                Breakpoint {
+                    event: BreakpointEvent::ArrayLength { max_len: 1000 },
+                    action: BreakpointAction::Print,
+                },
+                Breakpoint {
+                    event: BreakpointEvent::DictAccess {
+                        key: Felt::from(42)
+                    },
+                    action: BreakpointAction::Trap,
+                },
+            ]
+        );
+        assert!(auto_breakpoint.has_event(&BreakpointEvent::Panic));
+        assert!(!auto_breakpoint.has_event(&BreakpointEvent::ArrayLength { max_len: 10 }));
+    }
+
+    #[test]
+    fn test_breakpoint_matching() {
+        let mut auto_breakpoint = AutoBreakpoint::new();
+        auto_breakpoint.add_breakpoint(
+            BreakpointEvent::LibfuncInvocation {
+                name: "felt252_div".to_string(),
+            },
+            BreakpointAction::Print,
+        );
+        auto_breakpoint.add_event(BreakpointEvent::FunctionEntry {
+            name: "fib".to_string(),
+        });
+        // Breakpoints are only registered once.
+        auto_breakpoint.add_event(BreakpointEvent::FunctionEntry {
+            name: "fib".to_string(),
+        });
+
+        let libfunc_id = ConcreteLibfuncId::from_string("felt252_div_const<2>");
+        assert_eq!(
+            auto_breakpoint
+                .matching(|event| event.matches_libfunc(&libfunc_id, "felt252_div"))
+                .len(),
+            1
+        );
+        assert!(auto_breakpoint
+            .matching(|event| event.matches_libfunc(&libfunc_id, "felt252_add"))
+            .is_empty());
+
+        let function_id = FunctionId::from_string("program::program::fib");
+        assert_eq!(
+            auto_breakpoint
+                .matching(|event| event.matches_function(&function_id))
+                .len(),
+            1
+        );
+        assert!(auto_breakpoint
+            .matching(|event| event.matches_function(&FunctionId::new(3)))
+            .is_empty());
+        // Only whole path segments match.
+        let function_id = FunctionId::from_string("program::program::not_fib");
+        assert!(auto_breakpoint
+            .matching(|event| event.matches_function(&function_id))
+            .is_empty());
+    }
+}
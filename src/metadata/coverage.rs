@@ -0,0 +1,182 @@
+//! # Statement coverage instrumentation
+//!
+//! When coverage is enabled in the [`DebugOptions`](crate::debug::DebugOptions), this metadata is
+//! inserted before compiling the program. The compiler then increments a counter every time a
+//! statement is executed.
+//!
+//! The counters are stored in a zero-initialized global array with one 64-bit integer per
+//! statement, alongside another global containing the array's length. Both are looked up by the
+//! executors after the module is loaded, so that the hit counts can be read (and reset) after a
+//! run.
+//!
+//! The counters are incremented atomically and therefore concurrent invocations of the same
+//! executor are counted correctly, but they're accumulated together.
+
+use crate::{
+    debug::Coverage,
+    error::{Error, Result},
+    utils::{BlockExt, GepIndex},
+};
+use cairo_lang_sierra::program::StatementIdx;
+use melior::{
+    dialect::{llvm, ods},
+    ir::{
+        attribute::{FlatSymbolRefAttribute, IntegerAttribute, StringAttribute, TypeAttribute},
+        operation::OperationBuilder,
+        r#type::IntegerType,
+        Attribute, Block, BlockLike, Identifier, Location, Module, Region, Type,
+    },
+    Context,
+};
+use std::{
+    ffi::c_void,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const COUNTERS_SYMBOL: &str = "cairo_native__coverage_counters";
+const COUNTERS_LEN_SYMBOL: &str = "cairo_native__coverage_counters_len";
+
+#[derive(Debug)]
+pub struct CoverageMeta {
+    num_statements: usize,
+    declared: bool,
+}
+
+impl CoverageMeta {
+    /// Create the metadata for a program with `num_statements` statements.
+    pub fn new(num_statements: usize) -> Self {
+        Self {
+            num_statements,
+            declared: false,
+        }
+    }
+
+    /// Increment the statement's hit counter.
+    pub fn increment_counter<'c>(
+        &mut self,
+        context: &'c Context,
+        module: &Module<'c>,
+        block: &Block<'c>,
+        location: Location<'c>,
+        statement_idx: StatementIdx,
+    ) -> Result<()> {
+        self.declare_globals(context, module, location)?;
+
+        let i64_ty = IntegerType::new(context, 64).into();
+        let counters = block.append_op_result(
+            ods::llvm::mlir_addressof(
+                context,
+                llvm::r#type::pointer(context, 0),
+                FlatSymbolRefAttribute::new(context, COUNTERS_SYMBOL),
+                location,
+            )
+            .into(),
+        )?;
+        let counter = block.gep(
+            context,
+            location,
+            counters,
+            &[GepIndex::Const(statement_idx.0.try_into()?)],
+            i64_ty,
+        )?;
+
+        let k1 = block.const_int_from_type(context, location, 1, i64_ty)?;
+        block.append_operation(
+            OperationBuilder::new("llvm.atomicrmw", location)
+                .add_attributes(&[
+                    (
+                        Identifier::new(context, "bin_op"),
+                        Attribute::parse(context, "#llvm.atomic_bin_op<add>")
+                            .ok_or(Error::ParseAttributeError)?,
+                    ),
+                    (
+                        Identifier::new(context, "ordering"),
+                        Attribute::parse(context, "#llvm.atomic_ordering<monotonic>")
+                            .ok_or(Error::ParseAttributeError)?,
+                    ),
+                ])
+                .add_operands(&[counter, k1])
+                .add_results(&[i64_ty])
+                .build()?,
+        );
+
+        Ok(())
+    }
+
+    /// Declare the counters and their length, unless they have already been declared.
+    fn declare_globals<'c>(
+        &mut self,
+        context: &'c Context,
+        module: &Module<'c>,
+        location: Location<'c>,
+    ) -> Result<()> {
+        if self.declared {
+            return Ok(());
+        }
+
+        let i64_ty: Type = IntegerType::new(context, 64).into();
+        let counters_ty = llvm::r#type::array(i64_ty, self.num_statements.try_into()?);
+
+        let initializer = Region::new();
+        let block = initializer.append_block(Block::new(&[]));
+        let zero =
+            block.append_op_result(ods::llvm::mlir_zero(context, counters_ty, location).into())?;
+        block.append_operation(llvm::r#return(Some(zero), location));
+        module.body().append_operation(
+            ods::llvm::mlir_global(
+                context,
+                initializer,
+                TypeAttribute::new(counters_ty),
+                StringAttribute::new(context, COUNTERS_SYMBOL),
+                Attribute::parse(context, "#llvm.linkage<external>")
+                    .ok_or(Error::ParseAttributeError)?,
+                location,
+            )
+            .into(),
+        );
+
+        let initializer = Region::new();
+        let block = initializer.append_block(Block::new(&[]));
+        let len = block.append_op_result(
+            ods::llvm::mlir_constant(
+                context,
+                i64_ty,
+                IntegerAttribute::new(i64_ty, self.num_statements.try_into()?).into(),
+                location,
+            )
+            .into(),
+        )?;
+        block.append_operation(llvm::r#return(Some(len), location));
+        module.body().append_operation(
+            ods::llvm::mlir_global(
+                context,
+                initializer,
+                TypeAttribute::new(i64_ty),
+                StringAttribute::new(context, COUNTERS_LEN_SYMBOL),
+                Attribute::parse(context, "#llvm.linkage<external>")
+                    .ok_or(Error::ParseAttributeError)?,
+                location,
+            )
+            .into(),
+        );
+
+        self.declared = true;
+        Ok(())
+    }
+}
+
+/// Read the statements' hit counts from a loaded module and reset them to zero.
+///
+/// Returns `None` if the module wasn't compiled with coverage instrumentation.
+pub fn take_coverage(find_symbol_ptr: impl Fn(&str) -> Option<*mut c_void>) -> Option<Coverage> {
+    let counters = find_symbol_ptr(COUNTERS_SYMBOL)?.cast::<AtomicU64>();
+    let len = find_symbol_ptr(COUNTERS_LEN_SYMBOL)?.cast::<u64>();
+
+    let hits = unsafe {
+        (0..*len as usize)
+            .map(|idx| (*counters.add(idx)).swap(0, Ordering::Relaxed))
+            .collect()
+    };
+
+    Some(Coverage::from_hits(hits))
+}
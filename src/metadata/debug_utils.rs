@@ -108,6 +108,7 @@ use std::collections::HashSet;
 enum DebugBinding {
     BreakpointMarker,
     DebugPrint,
+    Abort,
     PrintI1,
     PrintI8,
     PrintI32,
@@ -247,6 +248,39 @@ impl DebugUtils {
         Ok(())
     }
 
+    /// Aborts the process.
+    pub fn abort(
+        &mut self,
+        context: &Context,
+        module: &Module,
+        block: &Block,
+        location: Location,
+    ) -> Result<()> {
+        if self.active_map.insert(DebugBinding::Abort) {
+            module.body().append_operation(func::func(
+                context,
+                StringAttribute::new(context, "__debug__abort"),
+                TypeAttribute::new(FunctionType::new(context, &[], &[]).into()),
+                Region::new(),
+                &[(
+                    Identifier::new(context, "sym_visibility"),
+                    StringAttribute::new(context, "private").into(),
+                )],
+                Location::unknown(context),
+            ));
+        }
+
+        block.append_operation(func::call(
+            context,
+            FlatSymbolRefAttribute::new(context, "__debug__abort"),
+            &[],
+            &[],
+            location,
+        ));
+
+        Ok(())
+    }
+
     pub fn print_pointer(
         &mut self,
         context: &Context,
@@ -652,6 +686,13 @@ impl DebugUtils {
             }
         }
 
+        if self.active_map.contains(&DebugBinding::Abort) {
+            unsafe {
+                engine
+                    .register_symbol("__debug__abort", abort_impl as *const fn() -> () as *mut ());
+            }
+        }
+
         if self.active_map.contains(&DebugBinding::PrintI1) {
             unsafe {
                 engine.register_symbol(
@@ -742,6 +783,11 @@ extern "C" fn debug_print_impl(message: *const std::ffi::c_char, len: u64) {
     }
 }
 
+extern "C" fn abort_impl() {
+    println!("[DEBUG] Aborting.");
+    std::process::abort();
+}
+
 extern "C" fn print_i1_impl(value: bool) {
     println!("[DEBUG] {value}");
 }
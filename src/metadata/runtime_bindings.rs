@@ -41,6 +41,16 @@ enum RuntimeBinding {
     DictDup,
     GetGasBuiltin,
     DebugPrint,
+    ProfilerEnter,
+    ProfilerExit,
+    TraceStatement,
+    TraceBranch,
+    TraceValue,
+    BacktracePush,
+    BacktraceCall,
+    BacktracePop,
+    DebuggerVariable,
+    DebuggerStatement,
     #[cfg(feature = "with-cheatcode")]
     VtableCheatcode,
 }
@@ -65,6 +75,16 @@ impl RuntimeBinding {
             RuntimeBinding::DictDrop => "cairo_native__dict_drop",
             RuntimeBinding::DictDup => "cairo_native__dict_dup",
             RuntimeBinding::GetGasBuiltin => "cairo_native__get_costs_builtin",
+            RuntimeBinding::ProfilerEnter => "cairo_native__profiler_enter",
+            RuntimeBinding::ProfilerExit => "cairo_native__profiler_exit",
+            RuntimeBinding::TraceStatement => "cairo_native__trace_statement",
+            RuntimeBinding::TraceBranch => "cairo_native__trace_branch",
+            RuntimeBinding::TraceValue => "cairo_native__trace_value",
+            RuntimeBinding::BacktracePush => "cairo_native__backtrace_push",
+            RuntimeBinding::BacktraceCall => "cairo_native__backtrace_call",
+            RuntimeBinding::BacktracePop => "cairo_native__backtrace_pop",
+            RuntimeBinding::DebuggerVariable => "cairo_native__debugger_variable",
+            RuntimeBinding::DebuggerStatement => "cairo_native__debugger_statement",
             #[cfg(feature = "with-cheatcode")]
             RuntimeBinding::VtableCheatcode => "cairo_native__vtable_cheatcode",
         }
@@ -109,6 +129,32 @@ impl RuntimeBinding {
             RuntimeBinding::GetGasBuiltin => {
                 crate::runtime::cairo_native__get_costs_builtin as *const ()
             }
+            RuntimeBinding::ProfilerEnter => {
+                crate::runtime::cairo_native__profiler_enter as *const ()
+            }
+            RuntimeBinding::ProfilerExit => {
+                crate::runtime::cairo_native__profiler_exit as *const ()
+            }
+            RuntimeBinding::TraceStatement => {
+                crate::runtime::cairo_native__trace_statement as *const ()
+            }
+            RuntimeBinding::TraceBranch => crate::runtime::cairo_native__trace_branch as *const (),
+            RuntimeBinding::TraceValue => crate::runtime::cairo_native__trace_value as *const (),
+            RuntimeBinding::BacktracePush => {
+                crate::runtime::cairo_native__backtrace_push as *const ()
+            }
+            RuntimeBinding::BacktraceCall => {
+                crate::runtime::cairo_native__backtrace_call as *const ()
+            }
+            RuntimeBinding::BacktracePop => {
+                crate::runtime::cairo_native__backtrace_pop as *const ()
+            }
+            RuntimeBinding::DebuggerVariable => {
+                crate::runtime::cairo_native__debugger_variable as *const ()
+            }
+            RuntimeBinding::DebuggerStatement => {
+                crate::runtime::cairo_native__debugger_statement as *const ()
+            }
             #[cfg(feature = "with-cheatcode")]
             RuntimeBinding::VtableCheatcode => {
                 crate::starknet::cairo_native__vtable_cheatcode as *const ()
@@ -634,6 +680,318 @@ impl RuntimeBindingsMeta {
         ))
     }
 
+    /// Register if necessary, then invoke the `profiler_enter()` runtime function.
+    ///
+    /// Records the entry into the function with the given id, along with its gas counter.
+    pub fn profiler_enter<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        function_id: Value<'c, 'a>,
+        gas: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::ProfilerEnter,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[function_id, gas])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `profiler_exit()` runtime function.
+    ///
+    /// Records the exit from the function with the given id, along with its gas counter.
+    pub fn profiler_exit<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        function_id: Value<'c, 'a>,
+        gas: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::ProfilerExit,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[function_id, gas])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `trace_statement()` runtime function.
+    ///
+    /// Records the execution of the statement with the given index.
+    pub fn trace_statement<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        statement_idx: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::TraceStatement,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[statement_idx])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `trace_branch()` runtime function.
+    ///
+    /// Records the branch taken by the statement with the given index.
+    pub fn trace_branch<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        statement_idx: Value<'c, 'a>,
+        branch_idx: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::TraceBranch,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[statement_idx, branch_idx])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `trace_value()` runtime function.
+    ///
+    /// Records a value produced by the last traced branch. The value's `size` bytes are copied
+    /// from `value_ptr`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn trace_value<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        type_id: Value<'c, 'a>,
+        value_ptr: Value<'c, 'a>,
+        size: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function =
+            self.build_function(context, module, block, location, RuntimeBinding::TraceValue)?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[type_id, value_ptr, size])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `backtrace_push()` runtime function.
+    ///
+    /// Pushes the function with the given id, which starts at the given statement, into the shadow
+    /// call stack.
+    pub fn backtrace_push<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        function_id: Value<'c, 'a>,
+        statement_idx: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::BacktracePush,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[function_id, statement_idx])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `backtrace_call()` runtime function.
+    ///
+    /// Records the statement at which the innermost function of the shadow call stack calls
+    /// another.
+    pub fn backtrace_call<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        statement_idx: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::BacktraceCall,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[statement_idx])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `backtrace_pop()` runtime function.
+    ///
+    /// Pops the innermost function from the shadow call stack. The `panicking` flag must be an
+    /// `i8` which is non-zero if the function returns a panic.
+    pub fn backtrace_pop<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        statement_idx: Value<'c, 'a>,
+        panicking: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::BacktracePop,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[statement_idx, panicking])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `debugger_variable()` runtime function.
+    ///
+    /// Records a variable which is live at the next statement passed to the statement hook. The
+    /// value's `size` bytes are copied from `value_ptr`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn debugger_variable<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        var_id: Value<'c, 'a>,
+        type_id: Value<'c, 'a>,
+        value_ptr: Value<'c, 'a>,
+        size: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::DebuggerVariable,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[var_id, type_id, value_ptr, size])
+                .build()?,
+        ))
+    }
+
+    /// Register if necessary, then invoke the `debugger_statement()` runtime function.
+    ///
+    /// Calls the statement hook with the variables recorded since the previous statement.
+    pub fn debugger_statement<'c, 'a>(
+        &mut self,
+        context: &'c Context,
+        module: &Module,
+        block: &'a Block<'c>,
+        location: Location<'c>,
+        statement_idx: Value<'c, 'a>,
+    ) -> Result<OperationRef<'c, 'a>>
+    where
+        'c: 'a,
+    {
+        let function = self.build_function(
+            context,
+            module,
+            block,
+            location,
+            RuntimeBinding::DebuggerStatement,
+        )?;
+
+        Ok(block.append_operation(
+            OperationBuilder::new("llvm.call", location)
+                .add_operands(&[function])
+                .add_operands(&[statement_idx])
+                .build()?,
+        ))
+    }
+
     /// Register if necessary, then invoke the `vtable_cheatcode()` runtime function.
     ///
     /// Calls the cheatcode syscall with the given arguments.
@@ -689,6 +1047,16 @@ pub fn setup_runtime(find_symbol_ptr: impl Fn(&str) -> Option<*mut c_void>) {
         RuntimeBinding::DictDup,
         RuntimeBinding::GetGasBuiltin,
         RuntimeBinding::DebugPrint,
+        RuntimeBinding::ProfilerEnter,
+        RuntimeBinding::ProfilerExit,
+        RuntimeBinding::TraceStatement,
+        RuntimeBinding::TraceBranch,
+        RuntimeBinding::TraceValue,
+        RuntimeBinding::BacktracePush,
+        RuntimeBinding::BacktraceCall,
+        RuntimeBinding::BacktracePop,
+        RuntimeBinding::DebuggerVariable,
+        RuntimeBinding::DebuggerStatement,
         #[cfg(feature = "with-cheatcode")]
         RuntimeBinding::VtableCheatcode,
     ] {
@@ -1,4 +1,4 @@
-use crate::metadata::MetadataStorage;
+use crate::{debug::DebugOptions, metadata::MetadataStorage};
 use cairo_lang_sierra::{
     extensions::core::{CoreLibfunc, CoreType},
     program_registry::ProgramRegistry,
@@ -7,12 +7,13 @@ use melior::ir::Module;
 use std::{any::Any, fmt::Debug};
 
 /// A MLIR module in the context of Cairo Native.
-/// It is conformed by the MLIR module, the Sierra program registry
-/// and the program metadata.
+/// It is conformed by the MLIR module, the Sierra program registry,
+/// the program metadata and the debug options it was compiled with.
 pub struct NativeModule<'m> {
     pub(crate) module: Module<'m>,
     pub(crate) registry: ProgramRegistry<CoreType, CoreLibfunc>,
     pub(crate) metadata: MetadataStorage,
+    pub(crate) debug_options: DebugOptions,
 }
 
 impl<'m> NativeModule<'m> {
@@ -25,9 +26,16 @@ impl<'m> NativeModule<'m> {
             module,
             registry,
             metadata,
+            debug_options: DebugOptions::new(),
         }
     }
 
+    /// Set the debug options used by the later compilation stages, like the object file linking.
+    pub fn with_debug_options(mut self, debug_options: DebugOptions) -> Self {
+        self.debug_options = debug_options;
+        self
+    }
+
     /// Insert some metadata for the program execution and return a mutable reference to it.
     ///
     /// The insertion will fail, if there is already some metadata with the same type, in which case
@@ -66,6 +74,10 @@ impl<'m> NativeModule<'m> {
         &self.module
     }
 
+    pub const fn debug_options(&self) -> &DebugOptions {
+        &self.debug_options
+    }
+
     pub const fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
         &self.registry
     }
@@ -1,6 +1,15 @@
 #![allow(non_snake_case)]
 
-use crate::utils::BuiltinCosts;
+use crate::{
+    debug::{
+        backtrace::ShadowStack,
+        profiler::{ProfilerEvent, ProfilerEventKind},
+        trace::TraceEvent,
+        LiveVariable, StatementHook,
+    },
+    utils::BuiltinCosts,
+};
+use cairo_lang_sierra::program::StatementIdx;
 use cairo_lang_sierra_gas::core_libfunc_cost::{
     DICT_SQUASH_REPEATED_ACCESS_COST, DICT_SQUASH_UNIQUE_KEY_COST,
 };
@@ -16,7 +25,7 @@ use starknet_types_core::{
 };
 use std::{
     alloc::{alloc, dealloc, realloc, Layout},
-    cell::Cell,
+    cell::{Cell, RefCell},
     collections::{hash_map::Entry, HashMap},
     ffi::{c_int, c_void},
     fs::File,
@@ -25,6 +34,7 @@ use std::{
     os::fd::FromRawFd,
     ptr,
     rc::Rc,
+    time::Instant,
 };
 use std::{ops::Mul, vec::IntoIter};
 
@@ -599,6 +609,176 @@ pub extern "C" fn cairo_native__get_costs_builtin() -> *const [u64; 7] {
     BUILTIN_COSTS.with(|x| x.as_ptr()) as *const [u64; 7]
 }
 
+thread_local! {
+    /// The events recorded by the profiling instrumentation. Events are only recorded while the
+    /// executors install a buffer, which they do for the duration of each invocation.
+    pub(crate) static PROFILER_EVENTS: RefCell<Option<Vec<ProfilerEvent>>> = const {
+        RefCell::new(None)
+    };
+}
+
+fn record_profiler_event(kind: ProfilerEventKind, function_id: u64, gas: u64) {
+    let instant = Instant::now();
+    PROFILER_EVENTS.with_borrow_mut(|events| {
+        if let Some(events) = events {
+            events.push(ProfilerEvent {
+                kind,
+                function_id,
+                gas,
+                instant,
+            });
+        }
+    });
+}
+
+/// Record the entry into a function, along with its gas counter (or zero if it has none).
+pub extern "C" fn cairo_native__profiler_enter(function_id: u64, gas: u64) {
+    record_profiler_event(ProfilerEventKind::Enter, function_id, gas);
+}
+
+/// Record the exit from a function, along with its gas counter (or zero if it has none).
+pub extern "C" fn cairo_native__profiler_exit(function_id: u64, gas: u64) {
+    record_profiler_event(ProfilerEventKind::Exit, function_id, gas);
+}
+
+thread_local! {
+    /// The events recorded by the tracing instrumentation. Events are only recorded while the
+    /// executors install a buffer, which they do for the duration of each invocation.
+    pub(crate) static TRACE_EVENTS: RefCell<Option<Vec<TraceEvent>>> = const {
+        RefCell::new(None)
+    };
+}
+
+fn record_trace_event(event: TraceEvent) {
+    TRACE_EVENTS.with_borrow_mut(|events| {
+        if let Some(events) = events {
+            events.push(event);
+        }
+    });
+}
+
+/// Record the execution of a statement.
+pub extern "C" fn cairo_native__trace_statement(statement_idx: u64) {
+    record_trace_event(TraceEvent::Statement(statement_idx));
+}
+
+/// Record the branch taken by a statement.
+pub extern "C" fn cairo_native__trace_branch(statement_idx: u64, branch_idx: u64) {
+    record_trace_event(TraceEvent::Branch {
+        statement_idx,
+        branch_idx,
+    });
+}
+
+/// Record a value produced by the last recorded branch by copying `size` bytes from `value_ptr`, or
+/// record a missing value if `value_ptr` is null.
+///
+/// # Safety
+///
+/// Unless null, `value_ptr` must be valid for reads of `size` bytes.
+pub unsafe extern "C" fn cairo_native__trace_value(type_id: u64, value_ptr: *const u8, size: u64) {
+    let data = copy_value(value_ptr, size);
+    record_trace_event(TraceEvent::Value { type_id, data });
+}
+
+/// Copy the `size` bytes of a value captured by the generated code, or return `None` if
+/// `value_ptr` is null, which the generated code passes for the values which can't be copied (the
+/// ones referring to memory owned by the program).
+///
+/// The copy is stored in 16-byte words so that it's suitably aligned for any type, and therefore
+/// can be decoded in place with [`Value::from_ptr`](crate::values::Value::from_ptr).
+///
+/// # Safety
+///
+/// Unless null, `value_ptr` must be valid for reads of `size` bytes.
+unsafe fn copy_value(value_ptr: *const u8, size: u64) -> Option<Vec<u128>> {
+    (!value_ptr.is_null()).then(|| {
+        let size = size as usize;
+        let mut data = vec![0u128; size.div_ceil(size_of::<u128>())];
+        ptr::copy_nonoverlapping(value_ptr, data.as_mut_ptr().cast::<u8>(), size);
+        data
+    })
+}
+
+thread_local! {
+    /// The shadow call stack maintained by the backtrace instrumentation. It's only maintained
+    /// while the executors install one, which they do for the duration of each invocation.
+    pub(crate) static SHADOW_STACK: RefCell<Option<ShadowStack>> = const {
+        RefCell::new(None)
+    };
+}
+
+/// Push a function, which starts at the given statement, into the shadow call stack.
+pub extern "C" fn cairo_native__backtrace_push(function_id: u64, statement_idx: u64) {
+    SHADOW_STACK.with_borrow_mut(|stack| {
+        if let Some(stack) = stack {
+            stack.push(function_id, statement_idx);
+        }
+    });
+}
+
+/// Record the statement at which the innermost function of the shadow call stack calls another.
+pub extern "C" fn cairo_native__backtrace_call(statement_idx: u64) {
+    SHADOW_STACK.with_borrow_mut(|stack| {
+        if let Some(stack) = stack {
+            stack.set_statement(statement_idx);
+        }
+    });
+}
+
+/// Pop the innermost function from the shadow call stack, which returns at the given statement
+/// and may be returning a panic.
+pub extern "C" fn cairo_native__backtrace_pop(statement_idx: u64, panicking: bool) {
+    SHADOW_STACK.with_borrow_mut(|stack| {
+        if let Some(stack) = stack {
+            stack.pop(statement_idx, panicking);
+        }
+    });
+}
+
+thread_local! {
+    /// The hook called before every statement by the statement hooks instrumentation. It's
+    /// installed by [`with_statement_hook`](crate::debug::with_statement_hook).
+    pub(crate) static STATEMENT_HOOK: RefCell<Option<Box<StatementHook>>> = const {
+        RefCell::new(None)
+    };
+    /// The variables recorded for the statement about to be executed.
+    static LIVE_VARIABLES: RefCell<Vec<LiveVariable>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Record a variable which is live at the next statement by copying `size` bytes from `value_ptr`,
+/// or record its value as missing if `value_ptr` is null.
+///
+/// # Safety
+///
+/// Unless null, `value_ptr` must be valid for reads of `size` bytes.
+pub unsafe extern "C" fn cairo_native__debugger_variable(
+    var_id: u64,
+    type_id: u64,
+    value_ptr: *const u8,
+    size: u64,
+) {
+    let data = copy_value(value_ptr, size);
+    LIVE_VARIABLES.with_borrow_mut(|variables| {
+        variables.push(LiveVariable::new(var_id, type_id, data));
+    });
+}
+
+/// Call the statement hook, if any, with the variables recorded since the last statement.
+pub extern "C" fn cairo_native__debugger_statement(statement_idx: u64) {
+    let variables = LIVE_VARIABLES.take();
+
+    // The hook is taken out while it runs so that it may run other programs itself.
+    if let Some(mut hook) = STATEMENT_HOOK.take() {
+        hook(StatementIdx(statement_idx as usize), &variables);
+        STATEMENT_HOOK.with_borrow_mut(|current| {
+            if current.is_none() {
+                *current = Some(hook);
+            }
+        });
+    }
+}
+
 // Utility methods for the print runtime function
 
 /// Formats the given felts as a debug string.
@@ -3,6 +3,11 @@
 use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 
+pub mod abi;
+pub mod class_hash;
+pub mod recording;
+pub mod tracing_handler;
+
 pub type SyscallResult<T> = std::result::Result<T, Vec<Felt>>;
 
 #[repr(C)]
@@ -181,6 +186,39 @@ impl Secp256r1Point {
     }
 }
 
+/// Identifies a syscall from the [`StarknetSyscallHandler`] trait.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum SyscallKind {
+    GetBlockHash,
+    GetExecutionInfo,
+    GetExecutionInfoV2,
+    Deploy,
+    ReplaceClass,
+    LibraryCall,
+    CallContract,
+    StorageRead,
+    StorageWrite,
+    EmitEvent,
+    SendMessageToL1,
+    Keccak,
+    Secp256k1New,
+    Secp256k1Add,
+    Secp256k1Mul,
+    Secp256k1GetPointFromX,
+    Secp256k1GetXy,
+    Secp256r1New,
+    Secp256r1Add,
+    Secp256r1Mul,
+    Secp256r1GetPointFromX,
+    Secp256r1GetXy,
+    Sha256ProcessBlock,
+    GetClassHashAt,
+    #[cfg(feature = "with-cheatcode")]
+    Cheatcode,
+}
+
 pub trait StarknetSyscallHandler {
     fn get_block_hash(&mut self, block_number: u64, remaining_gas: &mut u64)
         -> SyscallResult<Felt>;
@@ -327,9 +365,10 @@ pub trait StarknetSyscallHandler {
         remaining_gas: &mut u64,
     ) -> SyscallResult<Felt>;
 
+    /// Handles the `cheatcode` testing syscall. Unknown cheatcodes return an empty result.
     #[cfg(feature = "with-cheatcode")]
     fn cheatcode(&mut self, _selector: Felt, _input: &[Felt]) -> Vec<Felt> {
-        unimplemented!();
+        Vec::new()
     }
 }
 
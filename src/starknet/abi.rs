@@ -0,0 +1,1038 @@
+//! # Contract ABI
+//!
+//! Serialization of calldata and deserialization of return data according to a contract's ABI, so
+//! that entry points can be invoked by name with typed arguments (see
+//! [`AotContractExecutor::run_by_name`](crate::executor::AotContractExecutor::run_by_name)), and
+//! decoding of emitted events into their Cairo names and fields (see
+//! [`ContractAbi::decode_event`]).
+//!
+//! Values are represented as JSON values:
+//!   - `felt252`, addresses, class hashes and integers (including `u256`): a number or a string
+//!     with the decimal or hexadecimal (`0x` prefixed) representation. When deserializing, felts
+//!     are returned as hexadecimal strings and integers wider than 64 bits as decimal strings.
+//!   - `bool`: a boolean.
+//!   - `ByteArray`: a string.
+//!   - Arrays, spans and tuples: an array.
+//!   - Structs: an object with a field per member.
+//!   - Enums: an object with a single field named after the variant, for example `{"Some": 5}`.
+//!     Unit variants may also be written as a string (`"None"`).
+//!   - `()`: `null`.
+
+use cairo_lang_starknet_classes::{
+    abi::{Contract, Enum, Event, EventFieldKind, EventKind, Input, Item, Output, Struct},
+    contract_class::ContractClass,
+    keccak::starknet_keccak,
+};
+use num_bigint::{BigInt, Sign};
+use num_traits::{Num, Signed, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use starknet_types_core::felt::Felt;
+use std::{collections::HashMap, fmt};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, AbiError>;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AbiError {
+    #[error("the contract class has no ABI")]
+    MissingAbi,
+    #[error("function `{0}` not found in the ABI")]
+    FunctionNotFound(String),
+    #[error("function `{function}` expects {expected} arguments, but {actual} were given")]
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("invalid value for `{path}` (of type `{ty}`): {value}")]
+    InvalidArgument {
+        path: String,
+        ty: String,
+        value: JsonValue,
+    },
+    #[error("type `{0}` not found in the ABI")]
+    UnknownType(String),
+    #[error("not enough data to deserialize a value of type `{0}`")]
+    MissingData(String),
+    #[error("invalid data for a value of type `{ty}`: {value}")]
+    InvalidData { ty: String, value: Felt },
+    #[error("{0} felts were left after deserializing the values")]
+    TrailingData(usize),
+    #[error("the ABI has no events")]
+    MissingEvents,
+    #[error("no event matches the selector {0}")]
+    UnknownEventSelector(Felt),
+}
+
+/// An entry point (external function, L1 handler or constructor) as described by the ABI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiFunction {
+    pub name: String,
+    pub selector: Felt,
+    pub inputs: Vec<Input>,
+    pub outputs: Vec<Output>,
+}
+
+/// An event decoded according to the ABI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedEvent {
+    /// The full name of the event's type, for example `contract::ERC20::Transfer`.
+    pub name: String,
+    /// The event enum variants leading to the event, starting from the contract's event enum. For
+    /// example `["ERC20Event", "Transfer"]`.
+    pub variants: Vec<String>,
+    /// The event's fields, in declaration order. They're (de)serialized as a JSON object.
+    #[serde(with = "ordered_fields")]
+    pub fields: Vec<(String, JsonValue)>,
+}
+
+impl DecodedEvent {
+    /// Return the value of a field.
+    pub fn field(&self, name: &str) -> Option<&JsonValue> {
+        self.fields
+            .iter()
+            .find_map(|(field, value)| (field == name).then_some(value))
+    }
+}
+
+/// (De)serialization of the fields of a [`DecodedEvent`] as a JSON object which keeps their order.
+mod ordered_fields {
+    use serde::{
+        de::{MapAccess, Visitor},
+        Deserializer, Serializer,
+    };
+    use serde_json::Value as JsonValue;
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(
+        fields: &[(String, JsonValue)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(fields.iter().map(|(field, value)| (field, value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, JsonValue)>, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Vec<(String, JsonValue)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(fields)
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+impl fmt::Display for DecodedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name.rsplit("::").next().unwrap_or(&self.name);
+        write!(f, "{name} {{")?;
+        for (i, (field, value)) in self.fields.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{sep}{field}: {value}")?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// A contract's ABI, indexed for serializing and deserializing values.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct ContractAbi {
+    functions: HashMap<String, AbiFunction>,
+    structs: HashMap<String, Struct>,
+    enums: HashMap<String, Enum>,
+    events: HashMap<String, Event>,
+    /// The contract's event enum, which isn't nested in any other event.
+    root_event: Option<String>,
+}
+
+impl ContractAbi {
+    pub fn new(contract: &Contract) -> Self {
+        let mut abi = Self::default();
+        for item in contract.clone() {
+            abi.insert_item(item);
+        }
+
+        let mut root_events = abi
+            .events
+            .keys()
+            .filter(|name| {
+                !abi.events.values().any(|event| match &event.kind {
+                    EventKind::Struct { members: fields }
+                    | EventKind::Enum { variants: fields } => {
+                        fields.iter().any(|field| &&field.ty == name)
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // Prefer the contract's `Event` enum if there are unrelated events, and break the ties by
+        // name so that the choice doesn't depend on the map's order.
+        root_events.sort_by_key(|name| (!name.ends_with("::Event"), *name));
+        abi.root_event = root_events.first().map(|name| name.to_string());
+
+        abi
+    }
+
+    pub fn from_contract_class(contract_class: &ContractClass) -> Result<Self> {
+        contract_class
+            .abi
+            .as_ref()
+            .map(Self::new)
+            .ok_or(AbiError::MissingAbi)
+    }
+
+    fn insert_item(&mut self, item: Item) {
+        let (name, inputs, outputs) = match item {
+            Item::Function(x) => (x.name, x.inputs, x.outputs),
+            Item::L1Handler(x) => (x.name, x.inputs, x.outputs),
+            Item::Constructor(x) => (x.name, x.inputs, Vec::new()),
+            Item::Interface(x) => {
+                x.items.into_iter().for_each(|x| self.insert_item(x));
+                return;
+            }
+            Item::Struct(x) => {
+                self.structs.insert(x.name.clone(), x);
+                return;
+            }
+            Item::Enum(x) => {
+                self.enums.insert(x.name.clone(), x);
+                return;
+            }
+            Item::Event(x) => {
+                self.events.insert(x.name.clone(), x);
+                return;
+            }
+            Item::Impl(_) => return,
+        };
+
+        let selector = Felt::from(&starknet_keccak(name.as_bytes()));
+        self.functions.insert(
+            name.clone(),
+            AbiFunction {
+                name,
+                selector,
+                inputs,
+                outputs,
+            },
+        );
+    }
+
+    pub fn function(&self, name: &str) -> Result<&AbiFunction> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| AbiError::FunctionNotFound(name.to_string()))
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &AbiFunction> {
+        self.functions.values()
+    }
+
+    /// Serialize the arguments of a function into its calldata.
+    pub fn serialize_calldata(&self, function: &str, args: &[JsonValue]) -> Result<Vec<Felt>> {
+        let function = self.function(function)?;
+        if function.inputs.len() != args.len() {
+            return Err(AbiError::ArgumentCount {
+                function: function.name.clone(),
+                expected: function.inputs.len(),
+                actual: args.len(),
+            });
+        }
+
+        let mut calldata = Vec::new();
+        for (input, arg) in function.inputs.iter().zip(args) {
+            self.serialize_into(&input.ty, arg, &input.name, &mut calldata)?;
+        }
+
+        Ok(calldata)
+    }
+
+    /// Deserialize the return data of a function into its return values.
+    pub fn deserialize_return_values(
+        &self,
+        function: &str,
+        data: &[Felt],
+    ) -> Result<Vec<JsonValue>> {
+        let function = self.function(function)?;
+
+        let mut data = data;
+        let values = function
+            .outputs
+            .iter()
+            .map(|output| self.deserialize_value(&output.ty, &mut data))
+            .collect::<Result<Vec<_>>>()?;
+
+        match data.len() {
+            0 => Ok(values),
+            len => Err(AbiError::TrailingData(len)),
+        }
+    }
+
+    /// Serialize a single value of the given type.
+    pub fn serialize_value(&self, ty: &str, value: &JsonValue) -> Result<Vec<Felt>> {
+        let mut data = Vec::new();
+        self.serialize_into(ty, value, "value", &mut data)?;
+        Ok(data)
+    }
+
+    fn serialize_into(
+        &self,
+        ty: &str,
+        value: &JsonValue,
+        path: &str,
+        data: &mut Vec<Felt>,
+    ) -> Result<()> {
+        let invalid = || AbiError::InvalidArgument {
+            path: path.to_string(),
+            ty: ty.to_string(),
+            value: value.clone(),
+        };
+
+        if ty == "()" {
+            return match value {
+                JsonValue::Null => Ok(()),
+                _ => Err(invalid()),
+            };
+        }
+        if let Some(tys) = tuple_members(ty) {
+            let values = value.as_array().ok_or_else(invalid)?;
+            if values.len() != tys.len() {
+                return Err(invalid());
+            }
+            for (i, (ty, value)) in tys.into_iter().zip(values).enumerate() {
+                self.serialize_into(ty, value, &format!("{path}.{i}"), data)?;
+            }
+            return Ok(());
+        }
+        if let Some(ty) =
+            generic_arg(ty, "core::array::Array").or_else(|| generic_arg(ty, "core::array::Span"))
+        {
+            let values = value.as_array().ok_or_else(invalid)?;
+            data.push(values.len().into());
+            for (i, value) in values.iter().enumerate() {
+                self.serialize_into(ty, value, &format!("{path}[{i}]"), data)?;
+            }
+            return Ok(());
+        }
+        if let Some(ty) = generic_arg(ty, "core::zeroable::NonZero") {
+            return self.serialize_into(ty, value, path, data);
+        }
+
+        match ty {
+            "core::bool" => data.push(value.as_bool().ok_or_else(invalid)?.into()),
+            "core::integer::u256" => {
+                let value = parse_integer(value).ok_or_else(invalid)?;
+                if value.is_negative() || value.bits() > 256 {
+                    return Err(invalid());
+                }
+                let (_, digits) = value.to_u64_digits();
+                let digit = |i: usize| u128::from(digits.get(i).copied().unwrap_or_default());
+                data.push((digit(0) | (digit(1) << 64)).into());
+                data.push((digit(2) | (digit(3) << 64)).into());
+            }
+            "core::byte_array::ByteArray" => {
+                serialize_byte_array(value.as_str().ok_or_else(invalid)?, data)
+            }
+            _ if is_felt_like(ty) => {
+                let value = parse_integer(value).ok_or_else(invalid)?;
+                if value.is_negative() || value > Felt::MAX.to_bigint() {
+                    return Err(invalid());
+                }
+                data.push(value.into());
+            }
+            _ if integer_range(ty).is_some() => {
+                let (min, max) = integer_range(ty).unwrap_or_default();
+                let value = parse_integer(value).ok_or_else(invalid)?;
+                if value < min || value > max {
+                    return Err(invalid());
+                }
+                data.push(value.into());
+            }
+            _ => {
+                if let Some(item) = self.structs.get(ty) {
+                    let fields = value.as_object().ok_or_else(invalid)?;
+                    if fields.len() != item.members.len() {
+                        return Err(invalid());
+                    }
+                    for member in &item.members {
+                        let value = fields.get(&member.name).ok_or_else(invalid)?;
+                        self.serialize_into(
+                            &member.ty,
+                            value,
+                            &format!("{path}.{}", member.name),
+                            data,
+                        )?;
+                    }
+                } else if let Some(item) = self.enums.get(ty) {
+                    let (variant, value) = match value {
+                        JsonValue::String(variant) => (variant, &JsonValue::Null),
+                        JsonValue::Object(fields) if fields.len() == 1 => {
+                            fields.iter().next().ok_or_else(invalid)?
+                        }
+                        _ => return Err(invalid()),
+                    };
+                    let (index, variant) = item
+                        .variants
+                        .iter()
+                        .enumerate()
+                        .find(|(_, x)| &x.name == variant)
+                        .ok_or_else(invalid)?;
+
+                    data.push(index.into());
+                    self.serialize_into(
+                        &variant.ty,
+                        value,
+                        &format!("{path}.{}", variant.name),
+                        data,
+                    )?;
+                } else {
+                    return Err(AbiError::UnknownType(ty.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize a single value of the given type, advancing `data` past it.
+    pub fn deserialize_value(&self, ty: &str, data: &mut &[Felt]) -> Result<JsonValue> {
+        let mut next = || {
+            let (value, rest) = data
+                .split_first()
+                .ok_or_else(|| AbiError::MissingData(ty.to_string()))?;
+            *data = rest;
+            Ok(*value)
+        };
+
+        if ty == "()" {
+            return Ok(JsonValue::Null);
+        }
+        if let Some(tys) = tuple_members(ty) {
+            return tys
+                .into_iter()
+                .map(|ty| self.deserialize_value(ty, data))
+                .collect::<Result<_>>();
+        }
+        if let Some(elem_ty) =
+            generic_arg(ty, "core::array::Array").or_else(|| generic_arg(ty, "core::array::Span"))
+        {
+            let len = next()?;
+            let len = len.to_usize().ok_or(AbiError::InvalidData {
+                ty: ty.to_string(),
+                value: len,
+            })?;
+            return (0..len)
+                .map(|_| self.deserialize_value(elem_ty, data))
+                .collect::<Result<_>>();
+        }
+        if let Some(ty) = generic_arg(ty, "core::zeroable::NonZero") {
+            return self.deserialize_value(ty, data);
+        }
+
+        let invalid = |value: Felt| AbiError::InvalidData {
+            ty: ty.to_string(),
+            value,
+        };
+
+        Ok(match ty {
+            "core::bool" => match next()? {
+                x if x == Felt::ZERO => JsonValue::Bool(false),
+                x if x == Felt::ONE => JsonValue::Bool(true),
+                x => return Err(invalid(x)),
+            },
+            "core::integer::u256" => {
+                let (lo, hi) = (next()?, next()?);
+                if lo.bits() > 128 {
+                    return Err(invalid(lo));
+                }
+                if hi.bits() > 128 {
+                    return Err(invalid(hi));
+                }
+                JsonValue::String(((hi.to_biguint() << 128u32) + lo.to_biguint()).to_string())
+            }
+            "core::byte_array::ByteArray" => JsonValue::String(self.deserialize_byte_array(data)?),
+            _ if is_felt_like(ty) => JsonValue::String(next()?.to_hex_string()),
+            _ if integer_range(ty).is_some() => {
+                let (min, max) = integer_range(ty).unwrap_or_default();
+                let raw = next()?;
+                let value = felt_to_signed(raw);
+                if value < min || value > max {
+                    return Err(invalid(raw));
+                }
+                match value.to_i64() {
+                    Some(value) => value.into(),
+                    None => JsonValue::String(value.to_string()),
+                }
+            }
+            _ => {
+                if let Some(item) = self.structs.get(ty) {
+                    JsonValue::Object(
+                        item.members
+                            .iter()
+                            .map(|member| {
+                                Ok((
+                                    member.name.clone(),
+                                    self.deserialize_value(&member.ty, data)?,
+                                ))
+                            })
+                            .collect::<Result<_>>()?,
+                    )
+                } else if let Some(item) = self.enums.get(ty) {
+                    let tag = next()?;
+                    let variant = tag
+                        .to_usize()
+                        .and_then(|x| item.variants.get(x))
+                        .ok_or_else(|| invalid(tag))?;
+
+                    let mut value = serde_json::Map::new();
+                    value.insert(
+                        variant.name.clone(),
+                        self.deserialize_value(&variant.ty, data)?,
+                    );
+                    JsonValue::Object(value)
+                } else {
+                    return Err(AbiError::UnknownType(ty.to_string()));
+                }
+            }
+        })
+    }
+
+    /// Decode an emitted event as one of the contract's events.
+    pub fn decode_event(&self, keys: &[Felt], data: &[Felt]) -> Result<DecodedEvent> {
+        let root_event = self.root_event.as_deref().ok_or(AbiError::MissingEvents)?;
+        self.decode_event_as(root_event, keys, data)
+    }
+
+    /// Decode an emitted event as the given event type.
+    pub fn decode_event_as(&self, ty: &str, keys: &[Felt], data: &[Felt]) -> Result<DecodedEvent> {
+        let (mut keys, mut data) = (keys, data);
+        let mut event = DecodedEvent {
+            name: ty.to_string(),
+            variants: Vec::new(),
+            fields: Vec::new(),
+        };
+        self.decode_event_into(ty, &mut keys, &mut data, &mut event)?;
+
+        match keys.len() + data.len() {
+            0 => Ok(event),
+            len => Err(AbiError::TrailingData(len)),
+        }
+    }
+
+    fn decode_event_into(
+        &self,
+        ty: &str,
+        keys: &mut &[Felt],
+        data: &mut &[Felt],
+        event: &mut DecodedEvent,
+    ) -> Result<()> {
+        let item = self
+            .events
+            .get(ty)
+            .ok_or_else(|| AbiError::UnknownType(ty.to_string()))?;
+
+        match &item.kind {
+            EventKind::Struct { members } => {
+                event.name = ty.to_string();
+                for member in members {
+                    let value = match member.kind {
+                        EventFieldKind::KeySerde => self.deserialize_value(&member.ty, keys)?,
+                        EventFieldKind::DataSerde => self.deserialize_value(&member.ty, data)?,
+                        EventFieldKind::Nested | EventFieldKind::Flat => {
+                            let mut nested = DecodedEvent {
+                                name: member.ty.clone(),
+                                variants: Vec::new(),
+                                fields: Vec::new(),
+                            };
+                            self.decode_event_into(&member.ty, keys, data, &mut nested)?;
+                            JsonValue::Object(nested.fields.into_iter().collect())
+                        }
+                    };
+                    event.fields.push((member.name.clone(), value));
+                }
+
+                Ok(())
+            }
+            EventKind::Enum { variants } => {
+                let selector = *keys
+                    .first()
+                    .ok_or_else(|| AbiError::MissingData(ty.to_string()))?;
+
+                // Nested variants are prefixed by their selector.
+                if let Some(variant) = variants.iter().find(|variant| {
+                    variant.kind == EventFieldKind::Nested
+                        && Felt::from(&starknet_keccak(variant.name.as_bytes())) == selector
+                }) {
+                    *keys = &keys[1..];
+                    event.variants.push(variant.name.clone());
+                    return self.decode_event_into(&variant.ty, keys, data, event);
+                }
+
+                // Flat variants aren't, so every one of them has to be tried.
+                for variant in variants
+                    .iter()
+                    .filter(|variant| variant.kind == EventFieldKind::Flat)
+                {
+                    let (mut flat_keys, mut flat_data) = (*keys, *data);
+                    let mut flat_event = event.clone();
+                    flat_event.variants.push(variant.name.clone());
+                    if self
+                        .decode_event_into(
+                            &variant.ty,
+                            &mut flat_keys,
+                            &mut flat_data,
+                            &mut flat_event,
+                        )
+                        .is_ok()
+                    {
+                        (*keys, *data, *event) = (flat_keys, flat_data, flat_event);
+                        return Ok(());
+                    }
+                }
+
+                Err(AbiError::UnknownEventSelector(selector))
+            }
+        }
+    }
+
+    fn deserialize_byte_array(&self, data: &mut &[Felt]) -> Result<String> {
+        const TY: &str = "core::byte_array::ByteArray";
+
+        let invalid = |value: Felt| AbiError::InvalidData {
+            ty: TY.to_string(),
+            value,
+        };
+        let mut next = || {
+            let (value, rest) = data
+                .split_first()
+                .ok_or_else(|| AbiError::MissingData(TY.to_string()))?;
+            *data = rest;
+            Ok(*value)
+        };
+
+        let len = next()?;
+        let len = len.to_usize().ok_or_else(|| invalid(len))?;
+
+        let mut bytes = Vec::new();
+        for _ in 0..len {
+            bytes.extend_from_slice(&next()?.to_bytes_be()[1..]);
+        }
+
+        let pending_word = next()?;
+        let pending_word_len = next()?;
+        let pending_word_len = pending_word_len
+            .to_usize()
+            .filter(|x| *x < 31)
+            .ok_or_else(|| invalid(pending_word_len))?;
+        bytes.extend_from_slice(&pending_word.to_bytes_be()[32 - pending_word_len..]);
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Serialize a string as a `ByteArray`.
+fn serialize_byte_array(value: &str, data: &mut Vec<Felt>) {
+    let chunks = value.as_bytes().chunks(31).collect::<Vec<_>>();
+    let (full_words, pending_word) = match chunks.split_last() {
+        Some((last, rest)) if last.len() < 31 => (rest, *last),
+        _ => (chunks.as_slice(), [].as_slice()),
+    };
+
+    data.push(full_words.len().into());
+    data.extend(full_words.iter().map(|x| Felt::from_bytes_be_slice(x)));
+    data.push(Felt::from_bytes_be_slice(pending_word));
+    data.push(pending_word.len().into());
+}
+
+/// Whether the type is serialized as a single felt with no range restrictions.
+fn is_felt_like(ty: &str) -> bool {
+    matches!(
+        ty,
+        "core::felt252"
+            | "core::starknet::contract_address::ContractAddress"
+            | "core::starknet::class_hash::ClassHash"
+            | "core::starknet::storage_access::StorageAddress"
+            | "core::starknet::eth_address::EthAddress"
+            | "core::bytes_31::bytes31"
+    )
+}
+
+/// Return the inclusive range of an integer type (other than `u256`).
+fn integer_range(ty: &str) -> Option<(BigInt, BigInt)> {
+    let ty = ty.strip_prefix("core::integer::")?;
+    let (signed, bits) = match ty.split_at_checked(1)? {
+        ("u", bits) => (false, bits.parse::<u32>().ok()?),
+        ("i", bits) => (true, bits.parse::<u32>().ok()?),
+        _ => return None,
+    };
+    if !matches!(bits, 8 | 16 | 32 | 64 | 128) {
+        return None;
+    }
+
+    Some(match signed {
+        false => (BigInt::ZERO, (BigInt::from(1) << bits) - 1),
+        true => (
+            -(BigInt::from(1) << (bits - 1)),
+            (BigInt::from(1) << (bits - 1)) - 1,
+        ),
+    })
+}
+
+/// Parse an integer from a JSON number or a decimal or hexadecimal string.
+fn parse_integer(value: &JsonValue) -> Option<BigInt> {
+    match value {
+        JsonValue::Number(x) => x
+            .as_u64()
+            .map(BigInt::from)
+            .or_else(|| x.as_i64().map(BigInt::from)),
+        JsonValue::String(x) => {
+            let (sign, x) = match x.strip_prefix('-') {
+                Some(x) => (Sign::Minus, x),
+                None => (Sign::Plus, x.as_str()),
+            };
+            let value = match x.strip_prefix("0x") {
+                Some(x) => BigInt::from_str_radix(x, 16),
+                None => BigInt::from_str_radix(x, 10),
+            }
+            .ok()?;
+
+            Some(match sign {
+                Sign::Minus => -value,
+                _ => value,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Interpret a felt as a signed integer.
+fn felt_to_signed(value: Felt) -> BigInt {
+    let value = value.to_bigint();
+    let prime = Felt::MAX.to_bigint() + 1;
+    if value > &prime / 2 {
+        value - prime
+    } else {
+        value
+    }
+}
+
+/// Return the generic argument of a type if its name (without generic arguments) matches.
+fn generic_arg<'a>(ty: &'a str, name: &str) -> Option<&'a str> {
+    ty.strip_prefix(name)?
+        .strip_prefix("::<")?
+        .strip_suffix('>')
+}
+
+/// Return the members of a tuple type, or `None` if it's not a tuple.
+fn tuple_members(ty: &str) -> Option<Vec<&str>> {
+    let inner = ty.strip_prefix('(')?.strip_suffix(')')?;
+
+    let mut members = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                members.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !inner[start..].trim().is_empty() {
+        members.push(inner[start..].trim());
+    }
+
+    Some(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn abi() -> ContractAbi {
+        ContractAbi::new(
+            &serde_json::from_value(json!([
+                {
+                    "type": "struct",
+                    "name": "core::integer::u256",
+                    "members": [
+                        { "name": "low", "type": "core::integer::u128" },
+                        { "name": "high", "type": "core::integer::u128" }
+                    ]
+                },
+                {
+                    "type": "struct",
+                    "name": "contract::Point",
+                    "members": [
+                        { "name": "x", "type": "core::integer::i32" },
+                        { "name": "y", "type": "core::integer::i32" }
+                    ]
+                },
+                {
+                    "type": "enum",
+                    "name": "core::option::Option::<core::felt252>",
+                    "variants": [
+                        { "name": "Some", "type": "core::felt252" },
+                        { "name": "None", "type": "()" }
+                    ]
+                },
+                {
+                    "type": "interface",
+                    "name": "contract::IContract",
+                    "items": [
+                        {
+                            "type": "function",
+                            "name": "call",
+                            "inputs": [
+                                { "name": "amount", "type": "core::integer::u256" },
+                                { "name": "points", "type": "core::array::Span::<contract::Point>" },
+                                { "name": "name", "type": "core::byte_array::ByteArray" },
+                                { "name": "opt", "type": "core::option::Option::<core::felt252>" }
+                            ],
+                            "outputs": [
+                                { "type": "(core::bool, core::integer::u8)" }
+                            ],
+                            "state_mutability": "external"
+                        }
+                    ]
+                },
+                {
+                    "type": "event",
+                    "name": "contract::Component::Transfer",
+                    "kind": "struct",
+                    "members": [
+                        { "name": "from", "type": "core::felt252", "kind": "key" },
+                        { "name": "amount", "type": "core::integer::u256", "kind": "data" }
+                    ]
+                },
+                {
+                    "type": "event",
+                    "name": "contract::Component::Event",
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Transfer", "type": "contract::Component::Transfer", "kind": "nested" }
+                    ]
+                },
+                {
+                    "type": "event",
+                    "name": "contract::Ping",
+                    "kind": "struct",
+                    "members": [
+                        { "name": "count", "type": "core::integer::u8", "kind": "data" }
+                    ]
+                },
+                {
+                    "type": "event",
+                    "name": "contract::Event",
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Ping", "type": "contract::Ping", "kind": "nested" },
+                        { "name": "ComponentEvent", "type": "contract::Component::Event", "kind": "flat" }
+                    ]
+                }
+            ]))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_serialize_calldata() {
+        let calldata = abi()
+            .serialize_calldata(
+                "call",
+                &[
+                    json!("0x100000000000000000000000000000002"),
+                    json!([{ "x": -1, "y": 2 }]),
+                    json!("hello"),
+                    json!({ "Some": 7 }),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(
+            calldata,
+            [
+                Felt::from(2),
+                Felt::from(1),
+                Felt::from(1),
+                Felt::from(-1),
+                Felt::from(2),
+                Felt::from(0),
+                Felt::from_bytes_be_slice(b"hello"),
+                Felt::from(5),
+                Felt::from(0),
+                Felt::from(7),
+            ]
+        );
+        assert_eq!(
+            abi().function("call").unwrap().selector,
+            Felt::from(&starknet_keccak(b"call"))
+        );
+    }
+
+    #[test]
+    fn test_serialize_calldata_errors() {
+        assert_eq!(
+            abi().serialize_calldata("call", &[json!(1)]),
+            Err(AbiError::ArgumentCount {
+                function: "call".to_string(),
+                expected: 4,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            abi().serialize_calldata(
+                "call",
+                &[
+                    json!(1),
+                    json!([{ "x": 1, "y": true }]),
+                    json!(""),
+                    json!("None")
+                ],
+            ),
+            Err(AbiError::InvalidArgument {
+                path: "points[0].y".to_string(),
+                ty: "core::integer::i32".to_string(),
+                value: json!(true),
+            })
+        );
+        assert_eq!(
+            abi().serialize_calldata("missing", &[]),
+            Err(AbiError::FunctionNotFound("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_deserialize_return_values() {
+        assert_eq!(
+            abi()
+                .deserialize_return_values("call", &[Felt::ONE, Felt::from(255)])
+                .unwrap(),
+            [json!([true, 255])]
+        );
+        assert_eq!(
+            abi().deserialize_return_values("call", &[Felt::ONE, Felt::from(256)]),
+            Err(AbiError::InvalidData {
+                ty: "core::integer::u8".to_string(),
+                value: Felt::from(256),
+            })
+        );
+        assert_eq!(
+            abi().deserialize_return_values("call", &[Felt::ONE]),
+            Err(AbiError::MissingData("core::integer::u8".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_byte_array_roundtrip() {
+        let value = json!("a string which is longer than thirty one bytes");
+        let data = abi()
+            .serialize_value("core::byte_array::ByteArray", &value)
+            .unwrap();
+
+        let mut slice = data.as_slice();
+        assert_eq!(
+            abi()
+                .deserialize_value("core::byte_array::ByteArray", &mut slice)
+                .unwrap(),
+            value
+        );
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let selector = |name: &str| Felt::from(&starknet_keccak(name.as_bytes()));
+
+        let event = abi()
+            .decode_event(&[selector("Ping")], &[Felt::from(3)])
+            .unwrap();
+        assert_eq!(event.name, "contract::Ping");
+        assert_eq!(event.variants, ["Ping"]);
+        assert_eq!(event.to_string(), "Ping { count: 3 }");
+
+        let event = abi()
+            .decode_event(
+                &[selector("Transfer"), Felt::from(5)],
+                &[Felt::from(10), Felt::ZERO],
+            )
+            .unwrap();
+        assert_eq!(event.name, "contract::Component::Transfer");
+        assert_eq!(event.variants, ["ComponentEvent", "Transfer"]);
+        // The fields keep their declaration order, also when serialized.
+        assert_eq!(
+            event.fields,
+            [
+                ("from".to_string(), json!("0x5")),
+                ("amount".to_string(), json!("10"))
+            ]
+        );
+        assert_eq!(event.field("amount"), Some(&json!("10")));
+        let serialized = serde_json::to_string(&event).unwrap();
+        assert!(serialized.contains(r#""fields":{"from":"0x5","amount":"10"}"#));
+        assert_eq!(
+            serde_json::from_str::<DecodedEvent>(&serialized).unwrap(),
+            event
+        );
+
+        assert_eq!(
+            abi().decode_event(&[Felt::from(7)], &[]),
+            Err(AbiError::UnknownEventSelector(Felt::from(7)))
+        );
+        assert_eq!(
+            abi().decode_event(&[selector("Ping")], &[Felt::from(3), Felt::ONE]),
+            Err(AbiError::TrailingData(1))
+        );
+    }
+
+    #[test]
+    fn test_root_event() {
+        let event = |name: &str| {
+            json!({
+                "type": "event",
+                "name": name,
+                "kind": "struct",
+                "members": []
+            })
+        };
+        let abi = |events: Vec<JsonValue>| {
+            ContractAbi::new(&serde_json::from_value(JsonValue::Array(events)).unwrap())
+        };
+
+        // The contract's event enum is preferred, then the first one by name.
+        assert_eq!(
+            abi(vec![
+                event("b::Event"),
+                event("a::Other"),
+                event("a::Event")
+            ])
+            .root_event
+            .as_deref(),
+            Some("a::Event")
+        );
+        assert_eq!(
+            abi(vec![event("b::Other"), event("a::Other")])
+                .root_event
+                .as_deref(),
+            Some("a::Other")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! # Class hashes
+//!
+//! Computation of the hashes identifying a Sierra [`ContractClass`], as defined by the Starknet
+//! specification:
+//!
+//!   - The class hash, which is what `deploy`, `library_call` and `get_class_hash_at` work with.
+//!   - The compiled class hash, which is the hash of the class compiled to CASM and is required
+//!     when declaring a class.
+//!
+//! Also, [`native_artifact_hash`] identifies the shared library compiled from a class by
+//! cairo-native, which makes it suitable as a cache key for compiled contracts.
+
+use crate::{error::Result, OptLevel};
+use cairo_lang_starknet_classes::{
+    casm_contract_class::CasmContractClass,
+    contract_class::{ContractClass, ContractEntryPoint},
+    keccak::starknet_keccak,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use starknet_types_core::{
+    felt::Felt,
+    hash::{Poseidon, StarkHash},
+};
+
+/// Compute the class hash of a contract class given its ABI exactly as it was declared.
+///
+/// The ABI is hashed as is, since re-serializing a parsed ABI doesn't necessarily yield the same
+/// string and therefore the same hash. Use [`parse_contract_class`] to obtain it from a class file.
+pub fn compute_class_hash(contract_class: &ContractClass, abi: &str) -> Felt {
+    let sierra_program = contract_class
+        .sierra_program
+        .iter()
+        .map(|x| Felt::from(&x.value))
+        .collect::<Vec<_>>();
+
+    let entry_points = &contract_class.entry_points_by_type;
+    Poseidon::hash_array(&[
+        Felt::from_bytes_be_slice(
+            format!("CONTRACT_CLASS_V{}", contract_class.contract_class_version).as_bytes(),
+        ),
+        entry_points_hash(&entry_points.external),
+        entry_points_hash(&entry_points.l1_handler),
+        entry_points_hash(&entry_points.constructor),
+        Felt::from(&starknet_keccak(abi.as_bytes())),
+        Poseidon::hash_array(&sierra_program),
+    ])
+}
+
+/// Compute the class hash of a contract class file (see [`parse_contract_class`]).
+pub fn compute_class_hash_from_json(class_json: &str) -> Result<Felt> {
+    let (contract_class, abi) = parse_contract_class(class_json)?;
+    Ok(compute_class_hash(&contract_class, &abi))
+}
+
+/// Parse a contract class file and return the class along with its declared ABI.
+///
+/// Both the compiler's output, where the ABI is a JSON array, and the declared form returned by the
+/// RPC, where it's a string, are supported. In the former, the declared ABI is the array in compact
+/// form, as sent by the tools declaring the class. In the latter, the class' parsed ABI is `None`.
+pub fn parse_contract_class(class_json: &str) -> Result<(ContractClass, String)> {
+    #[derive(Deserialize)]
+    struct DeclaredAbi<'a> {
+        #[serde(borrow, default)]
+        abi: Option<&'a RawValue>,
+    }
+
+    let abi = serde_json::from_str::<DeclaredAbi>(class_json)?.abi;
+    Ok(match abi.map(RawValue::get) {
+        Some(abi) if abi.starts_with('"') => {
+            let mut contract_class = serde_json::from_str::<serde_json::Value>(class_json)?;
+            if let Some(contract_class) = contract_class.as_object_mut() {
+                contract_class.remove("abi");
+            }
+            (
+                serde_json::from_value(contract_class)?,
+                serde_json::from_str(abi)?,
+            )
+        }
+        abi => (
+            serde_json::from_str(class_json)?,
+            abi.map(compact_json).unwrap_or_default(),
+        ),
+    })
+}
+
+/// Remove the whitespace outside of the strings of a JSON document, keeping everything else (the
+/// order of the keys included) untouched.
+fn compact_json(json: &str) -> String {
+    let mut compact = String::with_capacity(json.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for c in json.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c.is_ascii_whitespace() {
+            continue;
+        }
+        compact.push(c);
+    }
+    compact
+}
+
+/// Compute the compiled class hash of a contract class, compiling it to CASM first.
+pub fn compute_compiled_class_hash(contract_class: &ContractClass) -> Result<Felt> {
+    let casm_class =
+        CasmContractClass::from_contract_class(contract_class.clone(), false, usize::MAX)?;
+    Ok(casm_class.compiled_class_hash())
+}
+
+/// Compute a hash identifying the native artifact compiled from a class.
+///
+/// The hash depends on the class hash, the optimization level, the version of cairo-native and the
+/// target the artifact is built for, so that artifacts compiled differently never share a hash.
+pub fn native_artifact_hash(class_hash: Felt, opt_level: OptLevel) -> Felt {
+    Poseidon::hash_array(&[
+        Felt::from_bytes_be_slice(b"NATIVE_ARTIFACT_V0"),
+        class_hash,
+        Felt::from_bytes_be_slice(env!("CARGO_PKG_VERSION").as_bytes()),
+        Felt::from(usize::from(opt_level)),
+        Felt::from_bytes_be_slice(std::env::consts::ARCH.as_bytes()),
+        Felt::from_bytes_be_slice(std::env::consts::OS.as_bytes()),
+    ])
+}
+
+fn entry_points_hash(entry_points: &[ContractEntryPoint]) -> Felt {
+    Poseidon::hash_array(
+        &entry_points
+            .iter()
+            .flat_map(|x| [Felt::from(&x.selector), Felt::from(x.function_idx)])
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_starknet_contract;
+
+    fn contract_class() -> ContractClass {
+        let (_, contract_class) = load_starknet_contract! {
+            #[starknet::contract]
+            mod contract {
+                #[storage]
+                struct Storage {}
+
+                #[external(v0)]
+                fn get(self: @ContractState, x: felt252) -> felt252 {
+                    x
+                }
+            }
+        };
+        contract_class
+    }
+
+    #[test]
+    fn test_class_hash() {
+        let contract_class = contract_class();
+        let abi = serde_json::to_string(&contract_class.abi).unwrap();
+        let class_hash = compute_class_hash(&contract_class, &abi);
+
+        // The ABI is hashed as is, so any difference changes the hash.
+        assert_ne!(
+            compute_class_hash(&contract_class, &format!("{abi} ")),
+            class_hash
+        );
+        assert_ne!(
+            compute_compiled_class_hash(&contract_class).unwrap(),
+            class_hash
+        );
+
+        // Both the compiler's output and the declared form yield the same hash.
+        assert_eq!(
+            compute_class_hash_from_json(&serde_json::to_string_pretty(&contract_class).unwrap())
+                .unwrap(),
+            class_hash
+        );
+        let mut class_json = serde_json::to_value(&contract_class).unwrap();
+        class_json["abi"] = abi.into();
+        assert_eq!(
+            compute_class_hash_from_json(&class_json.to_string()).unwrap(),
+            class_hash
+        );
+    }
+
+    #[test]
+    fn test_class_hash_components() {
+        // A minimal class in the declared form, with its hash computed as in the specification.
+        let class_json = r#"{
+            "sierra_program": ["0x1", "0x2", "0x3"],
+            "contract_class_version": "0.1.0",
+            "entry_points_by_type": {
+                "EXTERNAL": [{ "selector": "0x10", "function_idx": 0 }],
+                "L1_HANDLER": [],
+                "CONSTRUCTOR": [{ "selector": "0x20", "function_idx": 1 }]
+            },
+            "abi": "[{\"type\": \"function\", \"name\": \"foo\"}]"
+        }"#;
+
+        let (contract_class, abi) = parse_contract_class(class_json).unwrap();
+        assert_eq!(contract_class.abi, None);
+        assert_eq!(abi, r#"[{"type": "function", "name": "foo"}]"#);
+        assert_eq!(
+            compute_class_hash_from_json(class_json).unwrap(),
+            Poseidon::hash_array(&[
+                Felt::from_bytes_be_slice(b"CONTRACT_CLASS_V0.1.0"),
+                Poseidon::hash_array(&[Felt::from(0x10), Felt::ZERO]),
+                Poseidon::hash_array(&[]),
+                Poseidon::hash_array(&[Felt::from(0x20), Felt::ONE]),
+                Felt::from(&starknet_keccak(
+                    br#"[{"type": "function", "name": "foo"}]"#
+                )),
+                Poseidon::hash_array(&[Felt::ONE, Felt::TWO, Felt::THREE]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compact_json() {
+        assert_eq!(
+            compact_json("[ {\"b\": \"x y\",\n \"a\": \"\\\" z\"} ]"),
+            r#"[{"b":"x y","a":"\" z"}]"#
+        );
+    }
+
+    #[test]
+    fn test_native_artifact_hash() {
+        let class_hash = Felt::from(1234);
+        assert_eq!(
+            native_artifact_hash(class_hash, OptLevel::Default),
+            native_artifact_hash(class_hash, OptLevel::Default)
+        );
+        assert_ne!(
+            native_artifact_hash(class_hash, OptLevel::Default),
+            native_artifact_hash(class_hash, OptLevel::None)
+        );
+        assert_ne!(
+            native_artifact_hash(class_hash, OptLevel::Default),
+            native_artifact_hash(Felt::from(1235), OptLevel::Default)
+        );
+    }
+}
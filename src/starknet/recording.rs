@@ -0,0 +1,1446 @@
+//! # Syscall recording and replay
+//!
+//! The [`RecordingSyscallHandler`] wraps another syscall handler and logs every syscall that goes
+//! through it (arguments, result and the gas before and after the call) into a [`SyscallLog`],
+//! which can be saved to disk.
+//!
+//! The [`ReplaySyscallHandler`] takes a [`SyscallLog`] and feeds the recorded responses back to
+//! the program, which allows reproducing an execution offline (for example with
+//! [`AotContractExecutor::run`](crate::executor::AotContractExecutor::run)) without the state
+//! that was available when it was recorded. If the program performs a syscall that doesn't match
+//! the recording, the first mismatch is reported as a [`SyscallDivergence`].
+
+use super::{
+    abi::{self, ContractAbi, DecodedEvent},
+    ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point, StarknetSyscallHandler,
+    SyscallKind, SyscallResult, U256,
+};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{fmt, fs, path::Path};
+
+/// The arguments of a syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyscallRequest {
+    GetBlockHash {
+        block_number: u64,
+    },
+    GetExecutionInfo,
+    GetExecutionInfoV2,
+    Deploy {
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: Vec<Felt>,
+        deploy_from_zero: bool,
+    },
+    ReplaceClass {
+        class_hash: Felt,
+    },
+    LibraryCall {
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: Vec<Felt>,
+    },
+    CallContract {
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+    },
+    StorageRead {
+        address_domain: u32,
+        address: Felt,
+    },
+    StorageWrite {
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+    },
+    EmitEvent {
+        keys: Vec<Felt>,
+        data: Vec<Felt>,
+    },
+    SendMessageToL1 {
+        to_address: Felt,
+        payload: Vec<Felt>,
+    },
+    Keccak {
+        input: Vec<u64>,
+    },
+    Secp256k1New {
+        x: U256,
+        y: U256,
+    },
+    Secp256k1Add {
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+    },
+    Secp256k1Mul {
+        p: Secp256k1Point,
+        m: U256,
+    },
+    Secp256k1GetPointFromX {
+        x: U256,
+        y_parity: bool,
+    },
+    Secp256k1GetXy {
+        p: Secp256k1Point,
+    },
+    Secp256r1New {
+        x: U256,
+        y: U256,
+    },
+    Secp256r1Add {
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+    },
+    Secp256r1Mul {
+        p: Secp256r1Point,
+        m: U256,
+    },
+    Secp256r1GetPointFromX {
+        x: U256,
+        y_parity: bool,
+    },
+    Secp256r1GetXy {
+        p: Secp256r1Point,
+    },
+    Sha256ProcessBlock {
+        state: [u32; 8],
+        block: [u32; 16],
+    },
+    GetClassHashAt {
+        contract_address: Felt,
+    },
+    #[cfg(feature = "with-cheatcode")]
+    Cheatcode {
+        selector: Felt,
+        input: Vec<Felt>,
+    },
+}
+
+impl SyscallRequest {
+    pub const fn kind(&self) -> SyscallKind {
+        match self {
+            Self::GetBlockHash { .. } => SyscallKind::GetBlockHash,
+            Self::GetExecutionInfo => SyscallKind::GetExecutionInfo,
+            Self::GetExecutionInfoV2 => SyscallKind::GetExecutionInfoV2,
+            Self::Deploy { .. } => SyscallKind::Deploy,
+            Self::ReplaceClass { .. } => SyscallKind::ReplaceClass,
+            Self::LibraryCall { .. } => SyscallKind::LibraryCall,
+            Self::CallContract { .. } => SyscallKind::CallContract,
+            Self::StorageRead { .. } => SyscallKind::StorageRead,
+            Self::StorageWrite { .. } => SyscallKind::StorageWrite,
+            Self::EmitEvent { .. } => SyscallKind::EmitEvent,
+            Self::SendMessageToL1 { .. } => SyscallKind::SendMessageToL1,
+            Self::Keccak { .. } => SyscallKind::Keccak,
+            Self::Secp256k1New { .. } => SyscallKind::Secp256k1New,
+            Self::Secp256k1Add { .. } => SyscallKind::Secp256k1Add,
+            Self::Secp256k1Mul { .. } => SyscallKind::Secp256k1Mul,
+            Self::Secp256k1GetPointFromX { .. } => SyscallKind::Secp256k1GetPointFromX,
+            Self::Secp256k1GetXy { .. } => SyscallKind::Secp256k1GetXy,
+            Self::Secp256r1New { .. } => SyscallKind::Secp256r1New,
+            Self::Secp256r1Add { .. } => SyscallKind::Secp256r1Add,
+            Self::Secp256r1Mul { .. } => SyscallKind::Secp256r1Mul,
+            Self::Secp256r1GetPointFromX { .. } => SyscallKind::Secp256r1GetPointFromX,
+            Self::Secp256r1GetXy { .. } => SyscallKind::Secp256r1GetXy,
+            Self::Sha256ProcessBlock { .. } => SyscallKind::Sha256ProcessBlock,
+            Self::GetClassHashAt { .. } => SyscallKind::GetClassHashAt,
+            #[cfg(feature = "with-cheatcode")]
+            Self::Cheatcode { .. } => SyscallKind::Cheatcode,
+        }
+    }
+}
+
+/// The successful result of a syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyscallResponse {
+    GetBlockHash(Felt),
+    GetExecutionInfo(ExecutionInfo),
+    GetExecutionInfoV2(ExecutionInfoV2),
+    Deploy(Felt, Vec<Felt>),
+    ReplaceClass,
+    LibraryCall(Vec<Felt>),
+    CallContract(Vec<Felt>),
+    StorageRead(Felt),
+    StorageWrite,
+    EmitEvent,
+    SendMessageToL1,
+    Keccak(U256),
+    Secp256k1New(Option<Secp256k1Point>),
+    Secp256k1Add(Secp256k1Point),
+    Secp256k1Mul(Secp256k1Point),
+    Secp256k1GetPointFromX(Option<Secp256k1Point>),
+    Secp256k1GetXy(U256, U256),
+    Secp256r1New(Option<Secp256r1Point>),
+    Secp256r1Add(Secp256r1Point),
+    Secp256r1Mul(Secp256r1Point),
+    Secp256r1GetPointFromX(Option<Secp256r1Point>),
+    Secp256r1GetXy(U256, U256),
+    /// The block state after processing the block.
+    Sha256ProcessBlock([u32; 8]),
+    GetClassHashAt(Felt),
+    #[cfg(feature = "with-cheatcode")]
+    Cheatcode(Vec<Felt>),
+}
+
+/// A single recorded syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallRecord {
+    pub request: SyscallRequest,
+    pub result: std::result::Result<SyscallResponse, Vec<Felt>>,
+    /// The remaining gas before the syscall, or `None` for syscalls without gas accounting (the
+    /// cheatcodes).
+    pub gas_before: Option<u64>,
+    /// The remaining gas after the syscall, or `None` for syscalls without gas accounting.
+    pub gas_after: Option<u64>,
+}
+
+impl SyscallRecord {
+    pub const fn kind(&self) -> SyscallKind {
+        self.request.kind()
+    }
+}
+
+/// An ordered log of syscalls, as produced by the [`RecordingSyscallHandler`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallLog {
+    /// The version of cairo-native which produced the log.
+    pub native_version: String,
+    pub records: Vec<SyscallRecord>,
+}
+
+impl Default for SyscallLog {
+    fn default() -> Self {
+        Self {
+            native_version: env!("CARGO_PKG_VERSION").to_string(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl SyscallLog {
+    /// Load a syscall log from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the syscall log into a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Decode the events emitted by the successful `emit_event` syscalls of the log.
+    pub fn decode_events(&self, abi: &ContractAbi) -> Vec<abi::Result<DecodedEvent>> {
+        self.records
+            .iter()
+            .filter(|record| record.result.is_ok())
+            .filter_map(|record| match &record.request {
+                SyscallRequest::EmitEvent { keys, data } => Some(abi.decode_event(keys, data)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A syscall handler which records every syscall forwarded to the wrapped handler.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct RecordingSyscallHandler<H> {
+    inner: H,
+    log: SyscallLog,
+}
+
+impl<H> RecordingSyscallHandler<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            log: SyscallLog::default(),
+        }
+    }
+
+    pub const fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    pub const fn log(&self) -> &SyscallLog {
+        &self.log
+    }
+
+    /// Return the recorded log, dropping the wrapped handler.
+    pub fn into_log(self) -> SyscallLog {
+        self.log
+    }
+
+    fn record<T: Clone>(
+        &mut self,
+        request: SyscallRequest,
+        gas_before: u64,
+        gas_after: u64,
+        result: &SyscallResult<T>,
+        map_fn: impl FnOnce(T) -> SyscallResponse,
+    ) {
+        self.log.records.push(SyscallRecord {
+            request,
+            result: result.clone().map(map_fn),
+            gas_before: Some(gas_before),
+            gas_after: Some(gas_after),
+        });
+    }
+}
+
+impl<H> StarknetSyscallHandler for &mut RecordingSyscallHandler<H>
+where
+    H: StarknetSyscallHandler,
+{
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.get_block_hash(block_number, remaining_gas);
+        self.record(
+            SyscallRequest::GetBlockHash { block_number },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::GetBlockHash,
+        );
+        result
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.get_execution_info(remaining_gas);
+        self.record(
+            SyscallRequest::GetExecutionInfo,
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::GetExecutionInfo,
+        );
+        result
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.get_execution_info_v2(remaining_gas);
+        self.record(
+            SyscallRequest::GetExecutionInfoV2,
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::GetExecutionInfoV2,
+        );
+        result
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.deploy(
+            class_hash,
+            contract_address_salt,
+            calldata,
+            deploy_from_zero,
+            remaining_gas,
+        );
+        self.record(
+            SyscallRequest::Deploy {
+                class_hash,
+                contract_address_salt,
+                calldata: calldata.to_vec(),
+                deploy_from_zero,
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |(address, retdata)| SyscallResponse::Deploy(address, retdata),
+        );
+        result
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.replace_class(class_hash, remaining_gas);
+        self.record(
+            SyscallRequest::ReplaceClass { class_hash },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |_| SyscallResponse::ReplaceClass,
+        );
+        result
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        let gas_before = *remaining_gas;
+        let result =
+            self.inner
+                .library_call(class_hash, function_selector, calldata, remaining_gas);
+        self.record(
+            SyscallRequest::LibraryCall {
+                class_hash,
+                function_selector,
+                calldata: calldata.to_vec(),
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::LibraryCall,
+        );
+        result
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        let gas_before = *remaining_gas;
+        let result =
+            self.inner
+                .call_contract(address, entry_point_selector, calldata, remaining_gas);
+        self.record(
+            SyscallRequest::CallContract {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::CallContract,
+        );
+        result
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .storage_read(address_domain, address, remaining_gas);
+        self.record(
+            SyscallRequest::StorageRead {
+                address_domain,
+                address,
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::StorageRead,
+        );
+        result
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .storage_write(address_domain, address, value, remaining_gas);
+        self.record(
+            SyscallRequest::StorageWrite {
+                address_domain,
+                address,
+                value,
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |_| SyscallResponse::StorageWrite,
+        );
+        result
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.emit_event(keys, data, remaining_gas);
+        self.record(
+            SyscallRequest::EmitEvent {
+                keys: keys.to_vec(),
+                data: data.to_vec(),
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |_| SyscallResponse::EmitEvent,
+        );
+        result
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .send_message_to_l1(to_address, payload, remaining_gas);
+        self.record(
+            SyscallRequest::SendMessageToL1 {
+                to_address,
+                payload: payload.to_vec(),
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |_| SyscallResponse::SendMessageToL1,
+        );
+        result
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.keccak(input, remaining_gas);
+        self.record(
+            SyscallRequest::Keccak {
+                input: input.to_vec(),
+            },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Keccak,
+        );
+        result
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256k1_new(x, y, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256k1New { x, y },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256k1New,
+        );
+        result
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256k1_add(p0, p1, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256k1Add { p0, p1 },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256k1Add,
+        );
+        result
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256k1_mul(p, m, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256k1Mul { p, m },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256k1Mul,
+        );
+        result
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .secp256k1_get_point_from_x(x, y_parity, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256k1GetPointFromX { x, y_parity },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256k1GetPointFromX,
+        );
+        result
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256k1_get_xy(p, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256k1GetXy { p },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |(x, y)| SyscallResponse::Secp256k1GetXy(x, y),
+        );
+        result
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256r1_new(x, y, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256r1New { x, y },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256r1New,
+        );
+        result
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256r1_add(p0, p1, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256r1Add { p0, p1 },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256r1Add,
+        );
+        result
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256r1_mul(p, m, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256r1Mul { p, m },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256r1Mul,
+        );
+        result
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .secp256r1_get_point_from_x(x, y_parity, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256r1GetPointFromX { x, y_parity },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::Secp256r1GetPointFromX,
+        );
+        result
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        let gas_before = *remaining_gas;
+        let result = self.inner.secp256r1_get_xy(p, remaining_gas);
+        self.record(
+            SyscallRequest::Secp256r1GetXy { p },
+            gas_before,
+            *remaining_gas,
+            &result,
+            |(x, y)| SyscallResponse::Secp256r1GetXy(x, y),
+        );
+        result
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let gas_before = *remaining_gas;
+        let request = SyscallRequest::Sha256ProcessBlock {
+            state: *state,
+            block: *block,
+        };
+        let result = self.inner.sha256_process_block(state, block, remaining_gas);
+        let state = *state;
+        self.record(request, gas_before, *remaining_gas, &result, |_| {
+            SyscallResponse::Sha256ProcessBlock(state)
+        });
+        result
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        let gas_before = *remaining_gas;
+        let result = self
+            .inner
+            .get_class_hash_at(contract_address, remaining_gas);
+        self.record(
+            SyscallRequest::GetClassHashAt { contract_address },
+            gas_before,
+            *remaining_gas,
+            &result,
+            SyscallResponse::GetClassHashAt,
+        );
+        result
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        let result = self.inner.cheatcode(selector, input);
+        // Cheatcodes don't have a gas counter.
+        self.log.records.push(SyscallRecord {
+            request: SyscallRequest::Cheatcode {
+                selector,
+                input: input.to_vec(),
+            },
+            result: Ok(SyscallResponse::Cheatcode(result.clone())),
+            gas_before: None,
+            gas_after: None,
+        });
+        result
+    }
+}
+
+/// The first syscall which didn't match the recording during a replay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallDivergence {
+    /// Position of the syscall within the log.
+    pub index: usize,
+    /// The recorded syscall, or `None` if the recording had already ended.
+    pub expected: Option<SyscallRequest>,
+    /// The syscall that was actually performed.
+    pub actual: SyscallRequest,
+}
+
+impl fmt::Display for SyscallDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expected {
+            Some(expected) => write!(
+                f,
+                "syscall #{} diverged: expected {:?}, found {:?}",
+                self.index, expected, self.actual
+            ),
+            None => write!(
+                f,
+                "syscall #{} diverged: the recording has ended, found {:?}",
+                self.index, self.actual
+            ),
+        }
+    }
+}
+
+/// A syscall handler which answers with the responses from a [`SyscallLog`].
+///
+/// Once a divergence is found, every following syscall fails with a `replay divergence` error.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug, Clone)]
+pub struct ReplaySyscallHandler {
+    log: SyscallLog,
+    position: usize,
+    divergence: Option<SyscallDivergence>,
+}
+
+impl ReplaySyscallHandler {
+    pub const fn new(log: SyscallLog) -> Self {
+        Self {
+            log,
+            position: 0,
+            divergence: None,
+        }
+    }
+
+    /// The first syscall which didn't match the recording, if any.
+    pub const fn divergence(&self) -> Option<&SyscallDivergence> {
+        self.divergence.as_ref()
+    }
+
+    /// Whether every recorded syscall has been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.position == self.log.records.len()
+    }
+
+    /// The recorded syscalls which have not been replayed yet.
+    pub fn remaining(&self) -> &[SyscallRecord] {
+        &self.log.records[self.position..]
+    }
+
+    fn replay<T>(
+        &mut self,
+        request: SyscallRequest,
+        remaining_gas: &mut u64,
+        extract_fn: impl FnOnce(SyscallResponse) -> Option<T>,
+    ) -> SyscallResult<T> {
+        let divergence_error = || vec![Felt::from_bytes_be_slice(b"replay divergence")];
+        if self.divergence.is_some() {
+            return Err(divergence_error());
+        }
+
+        let index = self.position;
+        let record = match self.log.records.get(index) {
+            Some(record) if record.request == request => record.clone(),
+            record => {
+                tracing::warn!("syscall #{index} diverged from the recording");
+                self.divergence = Some(SyscallDivergence {
+                    index,
+                    expected: record.map(|x| x.request.clone()),
+                    actual: request,
+                });
+                return Err(divergence_error());
+            }
+        };
+        self.position += 1;
+
+        // Logs may be edited by hand, so the recorded gas isn't trusted to decrease.
+        let gas_cost = match (record.gas_before, record.gas_after) {
+            (Some(gas_before), Some(gas_after)) => gas_before.checked_sub(gas_after),
+            (None, None) => Some(0),
+            _ => None,
+        };
+        let Some(gas_cost) = gas_cost else {
+            tracing::warn!("syscall #{index} has an invalid recorded gas");
+            return Err(vec![Felt::from_bytes_be_slice(b"replay invalid gas")]);
+        };
+        // The replayed program may have less gas left than the recorded one.
+        let Some(gas) = remaining_gas.checked_sub(gas_cost) else {
+            return Err(vec![Felt::from_bytes_be_slice(b"Out of gas")]);
+        };
+        *remaining_gas = gas;
+        match record.result {
+            Ok(response) => match extract_fn(response) {
+                Some(value) => Ok(value),
+                None => {
+                    self.divergence = Some(SyscallDivergence {
+                        index,
+                        expected: Some(record.request),
+                        actual: request,
+                    });
+                    Err(divergence_error())
+                }
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl StarknetSyscallHandler for &mut ReplaySyscallHandler {
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::GetBlockHash { block_number },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::GetBlockHash(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        self.replay(
+            SyscallRequest::GetExecutionInfo,
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::GetExecutionInfo(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        self.replay(
+            SyscallRequest::GetExecutionInfoV2,
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::GetExecutionInfoV2(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        self.replay(
+            SyscallRequest::Deploy {
+                class_hash,
+                contract_address_salt,
+                calldata: calldata.to_vec(),
+                deploy_from_zero,
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Deploy(address, retdata) => Some((address, retdata)),
+                _ => None,
+            },
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::ReplaceClass { class_hash },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::ReplaceClass => Some(()),
+                _ => None,
+            },
+        )
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.replay(
+            SyscallRequest::LibraryCall {
+                class_hash,
+                function_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::LibraryCall(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.replay(
+            SyscallRequest::CallContract {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::CallContract(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::StorageRead {
+                address_domain,
+                address,
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::StorageRead(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::StorageWrite {
+                address_domain,
+                address,
+                value,
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::StorageWrite => Some(()),
+                _ => None,
+            },
+        )
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::EmitEvent {
+                keys: keys.to_vec(),
+                data: data.to_vec(),
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::EmitEvent => Some(()),
+                _ => None,
+            },
+        )
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::SendMessageToL1 {
+                to_address,
+                payload: payload.to_vec(),
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::SendMessageToL1 => Some(()),
+                _ => None,
+            },
+        )
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        self.replay(
+            SyscallRequest::Keccak {
+                input: input.to_vec(),
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Keccak(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.replay(
+            SyscallRequest::Secp256k1New { x, y },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256k1New(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.replay(
+            SyscallRequest::Secp256k1Add { p0, p1 },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256k1Add(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.replay(
+            SyscallRequest::Secp256k1Mul { p, m },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256k1Mul(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.replay(
+            SyscallRequest::Secp256k1GetPointFromX { x, y_parity },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256k1GetPointFromX(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.replay(
+            SyscallRequest::Secp256k1GetXy { p },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256k1GetXy(x, y) => Some((x, y)),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.replay(
+            SyscallRequest::Secp256r1New { x, y },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256r1New(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.replay(
+            SyscallRequest::Secp256r1Add { p0, p1 },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256r1Add(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.replay(
+            SyscallRequest::Secp256r1Mul { p, m },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256r1Mul(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.replay(
+            SyscallRequest::Secp256r1GetPointFromX { x, y_parity },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256r1GetPointFromX(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.replay(
+            SyscallRequest::Secp256r1GetXy { p },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Secp256r1GetXy(x, y) => Some((x, y)),
+                _ => None,
+            },
+        )
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let new_state = self.replay(
+            SyscallRequest::Sha256ProcessBlock {
+                state: *state,
+                block: *block,
+            },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::Sha256ProcessBlock(x) => Some(x),
+                _ => None,
+            },
+        )?;
+
+        *state = new_state;
+        Ok(())
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::GetClassHashAt { contract_address },
+            remaining_gas,
+            |x| match x {
+                SyscallResponse::GetClassHashAt(x) => Some(x),
+                _ => None,
+            },
+        )
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        self.replay(
+            SyscallRequest::Cheatcode {
+                selector,
+                input: input.to_vec(),
+            },
+            &mut 0,
+            |x| match x {
+                SyscallResponse::Cheatcode(x) => Some(x),
+                _ => None,
+            },
+        )
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_stub::StubSyscallHandler;
+
+    fn record_storage_ops() -> SyscallLog {
+        let mut stub = StubSyscallHandler::default();
+        let mut recorder = RecordingSyscallHandler::new(&mut stub);
+        let mut handler = &mut recorder;
+
+        let mut gas = 100_000;
+        handler
+            .storage_write(0, 1.into(), 42.into(), &mut gas)
+            .unwrap();
+        assert_eq!(
+            handler.storage_read(0, 1.into(), &mut gas).unwrap(),
+            42.into()
+        );
+        handler.storage_read(0, 2.into(), &mut gas).unwrap_err();
+
+        recorder.into_log()
+    }
+
+    #[test]
+    fn test_record() {
+        let log = record_storage_ops();
+
+        assert_eq!(
+            log.records
+                .iter()
+                .map(SyscallRecord::kind)
+                .collect::<Vec<_>>(),
+            [
+                SyscallKind::StorageWrite,
+                SyscallKind::StorageRead,
+                SyscallKind::StorageRead
+            ]
+        );
+        assert_eq!(
+            log.records[1].result,
+            Ok(SyscallResponse::StorageRead(42.into()))
+        );
+        assert!(log.records[2].result.is_err());
+    }
+
+    #[test]
+    fn test_log_serialization() {
+        let log = record_storage_ops();
+
+        let serialized = serde_json::to_string(&log).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SyscallLog>(&serialized).unwrap(),
+            log
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut replay = ReplaySyscallHandler::new(record_storage_ops());
+        let mut handler = &mut replay;
+
+        let mut gas = 100_000;
+        handler
+            .storage_write(0, 1.into(), 42.into(), &mut gas)
+            .unwrap();
+        assert_eq!(
+            handler.storage_read(0, 1.into(), &mut gas).unwrap(),
+            42.into()
+        );
+        handler.storage_read(0, 2.into(), &mut gas).unwrap_err();
+
+        assert!(replay.divergence().is_none());
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn test_replay_divergence() {
+        let mut replay = ReplaySyscallHandler::new(record_storage_ops());
+        let mut handler = &mut replay;
+
+        let mut gas = 100_000;
+        handler
+            .storage_write(0, 1.into(), 43.into(), &mut gas)
+            .unwrap_err();
+        // Once diverged, every syscall fails.
+        handler.storage_read(0, 1.into(), &mut gas).unwrap_err();
+
+        let divergence = replay.divergence().unwrap();
+        assert_eq!(divergence.index, 0);
+        assert_eq!(
+            divergence.actual,
+            SyscallRequest::StorageWrite {
+                address_domain: 0,
+                address: 1.into(),
+                value: 43.into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_replay_invalid_gas() {
+        let mut log = record_storage_ops();
+        log.records[0].gas_after = log.records[0].gas_before.map(|gas| gas + 1);
+        let mut replay = ReplaySyscallHandler::new(log);
+        let mut handler = &mut replay;
+
+        let mut gas = 100_000;
+        assert_eq!(
+            handler.storage_write(0, 1.into(), 42.into(), &mut gas),
+            Err(vec![Felt::from_bytes_be_slice(b"replay invalid gas")])
+        );
+        assert_eq!(gas, 100_000);
+    }
+
+    #[test]
+    fn test_replay_out_of_gas() {
+        let log = record_storage_ops();
+        let gas_cost = log.records[0].gas_before.unwrap() - log.records[0].gas_after.unwrap();
+        let mut replay = ReplaySyscallHandler::new(log);
+        let mut handler = &mut replay;
+
+        let mut gas = gas_cost - 1;
+        assert_eq!(
+            handler.storage_write(0, 1.into(), 42.into(), &mut gas),
+            Err(vec![Felt::from_bytes_be_slice(b"Out of gas")])
+        );
+        assert_eq!(gas, gas_cost - 1);
+    }
+}
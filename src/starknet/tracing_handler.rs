@@ -0,0 +1,436 @@
+//! # Syscall tracing and metrics
+//!
+//! The [`TracingSyscallHandler`] wraps another syscall handler, emitting a `tracing` span for every
+//! syscall and collecting per-syscall metrics (number of calls, failures, gas charged and time
+//! spent), which are returned as a [`SyscallSummary`] once the execution is done.
+
+use super::{
+    ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point, StarknetSyscallHandler,
+    SyscallKind, SyscallResult, U256,
+};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Metrics of a single kind of syscall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallStats {
+    pub calls: u64,
+    pub failures: u64,
+    pub gas: u64,
+    pub time: Duration,
+}
+
+/// Metrics of every syscall performed through a [`TracingSyscallHandler`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallSummary {
+    pub stats: BTreeMap<SyscallKind, SyscallStats>,
+}
+
+impl SyscallSummary {
+    pub fn total_calls(&self) -> u64 {
+        self.stats.values().map(|x| x.calls).sum()
+    }
+
+    pub fn total_gas(&self) -> u64 {
+        self.stats.values().map(|x| x.gas).sum()
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.stats.values().map(|x| x.time).sum()
+    }
+}
+
+impl fmt::Display for SyscallSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8} {:>12} {:>12}",
+            "syscall", "calls", "failed", "gas", "time"
+        )?;
+        for (kind, stats) in &self.stats {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>8} {:>12} {:>12}",
+                format!("{kind:?}"),
+                stats.calls,
+                stats.failures,
+                stats.gas,
+                format!("{:?}", stats.time),
+            )?;
+        }
+        write!(
+            f,
+            "{:<24} {:>8} {:>8} {:>12} {:>12}",
+            "total",
+            self.total_calls(),
+            self.stats.values().map(|x| x.failures).sum::<u64>(),
+            self.total_gas(),
+            format!("{:?}", self.total_time()),
+        )
+    }
+}
+
+/// A syscall handler which traces and measures every syscall forwarded to the wrapped handler.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct TracingSyscallHandler<H> {
+    inner: H,
+    summary: SyscallSummary,
+}
+
+impl<H> TracingSyscallHandler<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            summary: SyscallSummary::default(),
+        }
+    }
+
+    pub const fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    pub const fn summary(&self) -> &SyscallSummary {
+        &self.summary
+    }
+
+    /// Return the collected metrics, dropping the wrapped handler.
+    pub fn into_summary(self) -> SyscallSummary {
+        self.summary
+    }
+
+    fn trace<T>(
+        &mut self,
+        kind: SyscallKind,
+        remaining_gas: &mut u64,
+        syscall_fn: impl FnOnce(&mut H, &mut u64) -> SyscallResult<T>,
+    ) -> SyscallResult<T> {
+        let span = tracing::debug_span!("syscall", ?kind, remaining_gas = *remaining_gas);
+        let _guard = span.enter();
+
+        let gas_before = *remaining_gas;
+        let start = Instant::now();
+        let result = syscall_fn(&mut self.inner, remaining_gas);
+        let time = start.elapsed();
+        let gas = gas_before.saturating_sub(*remaining_gas);
+
+        tracing::debug!(?time, gas, failed = result.is_err(), "syscall finished");
+
+        let stats = self.summary.stats.entry(kind).or_default();
+        stats.calls += 1;
+        stats.failures += u64::from(result.is_err());
+        stats.gas += gas;
+        stats.time += time;
+
+        result
+    }
+}
+
+impl<H> StarknetSyscallHandler for &mut TracingSyscallHandler<H>
+where
+    H: StarknetSyscallHandler,
+{
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.trace(SyscallKind::GetBlockHash, remaining_gas, |inner, gas| {
+            inner.get_block_hash(block_number, gas)
+        })
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        self.trace(
+            SyscallKind::GetExecutionInfo,
+            remaining_gas,
+            |inner, gas| inner.get_execution_info(gas),
+        )
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        self.trace(
+            SyscallKind::GetExecutionInfoV2,
+            remaining_gas,
+            |inner, gas| inner.get_execution_info_v2(gas),
+        )
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        self.trace(SyscallKind::Deploy, remaining_gas, |inner, gas| {
+            inner.deploy(
+                class_hash,
+                contract_address_salt,
+                calldata,
+                deploy_from_zero,
+                gas,
+            )
+        })
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        self.trace(SyscallKind::ReplaceClass, remaining_gas, |inner, gas| {
+            inner.replace_class(class_hash, gas)
+        })
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.trace(SyscallKind::LibraryCall, remaining_gas, |inner, gas| {
+            inner.library_call(class_hash, function_selector, calldata, gas)
+        })
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.trace(SyscallKind::CallContract, remaining_gas, |inner, gas| {
+            inner.call_contract(address, entry_point_selector, calldata, gas)
+        })
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.trace(SyscallKind::StorageRead, remaining_gas, |inner, gas| {
+            inner.storage_read(address_domain, address, gas)
+        })
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.trace(SyscallKind::StorageWrite, remaining_gas, |inner, gas| {
+            inner.storage_write(address_domain, address, value, gas)
+        })
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.trace(SyscallKind::EmitEvent, remaining_gas, |inner, gas| {
+            inner.emit_event(keys, data, gas)
+        })
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.trace(SyscallKind::SendMessageToL1, remaining_gas, |inner, gas| {
+            inner.send_message_to_l1(to_address, payload, gas)
+        })
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        self.trace(SyscallKind::Keccak, remaining_gas, |inner, gas| {
+            inner.keccak(input, gas)
+        })
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.trace(SyscallKind::Secp256k1New, remaining_gas, |inner, gas| {
+            inner.secp256k1_new(x, y, gas)
+        })
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.trace(SyscallKind::Secp256k1Add, remaining_gas, |inner, gas| {
+            inner.secp256k1_add(p0, p1, gas)
+        })
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.trace(SyscallKind::Secp256k1Mul, remaining_gas, |inner, gas| {
+            inner.secp256k1_mul(p, m, gas)
+        })
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.trace(
+            SyscallKind::Secp256k1GetPointFromX,
+            remaining_gas,
+            |inner, gas| inner.secp256k1_get_point_from_x(x, y_parity, gas),
+        )
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.trace(SyscallKind::Secp256k1GetXy, remaining_gas, |inner, gas| {
+            inner.secp256k1_get_xy(p, gas)
+        })
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.trace(SyscallKind::Secp256r1New, remaining_gas, |inner, gas| {
+            inner.secp256r1_new(x, y, gas)
+        })
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.trace(SyscallKind::Secp256r1Add, remaining_gas, |inner, gas| {
+            inner.secp256r1_add(p0, p1, gas)
+        })
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.trace(SyscallKind::Secp256r1Mul, remaining_gas, |inner, gas| {
+            inner.secp256r1_mul(p, m, gas)
+        })
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.trace(
+            SyscallKind::Secp256r1GetPointFromX,
+            remaining_gas,
+            |inner, gas| inner.secp256r1_get_point_from_x(x, y_parity, gas),
+        )
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.trace(SyscallKind::Secp256r1GetXy, remaining_gas, |inner, gas| {
+            inner.secp256r1_get_xy(p, gas)
+        })
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.trace(
+            SyscallKind::Sha256ProcessBlock,
+            remaining_gas,
+            |inner, gas| inner.sha256_process_block(state, block, gas),
+        )
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.trace(SyscallKind::GetClassHashAt, remaining_gas, |inner, gas| {
+            inner.get_class_hash_at(contract_address, gas)
+        })
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        self.trace(SyscallKind::Cheatcode, &mut 0, |inner, _| {
+            Ok(inner.cheatcode(selector, input))
+        })
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_stub::StubSyscallHandler;
+
+    #[test]
+    fn test_summary() {
+        let mut stub = StubSyscallHandler::default();
+        let mut tracer = TracingSyscallHandler::new(&mut stub);
+        let mut handler = &mut tracer;
+
+        let mut gas = 100_000;
+        handler
+            .storage_write(0, 1.into(), 42.into(), &mut gas)
+            .unwrap();
+        handler.storage_read(0, 1.into(), &mut gas).unwrap();
+        handler.storage_read(0, 2.into(), &mut gas).unwrap_err();
+        handler.get_block_hash(1, &mut gas).unwrap();
+
+        let summary = tracer.into_summary();
+        assert_eq!(summary.total_calls(), 4);
+        assert_eq!(summary.stats[&SyscallKind::StorageRead].calls, 2);
+        assert_eq!(summary.stats[&SyscallKind::StorageRead].failures, 1);
+        assert_eq!(summary.stats[&SyscallKind::StorageWrite].calls, 1);
+        assert_eq!(summary.total_gas(), 100_000 - gas);
+        assert!(!summary.stats.contains_key(&SyscallKind::Keccak));
+    }
+}
@@ -1,11 +1,17 @@
 //! A (somewhat) usable implementation of the starknet syscall handler trait.
 
+pub mod account;
+pub mod snapshot;
+pub mod state_diff;
+
+use self::state_diff::{OrderedEvent, OrderedL2ToL1Message, StateDiff};
 use std::{
     collections::{HashMap, VecDeque},
     fmt,
 };
 
 use crate::starknet::{
+    abi::{self, ContractAbi, DecodedEvent},
     BlockInfo, ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point,
     StarknetSyscallHandler, SyscallResult, TxInfo, TxV2Info, U256,
 };
@@ -14,18 +20,61 @@ use ark_ff::{BigInt, PrimeField};
 use itertools::Itertools;
 use num_bigint::BigUint;
 use num_traits::Zero;
-use starknet_types_core::felt::Felt;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::{
+    felt::{Felt, NonZeroFelt},
+    hash::{Pedersen, StarkHash},
+};
 use tracing::instrument;
 
 /// A (somewhat) usable implementation of the starknet syscall handler trait.
 ///
-/// Currently gas is not deducted.
+/// Gas is deducted according to [`SyscallGasCosts`], which defaults to the costs charged by the
+/// sequencer.
+///
+/// With the `with-cheatcode` feature it implements the cheatcodes used by `starknet::testing`
+/// (`set_block_number`, `set_caller_address`, `pop_log`...), plus:
+///   - `set_block_hash(block_number, hash)`
+///   - `mock_call(address, selector, retdata: Span<felt252>)` and `stop_mock_call(address,
+///     selector)`, which make `call_contract` and `library_call` return the given data.
+///   - `spy_events()`, which starts capturing events, and `get_spied_events()`, which returns
+///     (and clears) them serialized as `Array<(ContractAddress, Span<felt252>, Span<felt252>)>`.
+///   - `expect_revert(panic_data: Span<felt252>)`, which makes the test runners expect the test to
+///     panic.
+///
+/// Their arguments are serialized with `Serde`, so spans are prefixed with their length. Only
+/// `set_signature` takes its span unprefixed, as `starknet::testing` passes it. Cheatcodes given
+/// malformed arguments return `'invalid cheatcode input'`.
 #[derive(Debug, Clone)]
 pub struct StubSyscallHandler {
     pub storage: HashMap<(u32, Felt), Felt>,
     pub events: Vec<StubEvent>,
     pub execution_info: ExecutionInfoV2,
     pub logs: HashMap<Felt, ContractLogs>,
+    pub gas_costs: SyscallGasCosts,
+    /// Block hashes set through the `set_block_hash` cheatcode.
+    pub block_hashes: HashMap<u64, Felt>,
+    /// Return data of mocked calls, by (contract address or class hash, selector).
+    pub mocked_calls: HashMap<(Felt, Felt), Vec<Felt>>,
+    /// Events captured since the last `spy_events` cheatcode, with their emitter's address.
+    pub spied_events: Option<Vec<(Felt, StubEvent)>>,
+    /// Panic data expected by the `expect_revert` cheatcode. An empty vector matches any panic.
+    pub expected_revert: Option<Vec<Felt>>,
+    /// State changes performed through this handler.
+    pub state_diff: StateDiff,
+    pub execution_mode: ExecutionMode,
+}
+
+/// The mode the syscalls are executed in.
+///
+/// While validating a transaction, `get_block_hash`, `deploy` and calls to other contracts are
+/// forbidden, and `get_execution_info` returns a coarser block info: the block number rounded
+/// down to a multiple of 100, the timestamp rounded down to the hour and no sequencer address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    #[default]
+    Execute,
+    Validate,
 }
 
 impl Default for StubSyscallHandler {
@@ -59,7 +108,357 @@ impl Default for StubSyscallHandler {
                 entry_point_selector: 4.into(),
             },
             logs: HashMap::new(),
+            gas_costs: SyscallGasCosts::default(),
+            block_hashes: HashMap::new(),
+            mocked_calls: HashMap::new(),
+            spied_events: None,
+            expected_revert: None,
+            state_diff: StateDiff::default(),
+            execution_mode: ExecutionMode::default(),
+        }
+    }
+}
+
+/// Gas charged by every syscall, on top of the base amount already charged by the compiled
+/// program.
+///
+/// The defaults match blockifier's versioned constants (the same values used by the Cairo VM
+/// runner). Events are only accounted for in the transaction fee there, so their per-element costs
+/// default to zero; callers wanting them charged as gas can set them explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyscallGasCosts {
+    pub get_block_hash: u64,
+    pub get_execution_info: u64,
+    pub deploy: u64,
+    pub replace_class: u64,
+    pub library_call: u64,
+    pub call_contract: u64,
+    pub storage_read: u64,
+    pub storage_write: u64,
+    pub emit_event: u64,
+    /// Cost per key of an emitted event.
+    pub emit_event_key: u64,
+    /// Cost per data element of an emitted event.
+    pub emit_event_data: u64,
+    pub send_message_to_l1: u64,
+    pub keccak: u64,
+    /// Cost per keccak round (every 17 words of input).
+    pub keccak_round: u64,
+    pub secp256k1_new: u64,
+    pub secp256k1_add: u64,
+    pub secp256k1_mul: u64,
+    pub secp256k1_get_point_from_x: u64,
+    pub secp256k1_get_xy: u64,
+    pub secp256r1_new: u64,
+    pub secp256r1_add: u64,
+    pub secp256r1_mul: u64,
+    pub secp256r1_get_point_from_x: u64,
+    pub secp256r1_get_xy: u64,
+    pub sha256_process_block: u64,
+    pub get_class_hash_at: u64,
+}
+
+impl SyscallGasCosts {
+    const STEP: u64 = 100;
+    const RANGE_CHECK: u64 = 70;
+    const BITWISE: u64 = 594;
+    const ENTRY_POINT: u64 = 600 * Self::STEP;
+
+    /// A cost table where no syscall charges any gas.
+    pub const fn zero() -> Self {
+        Self {
+            get_block_hash: 0,
+            get_execution_info: 0,
+            deploy: 0,
+            replace_class: 0,
+            library_call: 0,
+            call_contract: 0,
+            storage_read: 0,
+            storage_write: 0,
+            emit_event: 0,
+            emit_event_key: 0,
+            emit_event_data: 0,
+            send_message_to_l1: 0,
+            keccak: 0,
+            keccak_round: 0,
+            secp256k1_new: 0,
+            secp256k1_add: 0,
+            secp256k1_mul: 0,
+            secp256k1_get_point_from_x: 0,
+            secp256k1_get_xy: 0,
+            secp256r1_new: 0,
+            secp256r1_add: 0,
+            secp256r1_mul: 0,
+            secp256r1_get_point_from_x: 0,
+            secp256r1_get_xy: 0,
+            sha256_process_block: 0,
+            get_class_hash_at: 0,
+        }
+    }
+}
+
+impl Default for SyscallGasCosts {
+    fn default() -> Self {
+        const STEP: u64 = SyscallGasCosts::STEP;
+        const RANGE_CHECK: u64 = SyscallGasCosts::RANGE_CHECK;
+
+        Self {
+            get_block_hash: 50 * STEP,
+            get_execution_info: 10 * STEP,
+            deploy: 200 * STEP + Self::ENTRY_POINT,
+            replace_class: 50 * STEP,
+            library_call: 10 * STEP + Self::ENTRY_POINT,
+            call_contract: 10 * STEP + Self::ENTRY_POINT,
+            storage_read: 50 * STEP,
+            storage_write: 50 * STEP,
+            emit_event: 10 * STEP,
+            emit_event_key: 0,
+            emit_event_data: 0,
+            send_message_to_l1: 50 * STEP,
+            keccak: 0,
+            keccak_round: 180000,
+            secp256k1_new: 340 * STEP + 36 * RANGE_CHECK,
+            secp256k1_add: 254 * STEP + 29 * RANGE_CHECK,
+            secp256k1_mul: 121810 * STEP + 10739 * RANGE_CHECK,
+            secp256k1_get_point_from_x: 260 * STEP + 29 * RANGE_CHECK,
+            secp256k1_get_xy: 24 * STEP + 9 * RANGE_CHECK,
+            secp256r1_new: 340 * STEP + 36 * RANGE_CHECK,
+            secp256r1_add: 254 * STEP + 29 * RANGE_CHECK,
+            secp256r1_mul: 121810 * STEP + 10739 * RANGE_CHECK,
+            secp256r1_get_point_from_x: 260 * STEP + 29 * RANGE_CHECK,
+            secp256r1_get_xy: 24 * STEP + 9 * RANGE_CHECK,
+            sha256_process_block: 1852 * STEP + 65 * RANGE_CHECK + 1115 * Self::BITWISE,
+            get_class_hash_at: 50 * STEP,
+        }
+    }
+}
+
+/// Calculates the address of a deployed contract, as defined in
+/// <https://docs.starknet.io/architecture-and-concepts/smart-contracts/contract-address/>.
+pub fn calculate_contract_address(
+    salt: Felt,
+    class_hash: Felt,
+    constructor_calldata: &[Felt],
+    deployer_address: Felt,
+) -> Felt {
+    // Cairo string of "STARKNET_CONTRACT_ADDRESS".
+    const CONTRACT_ADDRESS_PREFIX: Felt =
+        Felt::from_hex_unchecked("0x535441524b4e45545f434f4e54524143545f41444452455353");
+    // 2 ** 251 - 256
+    const ADDR_BOUND: NonZeroFelt = NonZeroFelt::from_felt_unchecked(Felt::from_hex_unchecked(
+        "0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00",
+    ));
+
+    Pedersen::hash_array(&[
+        CONTRACT_ADDRESS_PREFIX,
+        deployer_address,
+        salt,
+        class_hash,
+        Pedersen::hash_array(constructor_calldata),
+    ])
+    .mod_floor(&ADDR_BOUND)
+}
+
+impl StubSyscallHandler {
+    /// Fail if the syscall isn't allowed in the current execution mode.
+    fn check_execution_mode(&self, allowed_in_validate: bool) -> SyscallResult<()> {
+        match self.execution_mode {
+            ExecutionMode::Validate if !allowed_in_validate => {
+                Err(vec![Felt::from_bytes_be_slice(b"Unauthorized syscall")])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The block info as seen by the current execution mode.
+    fn block_info(&self) -> BlockInfo {
+        let block_info = self.execution_info.block_info;
+        match self.execution_mode {
+            ExecutionMode::Execute => block_info,
+            ExecutionMode::Validate => BlockInfo {
+                block_number: block_info.block_number - block_info.block_number % 100,
+                block_timestamp: block_info.block_timestamp - block_info.block_timestamp % 3600,
+                sequencer_address: Felt::ZERO,
+            },
+        }
+    }
+}
+
+/// Deducts `amount` from the remaining gas, failing with the sequencer's out of gas error when
+/// there isn't enough.
+fn charge_gas(remaining_gas: &mut u64, amount: u64) -> SyscallResult<()> {
+    match remaining_gas.checked_sub(amount) {
+        Some(gas) => {
+            *remaining_gas = gas;
+            Ok(())
+        }
+        None => Err(vec![Felt::from_bytes_be_slice(b"Out of gas")]),
+    }
+}
+
+/// The cost of `count` elements at `cost` each, failing with the out of gas error when it doesn't
+/// fit in the gas counter (it couldn't be paid anyway).
+fn cost_per_element(cost: u64, count: usize) -> SyscallResult<u64> {
+    u64::try_from(count)
+        .ok()
+        .and_then(|count| cost.checked_mul(count))
+        .ok_or_else(|| vec![Felt::from_bytes_be_slice(b"Out of gas")])
+}
+
+#[cfg(feature = "with-cheatcode")]
+impl StubSyscallHandler {
+    /// Runs the cheatcode with the given selector, returning `None` if its input is malformed.
+    fn run_cheatcode(&mut self, selector: &str, input: &mut CheatcodeInput) -> Option<Vec<Felt>> {
+        Some(match selector {
+            "set_sequencer_address" => {
+                self.execution_info.block_info.sequencer_address = input.felt()?;
+                vec![]
+            }
+            "set_caller_address" => {
+                self.execution_info.caller_address = input.felt()?;
+                vec![]
+            }
+            "set_contract_address" => {
+                self.execution_info.contract_address = input.felt()?;
+                vec![]
+            }
+            "set_account_contract_address" => {
+                self.execution_info.tx_info.account_contract_address = input.felt()?;
+                vec![]
+            }
+            "set_transaction_hash" => {
+                self.execution_info.tx_info.transaction_hash = input.felt()?;
+                vec![]
+            }
+            "set_nonce" => {
+                self.execution_info.tx_info.nonce = input.felt()?;
+                vec![]
+            }
+            "set_version" => {
+                self.execution_info.tx_info.version = input.felt()?;
+                vec![]
+            }
+            "set_chain_id" => {
+                self.execution_info.tx_info.chain_id = input.felt()?;
+                vec![]
+            }
+            "set_max_fee" => {
+                let max_fee = input.int()?;
+                self.execution_info.tx_info.max_fee = max_fee;
+                vec![]
+            }
+            "set_block_number" => {
+                let block_number = input.int()?;
+                self.execution_info.block_info.block_number = block_number;
+                vec![]
+            }
+            "set_block_timestamp" => {
+                let block_timestamp = input.int()?;
+                self.execution_info.block_info.block_timestamp = block_timestamp;
+                vec![]
+            }
+            "set_signature" => {
+                self.execution_info.tx_info.signature = input.rest().to_vec();
+                vec![]
+            }
+            "set_block_hash" => {
+                let block_number = input.int()?;
+                self.block_hashes.insert(block_number, input.felt()?);
+                vec![]
+            }
+            "mock_call" => {
+                let (address, selector) = (input.felt()?, input.felt()?);
+                self.mocked_calls
+                    .insert((address, selector), input.span()?.to_vec());
+                vec![]
+            }
+            "stop_mock_call" => {
+                self.mocked_calls.remove(&(input.felt()?, input.felt()?));
+                vec![]
+            }
+            "spy_events" => {
+                self.spied_events = Some(Vec::new());
+                vec![]
+            }
+            "get_spied_events" => {
+                let spied_events = self.spied_events.as_mut().map(std::mem::take);
+
+                let mut serialized_events = Vec::new();
+                serialized_events.push(spied_events.as_ref().map_or(0, Vec::len).into());
+                for (from_address, mut event) in spied_events.into_iter().flatten() {
+                    serialized_events.push(from_address);
+                    serialized_events.push(event.keys.len().into());
+                    serialized_events.append(&mut event.keys);
+                    serialized_events.push(event.data.len().into());
+                    serialized_events.append(&mut event.data);
+                }
+                serialized_events
+            }
+            "expect_revert" => {
+                self.expected_revert = Some(input.span()?.to_vec());
+                vec![]
+            }
+            "pop_log" => self
+                .logs
+                .get_mut(&input.felt()?)
+                .and_then(|logs| logs.events.pop_front())
+                .map(|mut log| {
+                    let mut serialized_log = Vec::new();
+                    serialized_log.push(log.keys.len().into());
+                    serialized_log.append(&mut log.keys);
+                    serialized_log.push(log.data.len().into());
+                    serialized_log.append(&mut log.data);
+                    serialized_log
+                })
+                .unwrap_or_default(),
+            "pop_l2_to_l1_message" => self
+                .logs
+                .get_mut(&input.felt()?)
+                .and_then(|logs| logs.l2_to_l1_messages.pop_front())
+                .map(|mut log| {
+                    let mut serialized_log = Vec::new();
+                    serialized_log.push(log.0);
+                    serialized_log.push(log.1.len().into());
+                    serialized_log.append(&mut log.1);
+                    serialized_log
+                })
+                .unwrap_or_default(),
+            _ => vec![],
+        })
+    }
+}
+
+/// The input of a cheatcode, from which its `Serde`-serialized arguments are read.
+#[cfg(feature = "with-cheatcode")]
+struct CheatcodeInput<'a>(&'a [Felt]);
+
+#[cfg(feature = "with-cheatcode")]
+impl<'a> CheatcodeInput<'a> {
+    fn felt(&mut self) -> Option<Felt> {
+        let (value, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*value)
+    }
+
+    fn int<T: TryFrom<Felt>>(&mut self) -> Option<T> {
+        self.felt()?.try_into().ok()
+    }
+
+    /// Reads a span, which is serialized as its length followed by its elements.
+    fn span(&mut self) -> Option<&'a [Felt]> {
+        let len: usize = self.int()?;
+        if len > self.0.len() {
+            return None;
         }
+
+        let (values, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(values)
+    }
+
+    fn rest(&mut self) -> &'a [Felt] {
+        std::mem::take(&mut self.0)
     }
 }
 
@@ -70,6 +469,13 @@ pub struct StubEvent {
     pub data: Vec<Felt>,
 }
 
+impl StubEvent {
+    /// Decode the event according to the emitter's ABI.
+    pub fn decode(&self, abi: &ContractAbi) -> abi::Result<DecodedEvent> {
+        abi.decode_event(&self.keys, &self.data)
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ContractLogs {
     pub events: VecDeque<StubEvent>,
@@ -271,7 +677,13 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<Felt> {
         tracing::debug!("called");
-        Ok(block_number.into())
+        charge_gas(remaining_gas, self.gas_costs.get_block_hash)?;
+        self.check_execution_mode(false)?;
+        Ok(self
+            .block_hashes
+            .get(&block_number)
+            .copied()
+            .unwrap_or_else(|| block_number.into()))
     }
 
     #[instrument(skip(self))]
@@ -280,8 +692,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<crate::starknet::ExecutionInfo> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.get_execution_info)?;
         Ok(ExecutionInfo {
-            block_info: self.execution_info.block_info,
+            block_info: self.block_info(),
             tx_info: TxInfo {
                 version: self.execution_info.tx_info.version,
                 account_contract_address: self.execution_info.tx_info.account_contract_address,
@@ -303,7 +716,11 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<crate::starknet::ExecutionInfoV2> {
         tracing::debug!("called");
-        Ok(self.execution_info.clone())
+        charge_gas(remaining_gas, self.gas_costs.get_execution_info)?;
+        Ok(ExecutionInfoV2 {
+            block_info: self.block_info(),
+            ..self.execution_info.clone()
+        })
     }
 
     #[instrument(skip(self))]
@@ -316,7 +733,34 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<(Felt, Vec<Felt>)> {
         tracing::debug!("called");
-        todo!()
+        charge_gas(remaining_gas, self.gas_costs.deploy)?;
+        self.check_execution_mode(false)?;
+
+        let deployer_address = if deploy_from_zero {
+            Felt::ZERO
+        } else {
+            self.execution_info.contract_address
+        };
+        let contract_address = calculate_contract_address(
+            contract_address_salt,
+            class_hash,
+            calldata,
+            deployer_address,
+        );
+
+        if self
+            .state_diff
+            .deployed_contracts
+            .insert(contract_address, class_hash)
+            .is_some()
+        {
+            return Err(vec![Felt::from_bytes_be_slice(
+                b"CONTRACT_ALREADY_DEPLOYED",
+            )]);
+        }
+
+        tracing::warn!("constructor not executed");
+        Ok((contract_address, vec![]))
     }
 
     #[instrument(skip(self))]
@@ -326,7 +770,10 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
-        tracing::warn!("unimplemented");
+        charge_gas(remaining_gas, self.gas_costs.replace_class)?;
+        self.state_diff
+            .replaced_classes
+            .insert(self.execution_info.contract_address, class_hash);
         Ok(())
     }
 
@@ -339,6 +786,10 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<Vec<Felt>> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.library_call)?;
+        if let Some(retdata) = self.mocked_calls.get(&(class_hash, function_selector)) {
+            return Ok(retdata.clone());
+        }
         tracing::warn!("unimplemented");
         Ok(vec![])
     }
@@ -352,6 +803,11 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<Vec<Felt>> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.call_contract)?;
+        self.check_execution_mode(address == self.execution_info.contract_address)?;
+        if let Some(retdata) = self.mocked_calls.get(&(address, entry_point_selector)) {
+            return Ok(retdata.clone());
+        }
         tracing::warn!("unimplemented");
         Ok(vec![])
     }
@@ -360,9 +816,10 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         address_domain: u32,
         address: Felt,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<Felt> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.storage_read)?;
         if let Some(value) = self.storage.get(&(address_domain, address)) {
             Ok(*value)
         } else {
@@ -379,7 +836,15 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.storage_write)?;
         self.storage.insert((address_domain, address), value);
+        if address_domain == 0 {
+            self.state_diff
+                .storage
+                .entry(self.execution_info.contract_address)
+                .or_default()
+                .insert(address, value);
+        }
         Ok(())
     }
 
@@ -391,11 +856,34 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
-        tracing::warn!("unimplemented but stored");
-        self.events.push(StubEvent {
+        charge_gas(remaining_gas, self.gas_costs.emit_event)?;
+        charge_gas(
+            remaining_gas,
+            cost_per_element(self.gas_costs.emit_event_key, keys.len())?,
+        )?;
+        charge_gas(
+            remaining_gas,
+            cost_per_element(self.gas_costs.emit_event_data, data.len())?,
+        )?;
+        let event = StubEvent {
+            keys: keys.to_vec(),
+            data: data.to_vec(),
+        };
+        let contract_address = self.execution_info.contract_address;
+        self.state_diff.events.push(OrderedEvent {
+            from_address: contract_address,
             keys: keys.to_vec(),
             data: data.to_vec(),
         });
+        if let Some(spied_events) = &mut self.spied_events {
+            spied_events.push((contract_address, event.clone()));
+        }
+        self.logs
+            .entry(contract_address)
+            .or_default()
+            .events
+            .push_back(event.clone());
+        self.events.push(event);
         Ok(())
     }
 
@@ -407,16 +895,30 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
-        tracing::warn!("unimplemented");
+        charge_gas(remaining_gas, self.gas_costs.send_message_to_l1)?;
+        self.state_diff
+            .l2_to_l1_messages
+            .push(OrderedL2ToL1Message {
+                from_address: self.execution_info.contract_address,
+                to_address,
+                payload: payload.to_vec(),
+            });
+        self.logs
+            .entry(self.execution_info.contract_address)
+            .or_default()
+            .l2_to_l1_messages
+            .push_back((to_address, payload.to_vec()));
         Ok(())
     }
 
     #[instrument(skip(self))]
-    fn keccak(&mut self, input: &[u64], gas: &mut u64) -> SyscallResult<U256> {
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
         const KECCAK_FULL_RATE_IN_WORDS: usize = 17;
 
+        charge_gas(remaining_gas, self.gas_costs.keccak)?;
+
         let length = input.len();
-        let (_n_rounds, remainder) = num_integer::div_rem(length, KECCAK_FULL_RATE_IN_WORDS);
+        let (n_rounds, remainder) = num_integer::div_rem(length, KECCAK_FULL_RATE_IN_WORDS);
 
         if remainder != 0 {
             // In VM this error is wrapped into `SyscallExecutionError::SyscallError`
@@ -426,6 +928,11 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
             .unwrap()]);
         }
 
+        charge_gas(
+            remaining_gas,
+            cost_per_element(self.gas_costs.keccak_round, n_rounds)?,
+        )?;
+
         let mut state = [0u64; 25];
         for chunk in input.chunks(KECCAK_FULL_RATE_IN_WORDS) {
             for (i, val) in chunk.iter().enumerate() {
@@ -445,8 +952,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         x: U256,
         y: U256,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Option<Secp256k1Point>> {
+        charge_gas(remaining_gas, self.gas_costs.secp256k1_new)?;
         Secp256Point::new(x, y).map(|op| op.map(|p| p.into()))
     }
 
@@ -455,9 +963,10 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         p0: Secp256k1Point,
         p1: Secp256k1Point,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Secp256k1Point> {
         tracing::debug!("called");
+        charge_gas(remaining_gas, self.gas_costs.secp256k1_add)?;
 
         Ok(Secp256Point::add(p0.into(), p1.into()).into())
     }
@@ -467,8 +976,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         p: Secp256k1Point,
         m: U256,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Secp256k1Point> {
+        charge_gas(remaining_gas, self.gas_costs.secp256k1_mul)?;
         Ok(Secp256Point::mul(p.into(), m).into())
     }
 
@@ -477,8 +987,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         x: U256,
         y_parity: bool,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Option<Secp256k1Point>> {
+        charge_gas(remaining_gas, self.gas_costs.secp256k1_get_point_from_x)?;
         Secp256Point::get_point_from_x(x, y_parity).map(|op| op.map(|p| p.into()))
     }
 
@@ -486,8 +997,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
     fn secp256k1_get_xy(
         &mut self,
         p: Secp256k1Point,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<(U256, U256)> {
+        charge_gas(remaining_gas, self.gas_costs.secp256k1_get_xy)?;
         Ok((p.x, p.y))
     }
 
@@ -496,8 +1008,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         x: U256,
         y: U256,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Option<Secp256r1Point>> {
+        charge_gas(remaining_gas, self.gas_costs.secp256r1_new)?;
         Secp256Point::new(x, y).map(|op| op.map(|p| p.into()))
     }
 
@@ -506,8 +1019,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         p0: Secp256r1Point,
         p1: Secp256r1Point,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Secp256r1Point> {
+        charge_gas(remaining_gas, self.gas_costs.secp256r1_add)?;
         Ok(Secp256Point::add(p0.into(), p1.into()).into())
     }
 
@@ -516,8 +1030,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         p: Secp256r1Point,
         m: U256,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Secp256r1Point> {
+        charge_gas(remaining_gas, self.gas_costs.secp256r1_mul)?;
         Ok(Secp256Point::mul(p.into(), m).into())
     }
 
@@ -526,8 +1041,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         &mut self,
         x: U256,
         y_parity: bool,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Option<Secp256r1Point>> {
+        charge_gas(remaining_gas, self.gas_costs.secp256r1_get_point_from_x)?;
         Secp256Point::get_point_from_x(x, y_parity).map(|op| op.map(|p| p.into()))
     }
 
@@ -535,8 +1051,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
     fn secp256r1_get_xy(
         &mut self,
         p: Secp256r1Point,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<(U256, U256)> {
+        charge_gas(remaining_gas, self.gas_costs.secp256r1_get_xy)?;
         Ok((p.x, p.y))
     }
 
@@ -551,93 +1068,21 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
             Err(_) => return Vec::new(),
         };
 
-        match selector {
-            "set_sequencer_address" => {
-                self.execution_info.block_info.sequencer_address = input[0];
-                vec![]
-            }
-            "set_caller_address" => {
-                self.execution_info.caller_address = input[0];
-                vec![]
-            }
-            "set_contract_address" => {
-                self.execution_info.contract_address = input[0];
-                vec![]
-            }
-            "set_account_contract_address" => {
-                self.execution_info.tx_info.account_contract_address = input[0];
-                vec![]
-            }
-            "set_transaction_hash" => {
-                self.execution_info.tx_info.transaction_hash = input[0];
-                vec![]
-            }
-            "set_nonce" => {
-                self.execution_info.tx_info.nonce = input[0];
-                vec![]
-            }
-            "set_version" => {
-                self.execution_info.tx_info.version = input[0];
-                vec![]
-            }
-            "set_chain_id" => {
-                self.execution_info.tx_info.chain_id = input[0];
-                vec![]
-            }
-            "set_max_fee" => {
-                let max_fee = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.tx_info.max_fee = max_fee;
-                vec![]
-            }
-            "set_block_number" => {
-                let block_number = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.block_info.block_number = block_number;
-                vec![]
-            }
-            "set_block_timestamp" => {
-                let block_timestamp = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.block_info.block_timestamp = block_timestamp;
-                vec![]
-            }
-            "set_signature" => {
-                self.execution_info.tx_info.signature = input.to_vec();
-                vec![]
-            }
-            "pop_log" => self
-                .logs
-                .get_mut(&input[0])
-                .and_then(|logs| logs.events.pop_front())
-                .map(|mut log| {
-                    let mut serialized_log = Vec::new();
-                    serialized_log.push(log.keys.len().into());
-                    serialized_log.append(&mut log.keys);
-                    serialized_log.push(log.data.len().into());
-                    serialized_log.append(&mut log.data);
-                    serialized_log
-                })
-                .unwrap_or_default(),
-            "pop_l2_to_l1_message" => self
-                .logs
-                .get_mut(&input[0])
-                .and_then(|logs| logs.l2_to_l1_messages.pop_front())
-                .map(|mut log| {
-                    let mut serialized_log = Vec::new();
-                    serialized_log.push(log.0);
-                    serialized_log.push(log.1.len().into());
-                    serialized_log.append(&mut log.1);
-                    serialized_log
-                })
-                .unwrap_or_default(),
-            _ => vec![],
-        }
+        let mut input = CheatcodeInput(input);
+        let Some(output) = self.run_cheatcode(selector, &mut input) else {
+            tracing::error!("invalid input for the `{selector}` cheatcode");
+            return vec![Felt::from_bytes_be_slice(b"invalid cheatcode input")];
+        };
+        output
     }
 
     fn sha256_process_block(
         &mut self,
         state: &mut [u32; 8],
         block: &[u32; 16],
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<()> {
+        charge_gas(remaining_gas, self.gas_costs.sha256_process_block)?;
         // reference impl
         // https://github.com/starkware-libs/cairo/blob/ba3f82b4a09972b6a24bf791e344cabce579bf69/crates/cairo-lang-runner/src/casm_run/mod.rs#L1292
         let data_as_bytes = sha2::digest::generic_array::GenericArray::from_exact_iter(
@@ -651,8 +1096,9 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
     fn get_class_hash_at(
         &mut self,
         contract_address: Felt,
-        _remaining_gas: &mut u64,
+        remaining_gas: &mut u64,
     ) -> SyscallResult<Felt> {
+        charge_gas(remaining_gas, self.gas_costs.get_class_hash_at)?;
         Ok(contract_address)
     }
 }
@@ -675,7 +1121,10 @@ mod tests {
             is_infinity: false,
         };
 
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         assert_eq!(
@@ -695,7 +1144,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_secp256k1_new() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 {
@@ -719,7 +1171,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_secp256k1_new_none() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 {
@@ -736,7 +1191,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_ssecp256k1_add() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let p1 = Secp256k1Point {
@@ -801,7 +1259,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_get_point_from_x_false_yparity() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         assert_eq!(
@@ -832,7 +1293,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_get_point_from_x_true_yparity() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         assert_eq!(
@@ -863,7 +1327,10 @@ mod tests {
 
     #[test]
     fn test_secp256k1_get_point_from_x_none() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         assert!(test_syscall_handler
@@ -874,7 +1341,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_new() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 {
@@ -901,7 +1371,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_new_infinity() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 { hi: 0, lo: 0 };
@@ -918,7 +1391,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_add() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let p1 = Secp256r1Point {
@@ -983,7 +1459,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_get_point_from_x_true_yparity() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 {
@@ -1011,7 +1490,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_get_point_from_x_false_yparity() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 {
@@ -1039,7 +1521,10 @@ mod tests {
 
     #[test]
     fn test_secp256r1_get_point_from_x_none() {
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         let x = U256 { lo: 0, hi: 10 };
@@ -1064,7 +1549,10 @@ mod tests {
             is_infinity: false,
         };
 
-        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
         let mut test_syscall_handler = &mut test_syscall_handler;
 
         assert_eq!(
@@ -1081,4 +1569,337 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn test_gas_costs() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let costs = SyscallGasCosts::default();
+        let mut gas = costs.storage_write + costs.keccak_round;
+
+        test_syscall_handler
+            .storage_write(0, 1.into(), 2.into(), &mut gas)
+            .unwrap();
+        assert_eq!(gas, costs.keccak_round);
+
+        test_syscall_handler.keccak(&[0; 17], &mut gas).unwrap();
+        assert_eq!(gas, 0);
+
+        assert_eq!(
+            test_syscall_handler.storage_read(0, 1.into(), &mut gas),
+            Err(vec![Felt::from_bytes_be_slice(b"Out of gas")])
+        );
+        assert_eq!(
+            test_syscall_handler.keccak(&[0; 17], &mut gas),
+            Err(vec![Felt::from_bytes_be_slice(b"Out of gas")])
+        );
+    }
+
+    #[test]
+    fn test_gas_costs_emit_event() {
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts {
+                emit_event_key: 10240,
+                emit_event_data: 5120,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let mut gas = u64::MAX;
+        test_syscall_handler
+            .emit_event(
+                &[1.into(), 2.into()],
+                &[3.into(), 4.into(), 5.into()],
+                &mut gas,
+            )
+            .unwrap();
+        assert_eq!(u64::MAX - gas, 1000 + 2 * 10240 + 3 * 5120);
+
+        // The default costs don't charge the keys and data.
+        test_syscall_handler.gas_costs = SyscallGasCosts::default();
+        let mut gas = u64::MAX;
+        test_syscall_handler
+            .emit_event(&[1.into()], &[2.into()], &mut gas)
+            .unwrap();
+        assert_eq!(u64::MAX - gas, 1000);
+    }
+
+    #[test]
+    fn test_gas_costs_overflow() {
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts {
+                emit_event_data: u64::MAX,
+                keccak_round: u64::MAX,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let mut gas = u64::MAX;
+        let error = test_syscall_handler
+            .emit_event(&[], &[1.into(), 2.into()], &mut gas)
+            .unwrap_err();
+        assert_eq!(error, vec![Felt::from_bytes_be_slice(b"Out of gas")]);
+
+        let mut gas = u64::MAX;
+        let error = test_syscall_handler.keccak(&[0; 34], &mut gas).unwrap_err();
+        assert_eq!(error, vec![Felt::from_bytes_be_slice(b"Out of gas")]);
+    }
+
+    #[test]
+    fn test_gas_costs_zero() {
+        let mut test_syscall_handler = StubSyscallHandler {
+            gas_costs: SyscallGasCosts::zero(),
+            ..Default::default()
+        };
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let mut gas = 0;
+        test_syscall_handler
+            .storage_write(0, 1.into(), 2.into(), &mut gas)
+            .unwrap();
+        test_syscall_handler.keccak(&[0; 34], &mut gas).unwrap();
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_logs() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+        let contract_address = test_syscall_handler.execution_info.contract_address;
+
+        let mut gas = u64::MAX;
+        test_syscall_handler
+            .emit_event(&[1.into()], &[2.into(), 3.into()], &mut gas)
+            .unwrap();
+        test_syscall_handler
+            .send_message_to_l1(4.into(), &[5.into()], &mut gas)
+            .unwrap();
+
+        assert_eq!(
+            test_syscall_handler
+                .cheatcode(Felt::from_bytes_be_slice(b"pop_log"), &[contract_address]),
+            vec![1.into(), 1.into(), 2.into(), 2.into(), 3.into()]
+        );
+        assert_eq!(
+            test_syscall_handler
+                .cheatcode(Felt::from_bytes_be_slice(b"pop_log"), &[contract_address]),
+            vec![]
+        );
+        assert_eq!(
+            test_syscall_handler.cheatcode(
+                Felt::from_bytes_be_slice(b"pop_l2_to_l1_message"),
+                &[contract_address]
+            ),
+            vec![4.into(), 1.into(), 5.into()]
+        );
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_mock_call() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        test_syscall_handler.cheatcode(
+            Felt::from_bytes_be_slice(b"mock_call"),
+            &[10.into(), 20.into(), 2.into(), 30.into(), 40.into()],
+        );
+
+        let mut gas = u64::MAX;
+        assert_eq!(
+            test_syscall_handler
+                .call_contract(10.into(), 20.into(), &[], &mut gas)
+                .unwrap(),
+            vec![30.into(), 40.into()]
+        );
+
+        test_syscall_handler.cheatcode(
+            Felt::from_bytes_be_slice(b"stop_mock_call"),
+            &[10.into(), 20.into()],
+        );
+        assert_eq!(
+            test_syscall_handler
+                .call_contract(10.into(), 20.into(), &[], &mut gas)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_invalid_input() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        // The return data is shorter than its length.
+        assert_eq!(
+            test_syscall_handler.cheatcode(
+                Felt::from_bytes_be_slice(b"mock_call"),
+                &[10.into(), 20.into(), 3.into(), 30.into()],
+            ),
+            vec![Felt::from_bytes_be_slice(b"invalid cheatcode input")]
+        );
+        assert!(test_syscall_handler.mocked_calls.is_empty());
+
+        assert_eq!(
+            test_syscall_handler
+                .cheatcode(Felt::from_bytes_be_slice(b"set_block_number"), &[Felt::MAX]),
+            vec![Felt::from_bytes_be_slice(b"invalid cheatcode input")]
+        );
+        assert_eq!(
+            test_syscall_handler.cheatcode(Felt::from_bytes_be_slice(b"set_caller_address"), &[]),
+            vec![Felt::from_bytes_be_slice(b"invalid cheatcode input")]
+        );
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_expect_revert() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        test_syscall_handler.cheatcode(
+            Felt::from_bytes_be_slice(b"expect_revert"),
+            &[1.into(), Felt::from_bytes_be_slice(b"error")],
+        );
+        assert_eq!(
+            test_syscall_handler.expected_revert,
+            Some(vec![Felt::from_bytes_be_slice(b"error")])
+        );
+
+        test_syscall_handler.cheatcode(Felt::from_bytes_be_slice(b"expect_revert"), &[0.into()]);
+        assert_eq!(test_syscall_handler.expected_revert, Some(vec![]));
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_spy_events() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let mut gas = u64::MAX;
+        test_syscall_handler
+            .emit_event(&[1.into()], &[], &mut gas)
+            .unwrap();
+        test_syscall_handler.cheatcode(Felt::from_bytes_be_slice(b"spy_events"), &[]);
+        test_syscall_handler.cheatcode(
+            Felt::from_bytes_be_slice(b"set_contract_address"),
+            &[7.into()],
+        );
+        test_syscall_handler
+            .emit_event(&[2.into()], &[3.into()], &mut gas)
+            .unwrap();
+
+        assert_eq!(
+            test_syscall_handler.cheatcode(Felt::from_bytes_be_slice(b"get_spied_events"), &[]),
+            vec![1.into(), 7.into(), 1.into(), 2.into(), 1.into(), 3.into()]
+        );
+        assert_eq!(
+            test_syscall_handler.cheatcode(Felt::from_bytes_be_slice(b"get_spied_events"), &[]),
+            vec![0.into()]
+        );
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_set_block_hash() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        test_syscall_handler.cheatcode(
+            Felt::from_bytes_be_slice(b"set_block_hash"),
+            &[5.into(), 1234.into()],
+        );
+
+        let mut gas = u64::MAX;
+        assert_eq!(
+            test_syscall_handler.get_block_hash(5, &mut gas).unwrap(),
+            1234.into()
+        );
+        assert_eq!(
+            test_syscall_handler.get_block_hash(6, &mut gas).unwrap(),
+            6.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_mode() {
+        let mut handler = StubSyscallHandler {
+            execution_mode: ExecutionMode::Validate,
+            ..Default::default()
+        };
+        handler.execution_info.block_info = BlockInfo {
+            block_number: 1234,
+            block_timestamp: 7300,
+            sequencer_address: 5.into(),
+        };
+        let contract_address = handler.execution_info.contract_address;
+        let mut handler = &mut handler;
+        let mut gas = 1_000_000;
+
+        let unauthorized = vec![Felt::from_bytes_be_slice(b"Unauthorized syscall")];
+        assert_eq!(
+            handler.get_block_hash(1, &mut gas),
+            Err(unauthorized.clone())
+        );
+        assert_eq!(
+            handler.call_contract(1.into(), 2.into(), &[], &mut gas),
+            Err(unauthorized)
+        );
+        assert_eq!(
+            handler.call_contract(contract_address, 2.into(), &[], &mut gas),
+            Ok(vec![])
+        );
+        assert_eq!(
+            handler.get_execution_info_v2(&mut gas).unwrap().block_info,
+            BlockInfo {
+                block_number: 1200,
+                block_timestamp: 7200,
+                sequencer_address: Felt::ZERO,
+            }
+        );
+
+        handler.execution_mode = ExecutionMode::Execute;
+        assert_eq!(handler.get_block_hash(1, &mut gas), Ok(1.into()));
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let contract_address = test_syscall_handler.execution_info.contract_address;
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let mut gas = u64::MAX;
+        test_syscall_handler
+            .storage_write(0, 1.into(), 2.into(), &mut gas)
+            .unwrap();
+        test_syscall_handler
+            .storage_write(0, 1.into(), 3.into(), &mut gas)
+            .unwrap();
+        test_syscall_handler
+            .replace_class(4.into(), &mut gas)
+            .unwrap();
+        let (deployed_address, _) = test_syscall_handler
+            .deploy(5.into(), 6.into(), &[], false, &mut gas)
+            .unwrap();
+        test_syscall_handler
+            .deploy(5.into(), 6.into(), &[], false, &mut gas)
+            .unwrap_err();
+
+        assert_eq!(
+            test_syscall_handler.state_diff,
+            StateDiff {
+                storage: [(contract_address, [(1.into(), 3.into())].into())].into(),
+                deployed_contracts: [(deployed_address, 5.into())].into(),
+                replaced_classes: [(contract_address, 4.into())].into(),
+                events: vec![],
+                l2_to_l1_messages: vec![],
+            }
+        );
+    }
 }
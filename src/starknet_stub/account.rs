@@ -0,0 +1,350 @@
+//! # Account transaction simulation
+//!
+//! The [`AccountSimulator`] runs invoke transactions through an account contract the same way the
+//! sequencer does:
+//!
+//! 1. The transaction's nonce is checked against the account's current nonce.
+//! 2. `__validate__` is run in [validate mode](ExecutionMode::Validate), where some syscalls are
+//!    forbidden. If it fails or doesn't return `VALID`, the transaction is rejected and the changes
+//!    made by `__validate__` are discarded.
+//! 3. The account's nonce is incremented.
+//! 4. `__execute__` is run normally. If it fails, the transaction is reverted: the changes it made
+//!    to the syscall handler (storage, events, logs, state diff...) are discarded, but the nonce is
+//!    still incremented.
+//!
+//! Both phases see the transaction's info (signature, resource bounds, nonce...) through the
+//! `get_execution_info` syscall. The gas consumed by both phases is reported together.
+
+use super::{ExecutionMode, StubSyscallHandler};
+use crate::{
+    error::{Error, Result},
+    execution_result::ContractExecutionResult,
+    executor::AotContractExecutor,
+    starknet::{ResourceBounds, TxV2Info},
+};
+use cairo_lang_starknet_classes::keccak::starknet_keccak;
+use starknet_types_core::felt::Felt;
+use std::collections::HashMap;
+
+/// An invoke transaction (version 3).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvokeTransaction {
+    pub sender_address: Felt,
+    /// The calldata of both `__validate__` and `__execute__`, usually a serialized `Array<Call>`.
+    pub calldata: Vec<Felt>,
+    pub signature: Vec<Felt>,
+    pub nonce: Felt,
+    pub transaction_hash: Felt,
+    pub resource_bounds: Vec<ResourceBounds>,
+    pub tip: u128,
+    pub paymaster_data: Vec<Felt>,
+    pub account_deployment_data: Vec<Felt>,
+}
+
+/// The outcome of an invoke transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvokeResult {
+    pub validate: ContractExecutionResult,
+    /// The result of `__execute__`, or `None` if the transaction was rejected during validation.
+    pub execute: Option<ContractExecutionResult>,
+    /// The gas consumed by both phases.
+    pub gas_consumed: u64,
+}
+
+impl InvokeResult {
+    /// Whether the transaction passed the validation.
+    pub fn is_validated(&self) -> bool {
+        self.execute.is_some()
+    }
+
+    /// Whether the transaction was both validated and executed successfully.
+    pub fn is_success(&self) -> bool {
+        self.execute
+            .as_ref()
+            .is_some_and(|execute| !execute.failure_flag)
+    }
+}
+
+/// Please look at the [module level docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct AccountSimulator {
+    pub syscall_handler: StubSyscallHandler,
+    /// Nonces by account address. Missing nonces are zero.
+    pub nonces: HashMap<Felt, Felt>,
+}
+
+impl AccountSimulator {
+    pub fn new(syscall_handler: StubSyscallHandler) -> Self {
+        Self {
+            syscall_handler,
+            nonces: HashMap::new(),
+        }
+    }
+
+    pub fn nonce(&self, account_address: Felt) -> Felt {
+        self.nonces
+            .get(&account_address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Run an invoke transaction through the given account contract, with the given amount of
+    /// gas for both phases.
+    ///
+    /// Returns an error if the transaction's nonce doesn't match the account's, or if the account
+    /// doesn't have the `__validate__` or `__execute__` entry points.
+    pub fn simulate_invoke(
+        &mut self,
+        account: &AotContractExecutor,
+        transaction: &InvokeTransaction,
+        gas: u64,
+    ) -> Result<InvokeResult> {
+        let account_address = transaction.sender_address;
+        let expected_nonce = self.nonce(account_address);
+        if transaction.nonce != expected_nonce {
+            return Err(Error::InvalidNonce {
+                account: account_address,
+                expected: expected_nonce,
+                actual: transaction.nonce,
+            });
+        }
+
+        let execution_info = &mut self.syscall_handler.execution_info;
+        execution_info.caller_address = Felt::ZERO;
+        execution_info.contract_address = account_address;
+        execution_info.tx_info = TxV2Info {
+            version: Felt::THREE,
+            account_contract_address: account_address,
+            max_fee: 0,
+            signature: transaction.signature.clone(),
+            transaction_hash: transaction.transaction_hash,
+            chain_id: execution_info.tx_info.chain_id,
+            nonce: transaction.nonce,
+            resource_bounds: transaction.resource_bounds.clone(),
+            tip: transaction.tip,
+            paymaster_data: transaction.paymaster_data.clone(),
+            nonce_data_availability_mode: 0,
+            fee_data_availability_mode: 0,
+            account_deployment_data: transaction.account_deployment_data.clone(),
+        };
+
+        // Restored if the transaction is rejected, since rejected transactions leave no state.
+        let snapshot = self.syscall_handler.clone();
+        let validate = self.run_phase(
+            account,
+            b"__validate__",
+            ExecutionMode::Validate,
+            &transaction.calldata,
+            gas,
+        )?;
+        let validated = !validate.failure_flag
+            && validate.return_values == [Felt::from_bytes_be_slice(b"VALID")];
+        if !validated {
+            self.syscall_handler = snapshot;
+            return Ok(InvokeResult {
+                gas_consumed: gas - validate.remaining_gas,
+                validate,
+                execute: None,
+            });
+        }
+
+        self.nonces
+            .insert(account_address, expected_nonce + Felt::ONE);
+
+        // Restored if `__execute__` fails to revert its changes.
+        let snapshot = self.syscall_handler.clone();
+        let execute = self.run_phase(
+            account,
+            b"__execute__",
+            ExecutionMode::Execute,
+            &transaction.calldata,
+            validate.remaining_gas,
+        )?;
+        if execute.failure_flag {
+            self.syscall_handler = snapshot;
+        }
+
+        Ok(InvokeResult {
+            gas_consumed: gas - execute.remaining_gas,
+            validate,
+            execute: Some(execute),
+        })
+    }
+
+    fn run_phase(
+        &mut self,
+        account: &AotContractExecutor,
+        entry_point: &[u8],
+        execution_mode: ExecutionMode,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<ContractExecutionResult> {
+        let selector = Felt::from(&starknet_keccak(entry_point));
+        self.syscall_handler.execution_info.entry_point_selector = selector;
+        self.syscall_handler.execution_mode = execution_mode;
+
+        let result = account.run(selector, calldata, gas, None, &mut self.syscall_handler);
+        self.syscall_handler.execution_mode = ExecutionMode::Execute;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::test::load_starknet_contract, OptLevel};
+    use cairo_lang_starknet_classes::contract_class::version_id_from_serialized_sierra_program;
+
+    fn account() -> AotContractExecutor {
+        let (_, program) = load_starknet_contract! {
+            #[starknet::contract(account)]
+            mod account {
+                use core::num::traits::Zero;
+                use starknet::{
+                    account::Call, syscalls::{call_contract_syscall, storage_write_syscall},
+                    SyscallResultTrait,
+                };
+
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl AccountContractImpl of starknet::account::AccountContract<ContractState> {
+                    fn __validate_declare__(self: @ContractState, class_hash: felt252) -> felt252 {
+                        starknet::VALIDATED
+                    }
+
+                    fn __validate__(ref self: ContractState, calls: Array<Call>) -> felt252 {
+                        let signature = starknet::get_tx_info().unbox().signature;
+                        // Written before failing so that the rejection can be checked.
+                        storage_write_syscall(
+                            0, 3_felt252.try_into().unwrap(), signature.len().into(),
+                        )
+                            .unwrap_syscall();
+                        assert!(signature == array![42].span(), "INVALID_SIGNATURE");
+                        starknet::VALIDATED
+                    }
+
+                    fn __execute__(
+                        ref self: ContractState, mut calls: Array<Call>,
+                    ) -> Array<Span<felt252>> {
+                        assert!(starknet::get_caller_address().is_zero(), "INVALID_CALLER");
+                        // Written before failing so that the revert can be checked.
+                        storage_write_syscall(0, 1_felt252.try_into().unwrap(), 2)
+                            .unwrap_syscall();
+                        assert!(calls.len() > 0, "NO_CALLS");
+
+                        let mut result = ArrayTrait::new();
+                        loop {
+                            match calls.pop_front() {
+                                Option::Some(call) => {
+                                    let res = call_contract_syscall(
+                                        call.to, call.selector, call.calldata,
+                                    )
+                                        .unwrap_syscall();
+                                    result.append(res);
+                                },
+                                Option::None => { break; },
+                            };
+                        };
+                        result
+                    }
+                }
+            }
+        };
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&program.sierra_program).unwrap();
+        AotContractExecutor::new(
+            &program.extract_sierra_program().unwrap(),
+            &program.entry_points_by_type,
+            sierra_version,
+            OptLevel::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_simulate_invoke() {
+        let account = account();
+        let mut simulator = AccountSimulator::default();
+        let transaction = InvokeTransaction {
+            sender_address: Felt::from(0x100),
+            // A single call to 0x200, selector 0x300 and no calldata.
+            calldata: vec![Felt::ONE, Felt::from(0x200), Felt::from(0x300), Felt::ZERO],
+            signature: vec![Felt::from(42)],
+            ..Default::default()
+        };
+
+        let result = simulator
+            .simulate_invoke(&account, &transaction, u64::MAX)
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(
+            result.execute.unwrap().return_values,
+            [Felt::ONE, Felt::ZERO]
+        );
+        assert!(result.gas_consumed > 0);
+        assert_eq!(simulator.nonce(transaction.sender_address), Felt::ONE);
+
+        // Replaying the transaction fails because of its nonce.
+        assert!(matches!(
+            simulator.simulate_invoke(&account, &transaction, u64::MAX),
+            Err(Error::InvalidNonce { .. })
+        ));
+
+        // Invalid signatures are rejected without bumping the nonce.
+        let transaction = InvokeTransaction {
+            nonce: Felt::ONE,
+            signature: vec![],
+            ..transaction
+        };
+        let result = simulator
+            .simulate_invoke(&account, &transaction, u64::MAX)
+            .unwrap();
+        assert!(!result.is_validated());
+        assert!(result.validate.failure_flag);
+        assert_eq!(simulator.nonce(transaction.sender_address), Felt::ONE);
+
+        // A failed execution is reverted, but still bumps the nonce.
+        let storage = simulator.syscall_handler.storage.clone();
+        let state_diff = simulator.syscall_handler.state_diff.clone();
+        let transaction = InvokeTransaction {
+            calldata: vec![Felt::ZERO],
+            signature: vec![Felt::from(42)],
+            ..transaction
+        };
+        let result = simulator
+            .simulate_invoke(&account, &transaction, u64::MAX)
+            .unwrap();
+        assert!(result.is_validated());
+        assert!(!result.is_success());
+        assert_eq!(simulator.syscall_handler.storage, storage);
+        assert_eq!(simulator.syscall_handler.state_diff, state_diff);
+        assert_eq!(simulator.nonce(transaction.sender_address), Felt::TWO);
+    }
+
+    #[test]
+    fn test_simulate_invoke_rejected() {
+        let account = account();
+        let mut simulator = AccountSimulator::default();
+        let transaction = InvokeTransaction {
+            sender_address: Felt::from(0x100),
+            calldata: vec![Felt::ZERO],
+            ..Default::default()
+        };
+
+        let result = simulator
+            .simulate_invoke(&account, &transaction, u64::MAX)
+            .unwrap();
+        assert!(!result.is_validated());
+
+        // The storage written by `__validate__` before failing is discarded.
+        assert!(simulator.syscall_handler.storage.is_empty());
+        assert_eq!(
+            simulator.syscall_handler.state_diff,
+            StubSyscallHandler::default().state_diff
+        );
+        assert_eq!(simulator.nonce(transaction.sender_address), Felt::ZERO);
+    }
+}
@@ -0,0 +1,533 @@
+//! # State snapshots
+//!
+//! A [`StateSnapshot`] holds the chain state needed to run transactions offline: storage, class
+//! hashes and nonces per contract, block info, block hashes and the classes themselves. Snapshots
+//! can be exported from a node (or a previous run) ahead of time.
+//!
+//! Only JSON snapshot files are supported for now. A binary format would need a new
+//! serialization dependency, and the snapshot types can still be (de)serialized with any serde
+//! format by the caller.
+//!
+//! The [`SnapshotSyscallHandler`] runs on top of a snapshot without modifying it. It builds on the
+//! [`StubSyscallHandler`]: every write goes to the stub's [`StateDiff`](super::state_diff::StateDiff),
+//! which acts as an overlay over the snapshot and can be exported as a snapshot of its own (see
+//! [`SnapshotSyscallHandler::overlay`]) or merged back (see [`StateSnapshot::apply`]).
+
+use super::{calculate_contract_address, charge_gas, StubSyscallHandler};
+use crate::{
+    error::Result,
+    starknet::{
+        class_hash::{compute_class_hash, compute_compiled_class_hash},
+        BlockInfo, ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point,
+        StarknetSyscallHandler, SyscallResult, U256,
+    },
+};
+use cairo_lang_starknet_classes::contract_class::ContractClass;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// The state of a chain at a given block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateSnapshot {
+    /// The block the transactions are executed in. The stub's defaults are kept when missing.
+    pub block_info: Option<BlockInfo>,
+    pub block_hashes: BTreeMap<u64, Felt>,
+    /// Storage values, by contract address and storage key. Missing keys read as zero.
+    pub storage: BTreeMap<Felt, BTreeMap<Felt, Felt>>,
+    /// Class hashes, by contract address.
+    pub class_hashes: BTreeMap<Felt, Felt>,
+    /// Nonces, by contract address. Missing nonces are zero.
+    pub nonces: BTreeMap<Felt, Felt>,
+    /// Compiled (CASM) class hashes, by class hash.
+    pub compiled_class_hashes: BTreeMap<Felt, Felt>,
+    /// Sierra classes, by class hash, ready to be compiled with
+    /// [`AotContractExecutor`](crate::executor::AotContractExecutor).
+    pub classes: BTreeMap<Felt, ContractClass>,
+}
+
+impl StateSnapshot {
+    /// Load a snapshot from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the snapshot into a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn storage_at(&self, contract_address: Felt, key: Felt) -> Felt {
+        self.storage
+            .get(&contract_address)
+            .and_then(|storage| storage.get(&key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn nonce(&self, contract_address: Felt) -> Felt {
+        self.nonces
+            .get(&contract_address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Return the class of the contract deployed at the given address, if both are known.
+    pub fn class_at(&self, contract_address: Felt) -> Option<&ContractClass> {
+        self.classes.get(self.class_hashes.get(&contract_address)?)
+    }
+
+    /// Add a class to the snapshot, computing both its class hash and its compiled class hash.
+    /// The ABI must be the one the class was declared with (see [`parse_contract_class`]).
+    /// Returns the class hash.
+    ///
+    /// [`parse_contract_class`]: crate::starknet::class_hash::parse_contract_class
+    pub fn declare(&mut self, contract_class: ContractClass, abi: &str) -> Result<Felt> {
+        let class_hash = compute_class_hash(&contract_class, abi);
+        let compiled_class_hash = compute_compiled_class_hash(&contract_class)?;
+
+        self.compiled_class_hashes
+            .insert(class_hash, compiled_class_hash);
+        self.classes.insert(class_hash, contract_class);
+        Ok(class_hash)
+    }
+
+    /// Merge another snapshot (usually an overlay) into this one. Values from `other` take
+    /// precedence.
+    pub fn apply(&mut self, other: &StateSnapshot) {
+        if other.block_info.is_some() {
+            self.block_info = other.block_info;
+        }
+        self.block_hashes.extend(&other.block_hashes);
+        for (contract_address, storage) in &other.storage {
+            self.storage
+                .entry(*contract_address)
+                .or_default()
+                .extend(storage);
+        }
+        self.class_hashes.extend(&other.class_hashes);
+        self.nonces.extend(&other.nonces);
+        self.compiled_class_hashes
+            .extend(&other.compiled_class_hashes);
+        self.classes.extend(
+            other
+                .classes
+                .iter()
+                .map(|(class_hash, class)| (*class_hash, class.clone())),
+        );
+    }
+}
+
+/// A syscall handler which reads the state from a [`StateSnapshot`] and writes into an overlay.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug, Clone)]
+pub struct SnapshotSyscallHandler {
+    pub snapshot: StateSnapshot,
+    /// The handler for everything not backed by the snapshot. Its state diff holds the overlay.
+    pub stub: StubSyscallHandler,
+    /// Nonces changed on top of the snapshot.
+    pub nonces: BTreeMap<Felt, Felt>,
+}
+
+impl SnapshotSyscallHandler {
+    pub fn new(snapshot: StateSnapshot) -> Self {
+        let mut stub = StubSyscallHandler::default();
+        if let Some(block_info) = snapshot.block_info {
+            stub.execution_info.block_info = block_info;
+        }
+
+        Self {
+            snapshot,
+            stub,
+            nonces: BTreeMap::new(),
+        }
+    }
+
+    /// Load the snapshot from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(StateSnapshot::load(path)?))
+    }
+
+    pub fn storage_at(&self, contract_address: Felt, key: Felt) -> Felt {
+        self.stub
+            .state_diff
+            .storage
+            .get(&contract_address)
+            .and_then(|storage| storage.get(&key))
+            .copied()
+            .unwrap_or_else(|| self.snapshot.storage_at(contract_address, key))
+    }
+
+    /// Return the class hash of the contract at the given address, or zero if there's none.
+    pub fn class_hash_at(&self, contract_address: Felt) -> Felt {
+        let state_diff = &self.stub.state_diff;
+        state_diff
+            .replaced_classes
+            .get(&contract_address)
+            .or_else(|| state_diff.deployed_contracts.get(&contract_address))
+            .or_else(|| self.snapshot.class_hashes.get(&contract_address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn nonce(&self, contract_address: Felt) -> Felt {
+        self.nonces
+            .get(&contract_address)
+            .copied()
+            .unwrap_or_else(|| self.snapshot.nonce(contract_address))
+    }
+
+    pub fn increment_nonce(&mut self, contract_address: Felt) {
+        let nonce = self.nonce(contract_address) + Felt::ONE;
+        self.nonces.insert(contract_address, nonce);
+    }
+
+    /// Return the changes performed on top of the snapshot.
+    pub fn overlay(&self) -> StateSnapshot {
+        let state_diff = &self.stub.state_diff;
+        StateSnapshot {
+            storage: state_diff.storage.clone(),
+            class_hashes: state_diff
+                .deployed_contracts
+                .iter()
+                .chain(&state_diff.replaced_classes)
+                .map(|(contract_address, class_hash)| (*contract_address, *class_hash))
+                .collect(),
+            nonces: self.nonces.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Return the snapshot with the overlay applied.
+    pub fn into_snapshot(self) -> StateSnapshot {
+        let overlay = self.overlay();
+        let mut snapshot = self.snapshot;
+        snapshot.apply(&overlay);
+        snapshot
+    }
+}
+
+impl StarknetSyscallHandler for &mut SnapshotSyscallHandler {
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        match self.snapshot.block_hashes.get(&block_number) {
+            Some(block_hash) if !self.stub.block_hashes.contains_key(&block_number) => {
+                charge_gas(remaining_gas, self.stub.gas_costs.get_block_hash)?;
+                Ok(*block_hash)
+            }
+            _ => (&mut self.stub).get_block_hash(block_number, remaining_gas),
+        }
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        (&mut self.stub).get_execution_info(remaining_gas)
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        (&mut self.stub).get_execution_info_v2(remaining_gas)
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        let deployer_address = if deploy_from_zero {
+            Felt::ZERO
+        } else {
+            self.stub.execution_info.contract_address
+        };
+        let contract_address = calculate_contract_address(
+            contract_address_salt,
+            class_hash,
+            calldata,
+            deployer_address,
+        );
+
+        if self.snapshot.class_hashes.contains_key(&contract_address) {
+            charge_gas(remaining_gas, self.stub.gas_costs.deploy)?;
+            return Err(vec![Felt::from_bytes_be_slice(
+                b"CONTRACT_ALREADY_DEPLOYED",
+            )]);
+        }
+
+        (&mut self.stub).deploy(
+            class_hash,
+            contract_address_salt,
+            calldata,
+            deploy_from_zero,
+            remaining_gas,
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        (&mut self.stub).replace_class(class_hash, remaining_gas)
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        (&mut self.stub).library_call(class_hash, function_selector, calldata, remaining_gas)
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        (&mut self.stub).call_contract(address, entry_point_selector, calldata, remaining_gas)
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        if address_domain != 0 {
+            return (&mut self.stub).storage_read(address_domain, address, remaining_gas);
+        }
+
+        charge_gas(remaining_gas, self.stub.gas_costs.storage_read)?;
+        Ok(self.storage_at(self.stub.execution_info.contract_address, address))
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        (&mut self.stub).storage_write(address_domain, address, value, remaining_gas)
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        (&mut self.stub).emit_event(keys, data, remaining_gas)
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        (&mut self.stub).send_message_to_l1(to_address, payload, remaining_gas)
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        (&mut self.stub).keccak(input, remaining_gas)
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        (&mut self.stub).secp256k1_new(x, y, remaining_gas)
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        (&mut self.stub).secp256k1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        (&mut self.stub).secp256k1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        (&mut self.stub).secp256k1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        (&mut self.stub).secp256k1_get_xy(p, remaining_gas)
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        (&mut self.stub).secp256r1_new(x, y, remaining_gas)
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        (&mut self.stub).secp256r1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        (&mut self.stub).secp256r1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        (&mut self.stub).secp256r1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        (&mut self.stub).secp256r1_get_xy(p, remaining_gas)
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        (&mut self.stub).sha256_process_block(state, block, remaining_gas)
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        charge_gas(remaining_gas, self.stub.gas_costs.get_class_hash_at)?;
+        Ok(self.class_hash_at(contract_address))
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        (&mut self.stub).cheatcode(selector, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> StateSnapshot {
+        StateSnapshot {
+            block_info: Some(BlockInfo {
+                block_number: 100,
+                block_timestamp: 1000,
+                sequencer_address: 1.into(),
+            }),
+            block_hashes: [(90, 9.into())].into(),
+            storage: [(3.into(), [(1.into(), 10.into())].into())].into(),
+            class_hashes: [(3.into(), 5.into())].into(),
+            nonces: [(3.into(), 2.into())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_reads() {
+        let mut handler = SnapshotSyscallHandler::new(snapshot());
+        let mut handler = &mut handler;
+        let mut gas = 100_000;
+
+        assert_eq!(handler.storage_read(0, 1.into(), &mut gas), Ok(10.into()));
+        assert_eq!(handler.storage_read(0, 2.into(), &mut gas), Ok(Felt::ZERO));
+        assert_eq!(handler.get_class_hash_at(3.into(), &mut gas), Ok(5.into()));
+        assert_eq!(handler.get_block_hash(90, &mut gas), Ok(9.into()));
+        assert_eq!(
+            handler.get_execution_info_v2(&mut gas).unwrap().block_info,
+            snapshot().block_info.unwrap()
+        );
+        assert_eq!(handler.nonce(3.into()), 2.into());
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut handler = SnapshotSyscallHandler::new(snapshot());
+        let mut gas = 100_000;
+
+        (&mut handler)
+            .storage_write(0, 1.into(), 11.into(), &mut gas)
+            .unwrap();
+        (&mut handler).replace_class(6.into(), &mut gas).unwrap();
+        handler.increment_nonce(3.into());
+
+        assert_eq!(
+            (&mut handler).storage_read(0, 1.into(), &mut gas),
+            Ok(11.into())
+        );
+        assert_eq!(handler.snapshot, snapshot());
+        assert_eq!(
+            handler.overlay(),
+            StateSnapshot {
+                storage: [(3.into(), [(1.into(), 11.into())].into())].into(),
+                class_hashes: [(3.into(), 6.into())].into(),
+                nonces: [(3.into(), 3.into())].into(),
+                ..Default::default()
+            }
+        );
+
+        let mut expected = snapshot();
+        expected
+            .storage
+            .get_mut(&3.into())
+            .unwrap()
+            .insert(1.into(), 11.into());
+        expected.class_hashes.insert(3.into(), 6.into());
+        expected.nonces.insert(3.into(), 3.into());
+        assert_eq!(handler.into_snapshot(), expected);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = tempfile::NamedTempFile::new().unwrap();
+        snapshot().save(path.path()).unwrap();
+        assert_eq!(StateSnapshot::load(path.path()).unwrap(), snapshot());
+    }
+}
@@ -0,0 +1,303 @@
+//! # State diffs
+//!
+//! A [`StateDiff`] holds the state changes performed by an execution through the
+//! [`StubSyscallHandler`](super::StubSyscallHandler): storage writes, deployed contracts, replaced
+//! classes, emitted events and L2 to L1 messages. It can be exported as JSON and compared against
+//! a reference diff (for example, one generated from a Cairo VM execution).
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+/// An event emitted during the execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderedEvent {
+    pub from_address: Felt,
+    pub keys: Vec<Felt>,
+    pub data: Vec<Felt>,
+}
+
+/// A message sent to L1 during the execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderedL2ToL1Message {
+    pub from_address: Felt,
+    pub to_address: Felt,
+    pub payload: Vec<Felt>,
+}
+
+/// The state changes performed by an execution.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    /// Storage writes, by contract address and storage key. Only the last value written to each
+    /// key is kept.
+    pub storage: BTreeMap<Felt, BTreeMap<Felt, Felt>>,
+    /// Class hashes of the deployed contracts, by contract address.
+    pub deployed_contracts: BTreeMap<Felt, Felt>,
+    /// New class hashes of the contracts whose class was replaced, by contract address.
+    pub replaced_classes: BTreeMap<Felt, Felt>,
+    pub events: Vec<OrderedEvent>,
+    pub l2_to_l1_messages: Vec<OrderedL2ToL1Message>,
+}
+
+/// A difference between two state diffs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateDiffMismatch {
+    Storage {
+        contract_address: Felt,
+        key: Felt,
+        expected: Option<Felt>,
+        actual: Option<Felt>,
+    },
+    DeployedContract {
+        contract_address: Felt,
+        expected: Option<Felt>,
+        actual: Option<Felt>,
+    },
+    ReplacedClass {
+        contract_address: Felt,
+        expected: Option<Felt>,
+        actual: Option<Felt>,
+    },
+    Event {
+        index: usize,
+        expected: Option<OrderedEvent>,
+        actual: Option<OrderedEvent>,
+    },
+    L2ToL1Message {
+        index: usize,
+        expected: Option<OrderedL2ToL1Message>,
+        actual: Option<OrderedL2ToL1Message>,
+    },
+}
+
+impl fmt::Display for StateDiffMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateDiffMismatch::Storage {
+                contract_address,
+                key,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "storage of {contract_address} at {key}: expected {expected:?}, got {actual:?}"
+            ),
+            StateDiffMismatch::DeployedContract {
+                contract_address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "class hash of deployed contract {contract_address}: expected {expected:?}, got {actual:?}"
+            ),
+            StateDiffMismatch::ReplacedClass {
+                contract_address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "replaced class of {contract_address}: expected {expected:?}, got {actual:?}"
+            ),
+            StateDiffMismatch::Event {
+                index,
+                expected,
+                actual,
+            } => write!(f, "event #{index}: expected {expected:?}, got {actual:?}"),
+            StateDiffMismatch::L2ToL1Message {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "l2 to l1 message #{index}: expected {expected:?}, got {actual:?}"
+            ),
+        }
+    }
+}
+
+impl StateDiff {
+    /// Load a state diff from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the state diff into a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+            && self.deployed_contracts.is_empty()
+            && self.replaced_classes.is_empty()
+            && self.events.is_empty()
+            && self.l2_to_l1_messages.is_empty()
+    }
+
+    /// Compare this state diff against a reference one, returning every difference found. An empty
+    /// result means both diffs are equal.
+    pub fn compare(&self, reference: &StateDiff) -> Vec<StateDiffMismatch> {
+        let mut mismatches = Vec::new();
+
+        let mut contract_addresses = self
+            .storage
+            .keys()
+            .chain(reference.storage.keys())
+            .collect::<Vec<_>>();
+        contract_addresses.sort();
+        contract_addresses.dedup();
+
+        let no_storage = BTreeMap::new();
+        for contract_address in contract_addresses {
+            let expected = reference
+                .storage
+                .get(contract_address)
+                .unwrap_or(&no_storage);
+            let actual = self.storage.get(contract_address).unwrap_or(&no_storage);
+
+            mismatches.extend(diff_maps(expected, actual).map(|(key, expected, actual)| {
+                StateDiffMismatch::Storage {
+                    contract_address: *contract_address,
+                    key,
+                    expected,
+                    actual,
+                }
+            }));
+        }
+
+        mismatches.extend(
+            diff_maps(&reference.deployed_contracts, &self.deployed_contracts).map(
+                |(contract_address, expected, actual)| StateDiffMismatch::DeployedContract {
+                    contract_address,
+                    expected,
+                    actual,
+                },
+            ),
+        );
+        mismatches.extend(
+            diff_maps(&reference.replaced_classes, &self.replaced_classes).map(
+                |(contract_address, expected, actual)| StateDiffMismatch::ReplacedClass {
+                    contract_address,
+                    expected,
+                    actual,
+                },
+            ),
+        );
+        mismatches.extend(diff_lists(&reference.events, &self.events).map(
+            |(index, expected, actual)| StateDiffMismatch::Event {
+                index,
+                expected,
+                actual,
+            },
+        ));
+        mismatches.extend(
+            diff_lists(&reference.l2_to_l1_messages, &self.l2_to_l1_messages).map(
+                |(index, expected, actual)| StateDiffMismatch::L2ToL1Message {
+                    index,
+                    expected,
+                    actual,
+                },
+            ),
+        );
+
+        mismatches
+    }
+}
+
+/// Return the keys whose values differ between both maps, along with both values.
+fn diff_maps<'a>(
+    expected: &'a BTreeMap<Felt, Felt>,
+    actual: &'a BTreeMap<Felt, Felt>,
+) -> impl 'a + Iterator<Item = (Felt, Option<Felt>, Option<Felt>)> {
+    let mut keys = expected.keys().chain(actual.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter().filter_map(|key| {
+        let (expected, actual) = (expected.get(key).copied(), actual.get(key).copied());
+        (expected != actual).then_some((*key, expected, actual))
+    })
+}
+
+/// Return the indices whose elements differ between both lists, along with both elements.
+fn diff_lists<'a, T: Clone + PartialEq>(
+    expected: &'a [T],
+    actual: &'a [T],
+) -> impl 'a + Iterator<Item = (usize, Option<T>, Option<T>)> {
+    (0..expected.len().max(actual.len())).filter_map(|index| {
+        let (expected, actual) = (expected.get(index), actual.get(index));
+        (expected != actual).then(|| (index, expected.cloned(), actual.cloned()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_diff() -> StateDiff {
+        StateDiff {
+            storage: [(1.into(), [(2.into(), 3.into())].into())].into(),
+            deployed_contracts: [(4.into(), 5.into())].into(),
+            replaced_classes: BTreeMap::new(),
+            events: vec![OrderedEvent {
+                from_address: 1.into(),
+                keys: vec![6.into()],
+                data: vec![],
+            }],
+            l2_to_l1_messages: vec![],
+        }
+    }
+
+    #[test]
+    fn test_compare_equal() {
+        assert!(state_diff().compare(&state_diff()).is_empty());
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut actual = state_diff();
+        actual
+            .storage
+            .get_mut(&1.into())
+            .unwrap()
+            .insert(2.into(), 7.into());
+        actual
+            .storage
+            .insert(8.into(), [(9.into(), 10.into())].into());
+        actual.events.clear();
+
+        assert_eq!(
+            actual.compare(&state_diff()),
+            [
+                StateDiffMismatch::Storage {
+                    contract_address: 1.into(),
+                    key: 2.into(),
+                    expected: Some(3.into()),
+                    actual: Some(7.into()),
+                },
+                StateDiffMismatch::Storage {
+                    contract_address: 8.into(),
+                    key: 9.into(),
+                    expected: None,
+                    actual: Some(10.into()),
+                },
+                StateDiffMismatch::Event {
+                    index: 0,
+                    expected: state_diff().events.pop(),
+                    actual: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialization() {
+        let serialized = serde_json::to_string(&state_diff()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<StateDiff>(&serialized).unwrap(),
+            state_diff()
+        );
+    }
+}
@@ -278,13 +278,16 @@ pub fn felt252_short_str(value: &str) -> Felt {
 }
 
 /// Creates the execution engine, with all symbols registered.
+///
+/// The object dump is required to extract the JIT-compiled functions' symbols.
 pub fn create_engine(
     module: &Module,
     _metadata: &MetadataStorage,
     opt_level: OptLevel,
+    enable_object_dump: bool,
 ) -> ExecutionEngine {
     // Create the JIT engine.
-    let engine = ExecutionEngine::new(module, opt_level.into(), &[], false);
+    let engine = ExecutionEngine::new(module, opt_level.into(), &[], enable_object_dump);
     #[cfg(feature = "with-debug-utils")]
     _metadata
         .get::<crate::metadata::debug_utils::DebugUtils>()
@@ -409,8 +412,8 @@ pub fn layout_repeat(layout: &Layout, n: usize) -> Result<(Layout, usize), Layou
 #[cfg(test)]
 pub mod test {
     use crate::{
-        context::NativeContext, execution_result::ExecutionResult, executor::JitNativeExecutor,
-        starknet_stub::StubSyscallHandler, utils::*, values::Value,
+        context::NativeContext, debug::DebugOptions, execution_result::ExecutionResult,
+        executor::JitNativeExecutor, starknet_stub::StubSyscallHandler, utils::*, values::Value,
     };
     use cairo_lang_compiler::{
         compile_prepared_db, db::RootDatabase, diagnostics::DiagnosticsReporter,
@@ -581,20 +584,35 @@ pub mod test {
         entry_point: &str,
         args: &[Value],
     ) -> ExecutionResult {
-        let entry_point = format!("{0}::{0}::{1}", program.0, entry_point);
-        let program = &program.1;
+        run_program_with_debug_options(program, entry_point, args, DebugOptions::new())
+    }
+
+    /// Returns the function of a test program given its name within the program's module.
+    pub fn find_test_function<'a>(
+        program: &'a (String, Program),
+        function_name: &str,
+    ) -> &'a GenFunction<StatementIdx> {
+        find_entry_point(
+            &program.1,
+            &format!("{0}::{0}::{1}", program.0, function_name),
+        )
+        .expect("Test program function not found.")
+    }
 
-        let entry_point_id = &program
-            .funcs
-            .iter()
-            .find(|x| x.id.debug_name.as_deref() == Some(&entry_point))
-            .expect("Test program entry point not found.")
-            .id;
+    /// Like [`run_program`], but compiling the program with the given debug options.
+    pub fn run_program_with_debug_options(
+        program: &(String, Program),
+        entry_point: &str,
+        args: &[Value],
+        debug_options: DebugOptions,
+    ) -> ExecutionResult {
+        let entry_point_id = &find_test_function(program, entry_point).id;
+        let program = &program.1;
 
         let context = NativeContext::new();
 
         let module = context
-            .compile(program, false, Some(Default::default()))
+            .compile_with_debug_options(program, false, Some(Default::default()), debug_options)
             .expect("Could not compile test program to MLIR.");
 
         let executor = JitNativeExecutor::from_native_module(module, OptLevel::Less).unwrap();